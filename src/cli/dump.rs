@@ -0,0 +1,81 @@
+use sw_miconlang::microcontroller::{
+    Component, ComponentData as _, LinkIndex, LinkNode, Node, PositionedMicrocontroller, StableId,
+    UnpositionedMicrocontroller,
+};
+
+// リンク元を n0 (ノード) や c3.1 (コンポーネントの出力) の形式で表示する
+fn link_name(index: &LinkIndex, link: &Option<LinkNode>) -> String {
+    let Some(link) = link else {
+        return "-".to_owned();
+    };
+    match (index.get(link), link) {
+        (Some(StableId::Node(i)), _) => format!("n{}", i),
+        (Some(StableId::Component(i)), LinkNode::Component(_, port)) => format!("c{}.{}", i, port),
+        _ => "?".to_owned(),
+    }
+}
+
+fn node_line(i: usize, node: &Node, index: &LinkIndex) -> String {
+    let mut line = format!(
+        "n{} {:?} {} {:?} at ({}, {})",
+        i,
+        node.mode(),
+        node.node_type(),
+        node.label_owned(),
+        node.position().x,
+        node.position().z
+    );
+    if let Node::Output(n) = node {
+        line += &format!(" <- {}", link_name(index, n.borrow().input_link_node()));
+    }
+    line
+}
+
+fn component_line(i: usize, component: &Component, index: &LinkIndex) -> String {
+    let inputs = component
+        .input_links_node()
+        .into_iter()
+        .map(|l| link_name(index, l))
+        .collect::<Vec<_>>();
+    format!("c{} {} <- ({})", i, component, inputs.join(", "))
+}
+
+pub(super) fn graph(name: &str, mc: &UnpositionedMicrocontroller) {
+    let index = LinkIndex::new(&mc.nodes, &mc.components);
+
+    println!("microcontroller {} ({}x{})", name, mc.width, mc.length);
+    for (i, node) in mc.nodes.iter().enumerate() {
+        println!("  {}", node_line(i, node, &index));
+    }
+    for (i, component) in mc.components.iter().enumerate() {
+        println!("  {}", component_line(i, component, &index));
+    }
+}
+
+pub(super) fn layout(name: &str, mc: &PositionedMicrocontroller) {
+    let index = LinkIndex::new(
+        mc.nodes.iter().map(|n| &n.inner),
+        mc.components.iter().map(|c| &c.inner),
+    );
+
+    println!("microcontroller {} ({}x{})", name, mc.width, mc.length);
+    for (i, node) in mc.nodes.iter().enumerate() {
+        let pos = &node.component_position;
+        println!(
+            "  [{:4}, {:4}] h=2 {}",
+            pos.x,
+            pos.y,
+            node_line(i, node, &index)
+        );
+    }
+    for (i, component) in mc.components.iter().enumerate() {
+        let pos = &component.position;
+        println!(
+            "  [{:4}, {:4}] h={} {}",
+            pos.x,
+            pos.y,
+            component.height(),
+            component_line(i, component, &index)
+        );
+    }
+}
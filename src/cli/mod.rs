@@ -0,0 +1,441 @@
+mod dump;
+
+use sw_miconlang::{
+    Diagnostic, analyze, compile_str,
+    decompile::decompile,
+    format::format,
+    layout, lex,
+    microcontroller::{OptLevel, SignalValue, Simulator, UnpositionedMicrocontroller},
+    parse,
+    project::{self, BuildError, MANIFEST_FILE_NAME},
+    render::Scene,
+    to_dot, to_xml, write_xml, xml_schema,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read as _},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Compiler for Stormworks microcontrollers")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile a source file, or the project described by the manifest
+    Build {
+        /// Source file (`-` for stdin). Uses the manifest if omitted
+        file: Option<PathBuf>,
+        /// Output directory
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Path to the manifest
+        #[arg(long, default_value = MANIFEST_FILE_NAME)]
+        manifest: PathBuf,
+        /// Also write the XML into this directory, e.g. the game's microprocessor folder
+        #[arg(long, value_name = "DIR")]
+        export: Option<PathBuf>,
+        /// Rebuild whenever the sources or the manifest change
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// Analyze a source file without generating XML
+    Check {
+        /// Source file (`-` for stdin)
+        file: PathBuf,
+    },
+    /// Format source files in place
+    Fmt {
+        /// Source files (`-` to format stdin to stdout)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Fail if a file is not formatted instead of rewriting it
+        #[arg(long)]
+        check: bool,
+    },
+    /// Convert microcontroller XML back into source
+    Decompile {
+        /// Microcontroller XML (`-` for stdin)
+        file: PathBuf,
+        /// Identifier of the generated microcontroller
+        #[arg(long, default_value = "Decompiled")]
+        name: String,
+    },
+    /// Draw the laid out logic as SVG, one file per microcontroller
+    Render {
+        /// Source file or microcontroller XML (`-` for stdin)
+        file: PathBuf,
+        /// Output directory
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Run the compiled logic for some ticks and print the outputs
+    Simulate {
+        /// Source file (`-` for stdin)
+        file: PathBuf,
+        /// Value of an input node
+        #[arg(short, long = "input", value_name = "LABEL=VALUE")]
+        inputs: Vec<String>,
+        /// Number of ticks to run
+        #[arg(short, long, default_value_t = 1)]
+        ticks: u32,
+        /// Microcontroller to simulate when the file declares several
+        #[arg(short, long)]
+        microcontroller: Option<String>,
+    },
+    /// Print an intermediate representation
+    Dump {
+        /// Source file (`-` for stdin)
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        stage: Stage,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Stage {
+    Tokens,
+    Ast,
+    Graph,
+    Dot,
+    Layout,
+    Xml,
+}
+
+pub fn run() -> ExitCode {
+    let cli = Cli::parse();
+
+    let r = match cli.command {
+        Command::Build {
+            file,
+            output,
+            manifest,
+            export,
+            watch,
+        } => {
+            let options = project::BuildOptions {
+                out_dir: output,
+                export_dir: export,
+            };
+            match (file, watch) {
+                (Some(file), false) => build_file(&file, &options),
+                (None, false) => build_project(&manifest, &options),
+                (Some(file), true) => {
+                    if file == Path::new("-") {
+                        eprintln!("Cannot watch stdin");
+                        return ExitCode::FAILURE;
+                    }
+                    project::watch(
+                        || project::source_paths(&file),
+                        || report(build_file(&file, &options)),
+                    )
+                }
+                (None, true) => project::watch(
+                    || project::manifest_paths(&manifest),
+                    || report(build_project(&manifest, &options)),
+                ),
+            }
+        }
+        Command::Check { file } => check(&file),
+        Command::Fmt { files, check } => fmt(&files, check),
+        Command::Decompile { file, name } => decompile_file(&file, &name),
+        Command::Render { file, output } => render(&file, output.as_deref()),
+        Command::Simulate {
+            file,
+            inputs,
+            ticks,
+            microcontroller,
+        } => simulate(&file, &inputs, ticks, microcontroller.as_deref()),
+        Command::Dump { file, stage } => dump(&file, stage),
+    };
+
+    match r {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            err.print();
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// watch 中はエラーを表示して監視を続ける
+fn report(r: Result<(), BuildError>) {
+    if let Err(err) = r {
+        err.print();
+    }
+}
+
+// `-` は標準入力から読み込む
+fn read_source(path: &Path) -> Result<(String, String), BuildError> {
+    let read_err = |err| BuildError::Read {
+        path: path.to_owned(),
+        err,
+    };
+    if path == Path::new("-") {
+        let mut code = String::new();
+        io::stdin().read_to_string(&mut code).map_err(read_err)?;
+        Ok((code, "<stdin>".to_owned()))
+    } else {
+        let code = fs::read_to_string(path).map_err(read_err)?;
+        Ok((code, path.to_string_lossy().into_owned()))
+    }
+}
+
+fn compile_error(path: &Path) -> BuildError {
+    BuildError::Compile {
+        path: path.to_owned(),
+    }
+}
+
+// 診断を表示してビルドエラーにする
+fn print_diagnostics<T>(
+    r: Result<T, Vec<Diagnostic>>,
+    code: &str,
+    path: &Path,
+) -> Result<T, BuildError> {
+    r.map_err(|diagnostics| {
+        for d in &diagnostics {
+            d.print(code);
+        }
+        compile_error(path)
+    })
+}
+
+fn analyze_source(
+    code: &str,
+    filename: &str,
+    path: &Path,
+) -> Result<BTreeMap<String, UnpositionedMicrocontroller>, BuildError> {
+    let tree = print_diagnostics(parse(code, filename), code, path)?.print(code);
+    print_diagnostics(analyze(&tree, filename), code, path).map(|mcs| mcs.print(code))
+}
+
+fn build_project(manifest: &Path, options: &project::BuildOptions) -> Result<(), BuildError> {
+    let summary = project::build(manifest, options)?;
+    for source in &summary.diagnostics {
+        for d in &source.diagnostics {
+            d.print(&source.code);
+        }
+    }
+    for err in &summary.errors {
+        err.print();
+    }
+    for path in &summary.written {
+        eprintln!("Wrote {}", path.display());
+    }
+    eprintln!(
+        "Built {} file(s), {} up to date, {} failed",
+        summary.built, summary.up_to_date, summary.failed
+    );
+    if summary.failed > 0 {
+        return Err(compile_error(manifest));
+    }
+    Ok(())
+}
+
+fn build_file(path: &Path, options: &project::BuildOptions) -> Result<(), BuildError> {
+    let (code, filename) = read_source(path)?;
+    let xml_files =
+        print_diagnostics(compile_str(&code, &filename, OptLevel::None), &code, path)?.print(&code);
+
+    let output = options.out_dir.as_deref().unwrap_or(Path::new("."));
+    let dirs = std::iter::once(output).chain(options.export_dir.as_deref());
+    for dir in dirs {
+        fs::create_dir_all(dir).map_err(|err| BuildError::Write {
+            path: dir.to_owned(),
+            err,
+        })?;
+        for (name, content) in &xml_files {
+            let path = dir.join(format!("{}.xml", name));
+            if project::write_if_changed(&path, content)? {
+                eprintln!("Wrote {}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check(path: &Path) -> Result<(), BuildError> {
+    let (code, filename) = read_source(path)?;
+    analyze_source(&code, &filename, path)?;
+    Ok(())
+}
+
+fn fmt(paths: &[PathBuf], check: bool) -> Result<(), BuildError> {
+    let mut unformatted = Vec::new();
+    for path in paths {
+        let (code, filename) = read_source(path)?;
+        let tokens = print_diagnostics(lex(&code, &filename), &code, path)?;
+        let formatted = format(&code, &tokens);
+
+        if path == Path::new("-") {
+            print!("{}", formatted);
+        } else if formatted != code {
+            if check {
+                unformatted.push(path);
+            } else {
+                fs::write(path, formatted).map_err(|err| BuildError::Write {
+                    path: path.to_owned(),
+                    err,
+                })?;
+            }
+        }
+    }
+
+    if check && !unformatted.is_empty() {
+        for path in &unformatted {
+            eprintln!("{} is not formatted", path.display());
+        }
+        return Err(compile_error(unformatted[0]));
+    }
+    Ok(())
+}
+
+fn decompile_file(path: &Path, name: &str) -> Result<(), BuildError> {
+    let (content, _) = read_source(path)?;
+    let mp: xml_schema::Microprocessor =
+        quick_xml::de::from_str(&content).map_err(|err| BuildError::InvalidXml {
+            path: path.to_owned(),
+            err,
+        })?;
+    let code = decompile(&mp, name).map_err(|err| BuildError::Decompile {
+        path: path.to_owned(),
+        err,
+    })?;
+    print!("{}", code);
+    Ok(())
+}
+
+fn render(path: &Path, output: Option<&Path>) -> Result<(), BuildError> {
+    let (content, filename) = read_source(path)?;
+
+    // XML ならそのまま、ソースならコンパイルして配置する
+    let scenes = if path.extension().is_some_and(|ext| ext == "xml") {
+        let mp: xml_schema::Microprocessor =
+            quick_xml::de::from_str(&content).map_err(|err| BuildError::InvalidXml {
+                path: path.to_owned(),
+                err,
+            })?;
+        let name = path
+            .file_stem()
+            .map_or("microcontroller".into(), |s| s.to_string_lossy());
+        BTreeMap::from([(name.into_owned(), Scene::from(&mp))])
+    } else {
+        analyze_source(&content, &filename, path)?
+            .into_iter()
+            .map(|(name, mc)| (name, Scene::from(&layout(mc))))
+            .collect()
+    };
+
+    let dir = output.unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).map_err(|err| BuildError::Write {
+        path: dir.to_owned(),
+        err,
+    })?;
+    for (name, mut scene) in scenes {
+        // name フィールドがなければ識別子を表題にする
+        if scene.name.is_empty() {
+            scene.name = name.clone();
+        }
+        let path = dir.join(format!("{}.svg", name));
+        if project::write_if_changed(&path, &scene.to_svg())? {
+            eprintln!("Wrote {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn simulate(
+    path: &Path,
+    inputs: &[String],
+    ticks: u32,
+    microcontroller: Option<&str>,
+) -> Result<(), BuildError> {
+    let (code, filename) = read_source(path)?;
+    let mcs = analyze_source(&code, &filename, path)?;
+
+    let name = match microcontroller {
+        Some(name) => name.to_owned(),
+        None => mcs.keys().next().cloned().unwrap_or_default(),
+    };
+    let Some(mc) = mcs.get(&name) else {
+        eprintln!("Microcontroller `{}` does not exist in {}", name, filename);
+        return Err(compile_error(path));
+    };
+
+    let simulation_err = |err| BuildError::Simulation {
+        path: path.to_owned(),
+        err,
+    };
+    let mut simulator = Simulator::new(mc).map_err(simulation_err)?;
+    for input in inputs {
+        let invalid_input = || BuildError::InvalidInput {
+            input: input.clone(),
+        };
+        let (label, value) = input.rsplit_once('=').ok_or_else(invalid_input)?;
+        let value = match value.trim() {
+            "true" => SignalValue::Bool(true),
+            "false" => SignalValue::Bool(false),
+            v => SignalValue::Number(v.parse().map_err(|_| invalid_input())?),
+        };
+        simulator
+            .set_input(label.trim(), value)
+            .map_err(simulation_err)?;
+    }
+
+    for tick in 1..=ticks {
+        simulator.tick();
+        let outputs = simulator
+            .outputs()
+            .into_iter()
+            .map(|(label, value)| format!("{} = {}", label, value))
+            .collect::<Vec<_>>();
+        println!("tick {}: {}", tick, outputs.join(", "));
+    }
+    Ok(())
+}
+
+fn dump(path: &Path, stage: Stage) -> Result<(), BuildError> {
+    let (code, filename) = read_source(path)?;
+    match stage {
+        Stage::Tokens => {
+            let tokens = print_diagnostics(lex(&code, &filename), &code, path)?;
+            for (token, span) in tokens {
+                println!("{:?} @ {:?}", token, span);
+            }
+        }
+        Stage::Ast => {
+            let tree = print_diagnostics(parse(&code, &filename), &code, path)?.print(&code);
+            println!("{:#?}", tree);
+        }
+        Stage::Graph | Stage::Dot | Stage::Layout | Stage::Xml => {
+            let mcs = analyze_source(&code, &filename, path)?;
+            for (name, mc) in mcs {
+                match stage {
+                    Stage::Graph => dump::graph(&name, &mc),
+                    Stage::Dot => print!("{}", to_dot(&mc)),
+                    Stage::Layout => dump::layout(&name, &layout(mc)),
+                    _ => {
+                        let xml = to_xml(&layout(mc))
+                            .map_err(|err| err.to_string())
+                            .and_then(|mp| write_xml(&mp).map_err(|err| err.to_string()))
+                            .map_err(|message| BuildError::XmlGeneration {
+                                path: path.to_owned(),
+                                message,
+                            })?;
+                        print!("{}", xml);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,64 +1,219 @@
 use crate::{
-    compile_error::{CompileError, CompileErrorType},
-    lexical::tokenize,
+    compile_error::{CompileErrorType, Diagnostic, WithWarnings},
+    lexical::{Token, tokenize},
+    microcontroller::{
+        LayoutBounds, LayoutError, OptLevel, PositionedMicrocontroller, UnpositionedMicrocontroller,
+    },
+    render::{self, Scene},
     semantic::analyze_file,
-    syntax::parser,
-    xml_schema,
+    syntax::{self, Spanned, ambiguous_continuations, misplaced_doc_comments, parser},
+    xml_schema::{self, Microprocessor, conversion::MicroprocessorConversionError},
 };
 
-use ariadne::Source;
 use chumsky::{Parser, input::IterInput};
-use std::collections::HashMap;
+use std::{collections::BTreeMap, ops::Range, path::PathBuf};
 
-pub fn compile(code: &str, filename: &str, verbose: bool) -> Option<HashMap<String, String>> {
-    let len = code.len();
-
-    let cache = Source::from(code);
-
-    // 字句解析
-    let tokens = tokenize(code);
-    if verbose && let Err(errors) = &tokens {
-        for span in errors {
-            CompileError::new(filename, span.clone(), CompileErrorType::InvalidToken).print(&cache);
+// ソースコードからマイコン名ごとのXMLまでをまとめて行う
+pub fn compile_str(
+    code: &str,
+    filename: &str,
+    opt_level: OptLevel,
+) -> Result<WithWarnings<BTreeMap<String, String>>, Vec<Diagnostic>> {
+    let WithWarnings {
+        value: tree,
+        mut warnings,
+    } = parse(code, filename)?;
+    let mcs = match analyze(&tree, filename) {
+        Ok(mcs) => {
+            warnings.extend(mcs.warnings);
+            mcs.value
         }
-    }
-    let tokens = tokens.ok()?;
+        Err(errors) => return Err(errors.into_iter().chain(warnings).collect()),
+    };
 
-    // 構文解析
-    let tree = parser().parse(IterInput::new(tokens.into_iter(), len..len));
-    if verbose && tree.has_errors() {
-        for e in tree.errors() {
-            CompileError::new(
+    let mut xml_files = BTreeMap::new();
+    let mut diagnostics = Vec::new();
+    for (name, mc) in mcs {
+        let mc = layout(optimize(mc, opt_level));
+        match check_layout(&mc, filename, header_span(code, &tree, &name)) {
+            Ok(layout) => warnings.extend(layout.warnings),
+            Err(errors) => {
+                diagnostics.extend(errors);
+                continue;
+            }
+        }
+        // 解析器が作るグラフは常に変換できるはずだが、念のため診断にする
+        let xml = to_xml(&mc)
+            .map_err(|err| err.to_string())
+            .and_then(|mp| write_xml(&mp).map_err(|err| err.to_string()));
+        match xml {
+            Ok(xml) => {
+                xml_files.insert(name, xml);
+            }
+            Err(message) => diagnostics.push(Diagnostic::new(
                 filename,
-                e.span().clone(),
-                CompileErrorType::unexpected_token(e),
-            )
-            .print(&cache);
+                header_span(code, &tree, &name),
+                CompileErrorType::XmlGeneration { message },
+            )),
         }
     }
-    let tree = tree.into_output()?;
+    if diagnostics.is_empty() {
+        Ok(WithWarnings::new(xml_files, warnings))
+    } else {
+        diagnostics.extend(warnings);
+        Err(diagnostics)
+    }
+}
 
-    // 意味解析
-    let mcs = analyze_file(&tree, filename);
-    if verbose && mcs.has_errors() {
-        for e in mcs.errors() {
-            e.print(&cache);
-        }
+// マイコン定義の 1 行目
+fn header_span(code: &str, tree: &Spanned<syntax::File>, name: &str) -> Range<usize> {
+    let span = tree
+        .elements
+        .iter()
+        .find(|e| matches!(&e.inner, syntax::Element::Microcontroller { name: n, .. } if n == name))
+        .map_or(0..0, |e| e.span.clone());
+    let end = code[span.clone()]
+        .find('\n')
+        .map_or(span.end, |i| span.start + i);
+    span.start..end
+}
+
+// 字句解析
+pub fn lex(code: &str, filename: &str) -> Result<Vec<(Token, Range<usize>)>, Vec<Diagnostic>> {
+    tokenize(code).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|(error, span)| {
+                Diagnostic::new(filename, span, CompileErrorType::InvalidToken { error })
+            })
+            .collect()
+    })
+}
+
+// 構文解析
+// 前の行の式の続きとして読まれた行と、何にも付かないドキュメントコメントは警告する
+// 何にも付かないドキュメントコメントは普通のコメントとして読み飛ばす
+pub fn parse(
+    code: &str,
+    filename: &str,
+) -> Result<WithWarnings<Spanned<syntax::File>>, Vec<Diagnostic>> {
+    let len = code.len();
+    let mut tokens = lex(code, filename)?;
+    let misplaced = misplaced_doc_comments(&tokens);
+    tokens.retain(|(_, span)| !misplaced.contains(span));
+    let warnings = ambiguous_continuations(code, &tokens)
+        .into_iter()
+        .map(|span| Diagnostic::new(filename, span, CompileErrorType::AmbiguousContinuation))
+        .chain(
+            misplaced
+                .into_iter()
+                .map(|span| Diagnostic::new(filename, span, CompileErrorType::MisplacedDocComment)),
+        )
+        .collect::<Vec<_>>();
+
+    let (tree, errors) = parser()
+        .parse(IterInput::new(tokens.into_iter(), len..len))
+        .into_output_errors();
+    match tree {
+        Some(tree) if errors.is_empty() => Ok(WithWarnings::new(tree, warnings)),
+        _ => Err(errors
+            .iter()
+            .map(|e| {
+                Diagnostic::new(
+                    filename,
+                    e.span().clone(),
+                    CompileErrorType::unexpected_token(e),
+                )
+            })
+            .chain(warnings)
+            .collect()),
     }
-    let mcs = mcs.into_output()?;
+}
 
-    // XML生成
-    let mut xml_files = HashMap::new();
-    for (name, mc) in mcs {
-        let mc_struct = xml_schema::Microprocessor::try_from(&mc.auto_layout())
-            .expect("Unexpected Error: Generated microcontrooler is invalid");
+// 意味解析
+pub fn analyze(
+    tree: &Spanned<syntax::File>,
+    filename: &str,
+) -> Result<WithWarnings<BTreeMap<String, UnpositionedMicrocontroller>>, Vec<Diagnostic>> {
+    analyze_file(tree, filename).into_result()
+}
+
+// lua(file = ...) で参照しているファイル (構文エラーなら空)
+pub fn script_files(code: &str, filename: &str) -> Vec<PathBuf> {
+    match parse(code, filename) {
+        Ok(tree) => analyze_file(&tree.value, filename)
+            .scripts
+            .into_iter()
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// 最適化
+pub fn optimize(mc: UnpositionedMicrocontroller, level: OptLevel) -> UnpositionedMicrocontroller {
+    mc.optimize(level)
+}
 
-        let mut buf = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n".to_owned();
-        quick_xml::se::to_writer_with_root(&mut buf, "microprocessor", &mc_struct)
-            .expect("Unexpected Error: XML Serialization Error");
+// コンポーネントの配置
+pub fn layout(mc: UnpositionedMicrocontroller) -> PositionedMicrocontroller {
+    mc.auto_layout()
+}
 
-        xml_files.insert(name, buf);
+// 配置の検査
+// span は診断で指す位置 (マイコン定義など)
+// 重なりはエラー、範囲外は警告にする
+pub fn check_layout(
+    mc: &PositionedMicrocontroller,
+    filename: &str,
+    span: Range<usize>,
+) -> Result<WithWarnings<()>, Vec<Diagnostic>> {
+    let (warnings, errors): (Vec<_>, Vec<_>) = mc
+        .check_layout(&LayoutBounds::default())
+        .into_iter()
+        .map(|e| {
+            let error_type = match e {
+                LayoutError::Overlap(a, b) => CompileErrorType::ComponentsOverlap {
+                    first: mc.vertex_name(a),
+                    second: mc.vertex_name(b),
+                },
+                LayoutError::OutOfBounds(id) => CompileErrorType::OutOfCanvas {
+                    name: mc.vertex_name(id),
+                },
+            };
+            Diagnostic::new(filename, span.clone(), error_type)
+        })
+        .partition(|d| d.error_type.is_warning());
+    if errors.is_empty() {
+        Ok(WithWarnings::new((), warnings))
+    } else {
+        Err(errors.into_iter().chain(warnings).collect())
     }
+}
+
+// XML構造への変換
+// ビルダーで組んだグラフのリンク先が存在しなければエラー
+pub fn to_xml(
+    mc: &PositionedMicrocontroller,
+) -> Result<Microprocessor, MicroprocessorConversionError> {
+    Microprocessor::try_from(mc)
+}
+
+// 配置前のグラフのDOT形式への変換
+pub fn to_dot(mc: &UnpositionedMicrocontroller) -> String {
+    render::to_dot(mc)
+}
+
+// レビュー用のSVGへの変換
+pub fn to_svg(mc: &PositionedMicrocontroller) -> String {
+    Scene::from(mc).to_svg()
+}
 
-    Some(xml_files)
+// XML文字列への変換
+pub fn write_xml(mp: &Microprocessor) -> Result<String, quick_xml::SeError> {
+    let mut body = String::new();
+    quick_xml::se::to_writer_with_root(&mut body, "microprocessor", mp)?;
+    Ok(format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+        xml_schema::escape_whitespace(&body)
+    ))
 }
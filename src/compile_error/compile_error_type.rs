@@ -1,16 +1,22 @@
-use crate::{lexical::Token, microcontroller::NodeType, semantic::ValueType};
+use crate::{
+    lexical::{LexError, Token},
+    microcontroller::NodeType,
+    semantic::ValueType,
+};
 
-use ariadne::{Color, Label};
 use chumsky::error::RichPattern;
 use std::ops::{Range, RangeInclusive};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum CompileErrorType {
-    InvalidToken,
+    InvalidToken {
+        error: LexError,
+    },
     UnexpectedToken {
         expected: String,
         found: Option<Token>,
     },
+    MisplacedDocComment,
     UnknownField {
         ident: String,
     },
@@ -30,6 +36,10 @@ pub enum CompileErrorType {
     StringInLogic,
     FieldAccessOnly,
     OutputsInExpression,
+    // 関数の本体での inputs と outputs
+    InterfaceInFunction {
+        interface: &'static str,
+    },
     NodeDoesNotExist {
         component_str: String,
         index: usize,
@@ -41,6 +51,11 @@ pub enum CompileErrorType {
     UnknownName {
         name: String,
     },
+    UnknownFunction {
+        name: String,
+        // 呼び出せる関数の名前
+        available: Vec<String>,
+    },
     LengthMismatch {
         found_len: usize,
         expect_str: &'static str,
@@ -48,6 +63,126 @@ pub enum CompileErrorType {
     PropertyRequired {
         expect_str: &'static str,
     },
+    UnknownAttribute {
+        name: String,
+    },
+    AttributeOnNonComponent {
+        name: String,
+    },
+    ComponentsOverlap {
+        first: String,
+        second: String,
+    },
+    OutOfCanvas {
+        name: String,
+    },
+    XmlGeneration {
+        message: String,
+    },
+    UnknownBackend {
+        name: String,
+    },
+    UnsupportedInLua {
+        what: String,
+    },
+    TooManyChannels {
+        node_type: NodeType,
+        limit: usize,
+    },
+    InvalidLuaSource,
+    LuaFileNotReadable {
+        path: String,
+        message: String,
+    },
+    LuaScriptTooLong {
+        length: usize,
+        limit: usize,
+    },
+    UndeclaredLuaChannel {
+        function: &'static str,
+        channel: usize,
+        count: usize,
+    },
+    UnusedLuaChannel {
+        function: &'static str,
+        channel: usize,
+    },
+    LuaBlockOutputs {
+        count: usize,
+    },
+    NumberNodeOnly {
+        field: &'static str,
+    },
+    EmptyRange {
+        min: f32,
+        max: f32,
+    },
+    InvalidUnit {
+        unit: String,
+    },
+    UnitMismatch {
+        lhs: String,
+        rhs: String,
+    },
+    IncompatibleUnit {
+        expected: String,
+        found: String,
+    },
+    ScalarNodeOnly {
+        field: &'static str,
+    },
+    NotAnArray,
+    IndexRequired {
+        name: String,
+    },
+    UnknownParameter {
+        name: String,
+        signature: String,
+    },
+    DuplicateParameter {
+        name: String,
+        signature: String,
+    },
+    MissingParameter {
+        name: String,
+        signature: String,
+    },
+    TooManyArguments {
+        limit: usize,
+        count: usize,
+        signature: String,
+    },
+    PositionalAfterNamed,
+    ModuloByZero,
+    FunctionAlreadyDefined {
+        name: String,
+    },
+    RecursiveFunction {
+        name: String,
+    },
+    NonExhaustiveMatch,
+    ConstantAlreadyDefined {
+        name: String,
+    },
+    NotConstant,
+    MissingElement {
+        name: String,
+        index: usize,
+    },
+    // 以下は警告
+    AmbiguousContinuation,
+    UnreachableArm,
+    UnsupportedPattern {
+        pattern: String,
+    },
+    PossibleDivisionByZero {
+        divisor: String,
+    },
+    OutputOutOfRange {
+        name: String,
+        range: String,
+        found: String,
+    },
 }
 
 impl CompileErrorType {
@@ -73,10 +208,24 @@ impl CompileErrorType {
         }
     }
 
+    // 警告はコンパイルを止めない
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            Self::AmbiguousContinuation
+                | Self::MisplacedDocComment
+                | Self::UnreachableArm
+                | Self::PossibleDivisionByZero { .. }
+                | Self::OutputOutOfRange { .. }
+                | Self::OutOfCanvas { .. }
+        )
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
-            Self::InvalidToken => "Invalid Token",
+            Self::InvalidToken { error } => error.name(),
             Self::UnexpectedToken { .. } => "Unexpected Token",
+            Self::MisplacedDocComment => "Misplaced Doc Comment",
             Self::UnknownField { .. } => "Unknown Field",
             Self::InvalidAssignment => "Invalid Assignment",
             Self::IncompatibleType { .. } => "Incompatible Types",
@@ -87,101 +236,280 @@ impl CompileErrorType {
             Self::StringInLogic => "String in Logic",
             Self::FieldAccessOnly => "Field Access Only",
             Self::OutputsInExpression => "Outputs in Expression",
+            Self::InterfaceInFunction { .. } => "Interface in Function",
             Self::NodeDoesNotExist { .. } => "Node Does Not Exist",
             Self::IncompatibleNodeType { .. } => "Incompatible Node Type",
             Self::UnknownName { .. } => "Unknown Name",
+            Self::UnknownFunction { .. } => "Unknown Function",
             Self::LengthMismatch { .. } => "Length Mismatch",
             Self::PropertyRequired { .. } => "Property Required",
+            Self::UnknownAttribute { .. } => "Unknown Attribute",
+            Self::AttributeOnNonComponent { .. } => "Attribute on Non-Component",
+            Self::ComponentsOverlap { .. } => "Components Overlap",
+            Self::OutOfCanvas { .. } => "Out of Canvas",
+            Self::XmlGeneration { .. } => "XML Generation Failed",
+            Self::UnknownBackend { .. } => "Unknown Backend",
+            Self::UnsupportedInLua { .. } => "Unsupported in Lua",
+            Self::TooManyChannels { .. } => "Too Many Channels",
+            Self::InvalidLuaSource => "Invalid Lua Source",
+            Self::LuaFileNotReadable { .. } => "Lua File Not Readable",
+            Self::LuaScriptTooLong { .. } => "Lua Script Too Long",
+            Self::UndeclaredLuaChannel { .. } => "Undeclared Lua Channel",
+            Self::UnusedLuaChannel { .. } => "Unused Lua Channel",
+            Self::LuaBlockOutputs { .. } => "Lua Block Outputs",
+            Self::NumberNodeOnly { .. } => "Number Node Only",
+            Self::EmptyRange { .. } => "Empty Range",
+            Self::InvalidUnit { .. } => "Invalid Unit",
+            Self::UnitMismatch { .. } => "Unit Mismatch",
+            Self::IncompatibleUnit { .. } => "Incompatible Unit",
+            Self::ScalarNodeOnly { .. } => "Scalar Node Only",
+            Self::NotAnArray => "Not an Array",
+            Self::IndexRequired { .. } => "Index Required",
+            Self::UnknownParameter { .. } => "Unknown Parameter",
+            Self::DuplicateParameter { .. } => "Duplicate Parameter",
+            Self::MissingParameter { .. } => "Missing Parameter",
+            Self::TooManyArguments { .. } => "Too Many Arguments",
+            Self::PositionalAfterNamed => "Positional After Named",
+            Self::ModuloByZero => "Modulo by Zero",
+            Self::FunctionAlreadyDefined { .. } => "Function Already Defined",
+            Self::RecursiveFunction { .. } => "Recursive Function",
+            Self::NonExhaustiveMatch => "Non-Exhaustive Match",
+            Self::ConstantAlreadyDefined { .. } => "Constant Already Defined",
+            Self::NotConstant => "Not Constant",
+            Self::MissingElement { .. } => "Missing Element",
+            Self::AmbiguousContinuation => "Ambiguous Continuation",
+            Self::UnreachableArm => "Unreachable Arm",
+            Self::UnsupportedPattern { .. } => "Unsupported Pattern",
+            Self::PossibleDivisionByZero { .. } => "Possible Division by Zero",
+            Self::OutputOutOfRange { .. } => "Output Out of Range",
+        }
+    }
+
+    // 直し方の案内
+    pub fn help(&self) -> Option<String> {
+        match self {
+            Self::InvalidToken { error } => Some(error.help()),
+            Self::MisplacedDocComment => Some(
+                "Doc comments describe a `microcontroller`, its fields, interface nodes and their properties; use `//` elsewhere".into(),
+            ),
+            Self::ScalarNodeOnly { field: "position" } => {
+                Some("Elements of an array are placed automatically".into())
+            }
+            Self::UnknownParameter { signature, .. }
+            | Self::DuplicateParameter { signature, .. }
+            | Self::MissingParameter { signature, .. }
+            | Self::TooManyArguments { signature, .. } => Some(format!("Expected `{}`", signature)),
+            Self::UnknownFunction { available, .. } => Some(format!(
+                "Available functions are {}",
+                available
+                    .iter()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Self::InterfaceInFunction { interface: "inputs" } => {
+                Some("Pass the input to the function as an argument".into())
+            }
+            Self::InterfaceInFunction { .. } => {
+                Some("Return the value and assign it to the output where the function is called".into())
+            }
+            Self::RecursiveFunction { .. } => {
+                Some("Functions are expanded at each call, so they cannot call themselves".into())
+            }
+            Self::NonExhaustiveMatch => Some("Add a `_ => ...` arm for the other values".into()),
+            Self::UnsupportedPattern { .. } => Some(
+                "Patterns are numbers, number constants or `_`; there are no enums to match on"
+                    .into(),
+            ),
+            Self::NotConstant => Some(
+                "Only literals, constants, operators and `str(...)` can be evaluated here".into(),
+            ),
+            Self::MissingElement { .. } => {
+                Some("Declare every element from index 0 without gaps".into())
+            }
+            Self::AmbiguousContinuation => Some(
+                "End the previous statement with `;`, or indent this line to continue it".into(),
+            ),
+            Self::OutOfCanvas { .. } => {
+                Some("Split the logic into smaller groups, or move components with `@pos`".into())
+            }
+            _ => None,
         }
     }
 
-    pub(super) fn create_label<'b>(
-        &self,
-        label: Label<(&'b str, Range<usize>)>,
-    ) -> Label<(&'b str, Range<usize>)> {
+    pub fn message(&self) -> String {
         match self {
-            Self::InvalidToken => label
-                .with_message("Unable to parse this word")
-                .with_color(Color::Red),
+            Self::InvalidToken { error } => error.message(),
             Self::UnexpectedToken { expected, found } => {
-                let label_msg = if found.is_none() {
+                if found.is_none() {
                     format!("Expected {}, but file ended", expected)
                 } else {
                     format!("Expected {}", expected)
-                };
-                label.with_message(label_msg).with_color(Color::Red)
-            }
-            Self::UnknownField { ident } => label
-                .with_message(format!("Field `{}` is unknown", ident))
-                .with_color(Color::Red),
-            Self::InvalidAssignment => label
-                .with_message("Cannot assign to this")
-                .with_color(Color::Red),
+                }
+            }
+            Self::MisplacedDocComment => {
+                "This doc comment is not attached to anything and is ignored".into()
+            }
+            Self::UnknownField { ident } => format!("Field `{}` is unknown", ident),
+            Self::InvalidAssignment => "Cannot assign to this".into(),
             Self::IncompatibleType {
                 expected_types: expected_type,
                 found_type,
-            } => label
-                .with_message(format!(
-                    "Type {} expected, `{}` found",
-                    format_iter(expected_type),
-                    found_type
-                ))
-                .with_color(Color::Red),
-            Self::OutOfBounds { bounds } => label
-                .with_message(format!(
-                    "Only accepts value between {} and {}",
-                    bounds.start(),
-                    bounds.end()
-                ))
-                .with_color(Color::Red),
-            Self::UnknownType { type_name } => label
-                .with_message(format!("Type name `{}` is unknown", type_name))
-                .with_color(Color::Red),
-            Self::FieldAlreadyDeclared => label
-                .with_message("This field is already declared")
-                .with_color(Color::Red),
-            Self::ElementAlreadyDeclared => label
-                .with_message("This element is already declared")
-                .with_color(Color::Red),
-            Self::StringInLogic => label
-                .with_message("Cannot use string in logic")
-                .with_color(Color::Red),
-            Self::FieldAccessOnly => label
-                .with_message("Use with a field access by a dot")
-                .with_color(Color::Red),
-            Self::OutputsInExpression => label
-                .with_message("Keyword `outputs` is only valid for assignment target")
-                .with_color(Color::Red),
+            } => format!(
+                "Type {} expected, `{}` found",
+                format_iter(expected_type),
+                found_type
+            ),
+            Self::OutOfBounds { bounds } => format!(
+                "Only accepts value between {} and {}",
+                bounds.start(),
+                bounds.end()
+            ),
+            Self::UnknownType { type_name } => format!("Type name `{}` is unknown", type_name),
+            Self::FieldAlreadyDeclared => "This field is already declared".into(),
+            Self::ElementAlreadyDeclared => "This element is already declared".into(),
+            Self::StringInLogic => "Cannot use string in logic".into(),
+            Self::FieldAccessOnly => "Use with a field access by a dot".into(),
+            Self::OutputsInExpression => {
+                "Keyword `outputs` is only valid for assignment target".into()
+            }
+            Self::InterfaceInFunction { interface } => {
+                format!("Cannot use `{}` in the body of a function", interface)
+            }
             Self::NodeDoesNotExist {
                 component_str,
                 index,
-            } => label
-                .with_message(format!(
-                    "{} th output node does not exist in component {}",
-                    index, component_str
-                ))
-                .with_color(Color::Red),
+            } => format!(
+                "{} th output node does not exist in component {}",
+                index, component_str
+            ),
             Self::IncompatibleNodeType {
                 expected_type,
                 found_type,
-            } => label
-                .with_message(format!(
-                    "Type `{}` expected, `{}` found",
-                    expected_type, found_type
-                ))
-                .with_color(Color::Red),
-            Self::UnknownName { name } => label
-                .with_message(format!("Name `{}` is unknwon", name))
-                .with_color(Color::Red),
+            } => format!("Type `{}` expected, `{}` found", expected_type, found_type),
+            Self::UnknownName { name } => format!("Name `{}` is unknwon", name),
+            Self::UnknownFunction { name, .. } => format!("Function `{}` is unknown", name),
             Self::LengthMismatch {
                 found_len,
                 expect_str,
-            } => label
-                .with_message(format!("Expected {}, {} found", expect_str, found_len))
-                .with_color(Color::Red),
-            Self::PropertyRequired { expect_str } => label
-                .with_message(format!("Following properties are required: {}", expect_str))
-                .with_color(Color::Red),
+            } => format!("Expected {}, {} found", expect_str, found_len),
+            Self::PropertyRequired { expect_str } => {
+                format!("Following properties are required: {}", expect_str)
+            }
+            Self::UnknownAttribute { name } => format!(
+                "Attribute `@{}` is unknown, expected `@pos`, `@group` or `@note`",
+                name
+            ),
+            Self::AttributeOnNonComponent { name } => format!(
+                "Attribute `@{}` needs an expression that creates a component",
+                name
+            ),
+            Self::ComponentsOverlap { first, second } => {
+                format!("{} overlaps {} in the layout", first, second)
+            }
+            Self::OutOfCanvas { name } => {
+                format!("{} is placed outside the editor canvas", name)
+            }
+            Self::XmlGeneration { message } => format!("Cannot generate the XML: {}", message),
+            Self::UnknownBackend { name } => format!(
+                "Backend `{}` is unknown, expected `components` or `lua`",
+                name
+            ),
+            Self::UnsupportedInLua { what } => format!("{} is not supported in Lua", what),
+            Self::TooManyChannels { node_type, limit } => format!(
+                "A Lua script can pass at most {} {} values in each direction",
+                limit, node_type
+            ),
+            Self::InvalidLuaSource => {
+                "Give the script either as `script = \"...\"` or as `lua(file = \"...\")`".into()
+            }
+            Self::LuaFileNotReadable { path, message } => {
+                format!("Cannot read `{}`: {}", path, message)
+            }
+            Self::LuaScriptTooLong { length, limit } => format!(
+                "Script has {} characters, but the game accepts at most {}",
+                length, limit
+            ),
+            Self::UndeclaredLuaChannel {
+                function,
+                channel,
+                count,
+            } => format!(
+                "Script calls `{}({})`, but the block declares only {} of them",
+                function, channel, count
+            ),
+            Self::UnusedLuaChannel { function, channel } => format!(
+                "Declared output is never written, expected `{}({}, ...)` in the script",
+                function, channel
+            ),
+            Self::LuaBlockOutputs { count } => format!(
+                "Lua block has {} outputs, bind it with `let` and use `name.output`",
+                count
+            ),
+            Self::NumberNodeOnly { field } => {
+                format!("Field `{}` is only valid on float nodes", field)
+            }
+            Self::EmptyRange { min, max } => {
+                format!("Minimum {:?} is greater than maximum {:?}", min, max)
+            }
+            Self::InvalidUnit { unit } => format!(
+                "Unit `{}` is not valid, write it like `m/s^2` or `kg*m`",
+                unit
+            ),
+            Self::UnitMismatch { lhs, rhs } => {
+                format!("Units `{}` and `{}` cannot be mixed", lhs, rhs)
+            }
+            Self::IncompatibleUnit { expected, found } => {
+                format!("Unit `{}` expected, `{}` found", expected, found)
+            }
+            Self::ScalarNodeOnly { field } => {
+                format!("Field `{}` is not valid on node arrays", field)
+            }
+            Self::NotAnArray => "Only arrays of interface nodes can be indexed".into(),
+            Self::IndexRequired { name } => {
+                format!("`{}` is an array, pick an element like `{}[0]`", name, name)
+            }
+            Self::UnknownParameter { name, .. } => format!("Parameter `{}` is unknown", name),
+            Self::DuplicateParameter { name, .. } => {
+                format!("Parameter `{}` is given more than once", name)
+            }
+            Self::MissingParameter { name, .. } => format!("Parameter `{}` is missing", name),
+            Self::TooManyArguments { limit, count, .. } => {
+                format!("Accepts at most {} values, {} given", limit, count)
+            }
+            Self::PositionalAfterNamed => {
+                "Values without a name must come before named ones".into()
+            }
+            Self::ModuloByZero => "Divisor of `%` is zero, so the result is always NaN".into(),
+            Self::FunctionAlreadyDefined { name } => {
+                format!("Function `{}` is already defined in this scope", name)
+            }
+            Self::RecursiveFunction { name } => format!("Function `{}` calls itself", name),
+            Self::NonExhaustiveMatch => "Some values match no arm".into(),
+            Self::ConstantAlreadyDefined { name } => {
+                format!("Constant `{}` is already defined in this scope", name)
+            }
+            Self::NotConstant => "This expression cannot be evaluated at compile time".into(),
+            Self::MissingElement { name, index } => {
+                format!(
+                    "Element `{}[{}]` of this array is not declared",
+                    name, index
+                )
+            }
+            Self::AmbiguousContinuation => {
+                "This line continues the expression on the previous line".into()
+            }
+            Self::UnreachableArm => "Earlier arms already match every value of this one".into(),
+            Self::UnsupportedPattern { pattern } => {
+                format!("`{}` cannot be used as a pattern", pattern)
+            }
+            Self::PossibleDivisionByZero { divisor } => {
+                format!("Divisor ranges over {}, which includes zero", divisor)
+            }
+            Self::OutputOutOfRange { name, range, found } => format!(
+                "Output `{}` is declared as {} but can take values in {}, set `clamp = true` to limit it",
+                name, range, found
+            ),
         }
     }
 }
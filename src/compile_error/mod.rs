@@ -1,14 +1,17 @@
 mod compile_error_type;
 pub use compile_error_type::CompileErrorType;
 
-use ariadne::{Label, Report, ReportKind, Source};
-use std::ops::Range;
+use ariadne::{Color, Label, Report, ReportKind, Source};
+use std::{fmt::Display, io, ops::Range};
 
 #[derive(Debug)]
 pub struct CompileError<'a> {
     filename: &'a str,
     span: Range<usize>,
     error_type: CompileErrorType,
+    // どの状況で起きたか (for の何回目かなど)
+    // Result の Err に入れるので Vec より小さい Box<[String]> にする
+    notes: Box<[String]>,
 }
 
 impl<'a> CompileError<'a> {
@@ -17,18 +20,143 @@ impl<'a> CompileError<'a> {
             filename,
             span,
             error_type,
+            notes: Box::default(),
         }
     }
 
-    pub fn print(&self, cache: &Source<&str>) {
-        Report::build(ReportKind::Error, (self.filename, self.span.clone()))
+    pub fn add_note(&mut self, note: String) {
+        let mut notes = std::mem::take(&mut self.notes).into_vec();
+        notes.push(note);
+        self.notes = notes.into_boxed_slice();
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.error_type.is_warning()
+    }
+}
+
+// ariadne は文字単位の位置を使うので、バイト単位の位置から変換する
+pub(crate) fn char_span(code: &str, span: &Range<usize>) -> Range<usize> {
+    let to_char = |i: usize| {
+        let mut i = i.min(code.len());
+        while !code.is_char_boundary(i) {
+            i -= 1;
+        }
+        code[..i].chars().count()
+    };
+    to_char(span.start)..to_char(span.end)
+}
+
+// 解析中のエラーはファイル名を借用するので、外に返すときは所有する形にする
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub filename: String,
+    pub span: Range<usize>,
+    pub error_type: CompileErrorType,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(filename: &str, span: Range<usize>, error_type: CompileErrorType) -> Self {
+        Self {
+            filename: filename.to_owned(),
+            span,
+            error_type,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        self.error_type.message()
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.error_type.is_warning()
+    }
+
+    fn report(&self, code: &str) -> Report<'_, (&str, Range<usize>)> {
+        let filename = self.filename.as_str();
+        let span = char_span(code, &self.span);
+        let (kind, color) = if self.is_warning() {
+            (ReportKind::Warning, Color::Yellow)
+        } else {
+            (ReportKind::Error, Color::Red)
+        };
+        let mut report = Report::build(kind, (filename, span.clone()))
             .with_message(self.error_type.name())
             .with_label(
-                self.error_type
-                    .create_label(Label::new((self.filename, self.span.clone()))),
-            )
-            .finish()
-            .eprint((self.filename, cache))
-            .unwrap();
+                Label::new((filename, span))
+                    .with_message(self.message())
+                    .with_color(color),
+            );
+        if let Some(help) = self.error_type.help() {
+            report = report.with_help(help);
+        }
+        for note in &self.notes {
+            report = report.with_note(note);
+        }
+        report.finish()
+    }
+
+    // code はエラーが起きたソースコード
+    pub fn print(&self, code: &str) {
+        let _ = self
+            .report(code)
+            .eprint((self.filename.as_str(), Source::from(code)));
+    }
+
+    pub fn write<W: io::Write>(&self, code: &str, w: W) -> io::Result<()> {
+        self.report(code)
+            .write((self.filename.as_str(), Source::from(code)), w)
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.filename,
+            self.span.start,
+            self.error_type.name(),
+            self.message()
+        )
+    }
+}
+
+impl From<CompileError<'_>> for Diagnostic {
+    fn from(value: CompileError<'_>) -> Self {
+        Self {
+            notes: value.notes.into_vec(),
+            ..Self::new(value.filename, value.span, value.error_type)
+        }
+    }
+}
+
+// 成功した結果と、あわせて表示する警告
+#[derive(Debug)]
+pub struct WithWarnings<T> {
+    pub value: T,
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl<T> WithWarnings<T> {
+    pub fn new(value: T, warnings: Vec<Diagnostic>) -> Self {
+        Self { value, warnings }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> WithWarnings<U> {
+        WithWarnings {
+            value: f(self.value),
+            warnings: self.warnings,
+        }
+    }
+
+    // 警告を表示して値を取り出す
+    pub fn print(self, code: &str) -> T {
+        for w in &self.warnings {
+            w.print(code);
+        }
+        self.value
     }
 }
@@ -0,0 +1,402 @@
+use crate::{
+    microcontroller::{NodeMode, NodeType},
+    xml_schema::{ComponentItem, Microprocessor, ObjectInput, ObjectValueTag},
+};
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Display, Write as _},
+};
+
+#[derive(Debug)]
+pub enum DecompileError {
+    UnsupportedNodeType { label: String, node_type: u8 },
+    UnsupportedComponent { id: u32, component_type: u8 },
+    UnsupportedFunction { id: u32, function: String },
+    UnsupportedOutput { id: u32, index: usize },
+    UnknownComponent { id: u32 },
+    Cycle { id: u32 },
+}
+
+impl Display for DecompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedNodeType { label, node_type } => {
+                write!(f, "Node `{}` has unsupported type {}", label, node_type)
+            }
+            Self::UnsupportedComponent { id, component_type } => write!(
+                f,
+                "Component {} has unsupported type {}",
+                id, component_type
+            ),
+            Self::UnsupportedFunction { id, function } => write!(
+                f,
+                "Function `{}` of component {} cannot be expressed",
+                function, id
+            ),
+            Self::UnsupportedOutput { id, index } => write!(
+                f,
+                "Output {} of component {} cannot be expressed",
+                index, id
+            ),
+            Self::UnknownComponent { id } => write!(f, "Component {} does not exist", id),
+            Self::Cycle { id } => write!(f, "Component {} is part of a loop", id),
+        }
+    }
+}
+
+// マイコンXMLからソースコードを復元する
+pub fn decompile(mp: &Microprocessor, ident: &str) -> Result<String, DecompileError> {
+    let mut d = Decompiler::new(mp)?;
+    d.logic()?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "microcontroller {} {{", ident);
+    if let Some(name) = &mp.name {
+        let _ = writeln!(out, "    name = {}", string_literal(name));
+    }
+    if let Some(description) = &mp.description {
+        let _ = writeln!(out, "    description = {}", string_literal(description));
+    }
+    let _ = writeln!(out, "    size = ({}, {})", mp.width, mp.length);
+
+    out += "\n    interface {\n";
+    for (mode, keyword) in [(NodeMode::Input, "inputs"), (NodeMode::Output, "outputs")] {
+        let nodes = d
+            .nodes
+            .values()
+            .filter(|n| n.mode.is_input() == mode.is_input());
+        let _ = writeln!(out, "        {} {{", keyword);
+        for node in nodes {
+            let _ = write!(out, "            {}: {} {{", node.ident, node.type_name);
+            let _ = write!(out, " name = {}", string_literal(&node.label));
+            if !node.description.is_empty() {
+                let _ = write!(out, " description = {}", string_literal(&node.description));
+            }
+            let _ = writeln!(
+                out,
+                " position = ({}, {}) }}",
+                node.position.0, node.position.1
+            );
+        }
+        out += "        }\n";
+    }
+    out += "    }\n";
+
+    out += "\n    logic {\n";
+    for line in &d.lines {
+        let _ = writeln!(out, "        {}", line);
+    }
+    out += "    }\n}\n";
+
+    Ok(out)
+}
+
+#[derive(Debug)]
+struct DecompiledNode {
+    ident: String,
+    mode: NodeMode,
+    type_name: &'static str,
+    label: String,
+    description: String,
+    position: (u8, u8),
+}
+
+#[derive(Debug)]
+struct Decompiler<'a> {
+    // component_id -> ノード
+    nodes: BTreeMap<u32, DecompiledNode>,
+    bridges: HashMap<u32, &'a ComponentItem>,
+    components: BTreeMap<u32, &'a ComponentItem>,
+    lines: Vec<String>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(mp: &'a Microprocessor) -> Result<Self, DecompileError> {
+        let mut nodes = BTreeMap::new();
+        let mut known_idents = HashSet::new();
+        for item in &mp.nodes.n {
+            let node = &item.node;
+            let label = node.label.clone().unwrap_or_default();
+            let mode = NodeMode::try_from(node.mode.unwrap_or(0)).unwrap_or(NodeMode::Output);
+            let type_name = match NodeType::try_from(node.node_type.unwrap_or(0)) {
+                Ok(NodeType::Bool) => "bool",
+                Ok(NodeType::Number) => "float",
+                _ => {
+                    return Err(DecompileError::UnsupportedNodeType {
+                        label,
+                        node_type: node.node_type.unwrap_or(0),
+                    });
+                }
+            };
+            let position = node
+                .position
+                .as_ref()
+                .map(|p| (p.x.unwrap_or(0), p.z.unwrap_or(0)))
+                .unwrap_or_default();
+            nodes.insert(
+                item.component_id,
+                DecompiledNode {
+                    ident: to_ident(&label, &mut known_idents),
+                    mode,
+                    type_name,
+                    label,
+                    description: node.description.clone().unwrap_or_default(),
+                    position,
+                },
+            );
+        }
+
+        Ok(Self {
+            nodes,
+            bridges: mp
+                .group
+                .components_bridge
+                .c
+                .iter()
+                .map(|c| (c.object.id, c))
+                .collect(),
+            components: mp
+                .group
+                .components
+                .c
+                .iter()
+                .map(|c| (c.object.id, c))
+                .collect(),
+            lines: Vec::new(),
+        })
+    }
+
+    fn logic(&mut self) -> Result<(), DecompileError> {
+        // 依存先から順に let 文にする
+        let mut done = HashSet::new();
+        let mut visiting = HashSet::new();
+        let ids = self.components.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            self.visit(id, &mut done, &mut visiting)?;
+        }
+
+        let outputs = self
+            .nodes
+            .iter()
+            .filter(|(_, n)| n.mode.is_output())
+            .map(|(id, n)| (*id, n.ident.clone()))
+            .collect::<Vec<_>>();
+        for (id, ident) in outputs {
+            if let Some(bridge) = self.bridges.get(&id)
+                && let Some(input) = bridge.object.in_map.get(&1)
+            {
+                let value = self.input_expr(Some(input))?;
+                self.lines.push(format!("outputs.{} = {}", ident, value));
+            }
+        }
+        Ok(())
+    }
+
+    fn visit(
+        &mut self,
+        id: u32,
+        done: &mut HashSet<u32>,
+        visiting: &mut HashSet<u32>,
+    ) -> Result<(), DecompileError> {
+        if done.contains(&id) {
+            return Ok(());
+        }
+        if !visiting.insert(id) {
+            return Err(DecompileError::Cycle { id });
+        }
+
+        let component = *self
+            .components
+            .get(&id)
+            .ok_or(DecompileError::UnknownComponent { id })?;
+        for input in component.object.in_map.values() {
+            if let Some(c) = input.component_id
+                && self.components.contains_key(&c)
+            {
+                self.visit(c, done, visiting)?;
+            }
+        }
+
+        let expr = self.component_expr(component)?;
+        self.lines.push(format!("let c{} = {}", id, expr));
+
+        visiting.remove(&id);
+        done.insert(id);
+        Ok(())
+    }
+
+    fn component_expr(&self, component: &ComponentItem) -> Result<String, DecompileError> {
+        let id = component.object.id;
+        let input = |i: usize| self.input_expr(component.object.in_map.get(&i));
+        // 未接続のオンオフ入力は false
+        let bool_input = |i: usize| match component.object.in_map.get(&i) {
+            Some(input) if input.component_id.is_some() => self.input_expr(Some(input)),
+            _ => Ok("false".to_owned()),
+        };
+        let value = |tag: ObjectValueTag| {
+            let v = component
+                .object
+                .value_list
+                .iter()
+                .find(|(t, _)| *t == tag)
+                .and_then(|(_, v)| v.value.as_ref().or(v.text.as_ref()))
+                .and_then(|v| v.parse::<f32>().ok())
+                .unwrap_or(0.0);
+            format!("{:?}", v)
+        };
+
+        let component_type = component.component_type.unwrap_or(0);
+        let expr = match component_type {
+            6 => format!("{} + {}", input(1)?, input(2)?),
+            7 => format!("{} - {}", input(1)?, input(2)?),
+            8 => format!("{} * {}", input(1)?, input(2)?),
+            9 => format!("{} / {}", input(1)?, input(2)?),
+            11 => format!(
+                "clamp{{{}, {}}}({})",
+                value(ObjectValueTag::Min),
+                value(ObjectValueTag::Max),
+                input(1)?
+            ),
+            14 => format!("abs({})", input(1)?),
+            17 => format!("{} > {}", input(1)?, input(2)?),
+            18 => format!("{} < {}", input(1)?, input(2)?),
+            22 => format!(
+                "if {} {{ {} }} else {{ {} }}",
+                bool_input(3)?,
+                input(1)?,
+                input(2)?
+            ),
+            23 => format!(
+                "pid({}, {}, {}, {}, {}, {})",
+                input(1)?,
+                input(2)?,
+                input(3)?,
+                input(4)?,
+                input(5)?,
+                bool_input(6)?
+            ),
+            39 => format!(
+                "pid{{{}, {}, {}}}({}, {}, {})",
+                value(ObjectValueTag::Kp),
+                value(ObjectValueTag::Ki),
+                value(ObjectValueTag::Kd),
+                input(1)?,
+                input(2)?,
+                bool_input(3)?
+            ),
+            15 => value(ObjectValueTag::N),
+            45 => {
+                let function = component
+                    .object
+                    .attrs
+                    .as_ref()
+                    .and_then(|a| a.get("@e"))
+                    .cloned()
+                    .unwrap_or_default();
+                if function.trim() == "-x" {
+                    format!("-{}", input(1)?)
+                } else {
+                    return Err(DecompileError::UnsupportedFunction { id, function });
+                }
+            }
+            _ => {
+                return Err(DecompileError::UnsupportedComponent { id, component_type });
+            }
+        };
+        Ok(expr)
+    }
+
+    fn input_expr(&self, input: Option<&ObjectInput>) -> Result<String, DecompileError> {
+        // 未接続の入力は 0 として扱われる
+        let Some(id) = input.and_then(|i| i.component_id) else {
+            return Ok("0.0".to_owned());
+        };
+        let index = input.and_then(|i| i.node_index).unwrap_or(0);
+
+        if let Some(node) = self.nodes.get(&id) {
+            return Ok(format!("inputs.{}", node.ident));
+        }
+        if !self.components.contains_key(&id) {
+            return Err(DecompileError::UnknownComponent { id });
+        }
+        // 除算のゼロ除算フラグなど、2番目以降の出力は式で表せない
+        if index != 0 {
+            return Err(DecompileError::UnsupportedOutput { id, index });
+        }
+        Ok(format!("c{}", id))
+    }
+}
+
+// ラベルから識別子を作る
+fn to_ident(label: &str, known: &mut HashSet<String>) -> String {
+    let mut ident = String::with_capacity(label.len());
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            ident.push(c.to_ascii_lowercase());
+        } else if !c.is_ascii() && unicode_ident::is_xid_continue(c) {
+            ident.push(c);
+        } else if !ident.is_empty() && !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+    let mut ident = ident.trim_end_matches('_').to_owned();
+    if ident.is_empty() || ident.starts_with(|c: char| !unicode_ident::is_xid_start(c)) {
+        ident.insert_str(0, "node_");
+    }
+    if is_keyword(&ident) {
+        ident.push('_');
+    }
+
+    let mut unique = ident.clone();
+    let mut i = 2;
+    while known.contains(&unique) {
+        unique = format!("{}_{}", ident, i);
+        i += 1;
+    }
+    known.insert(unique.clone());
+    unique
+}
+
+fn is_keyword(ident: &str) -> bool {
+    matches!(
+        ident,
+        "composite"
+            | "microcontroller"
+            | "interface"
+            | "inputs"
+            | "outputs"
+            | "properties"
+            | "tooltips"
+            | "logic"
+            | "let"
+            | "null"
+            | "lua"
+            | "fn"
+            | "const"
+            | "if"
+            | "else"
+            | "match"
+            | "for"
+            | "in"
+            | "true"
+            | "false"
+    )
+}
+
+fn string_literal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,309 @@
+use crate::{lexical::Token, syntax::ends_expr};
+
+use std::{collections::HashSet, ops::Range};
+
+const INDENT: &str = "    ";
+
+// 字句解析済みのトークン列を整形する
+// 改行の位置はソースのものを保ち、インデントとトークン間の空白だけを揃える
+pub fn format(code: &str, tokens: &[(Token, Range<usize>)]) -> String {
+    let props = props_braces(tokens);
+    let mut f = Formatter::default();
+
+    let mut prev_end = 0;
+    for (i, (token, span)) in tokens.iter().enumerate() {
+        f.gap(&code[prev_end..span.start]);
+        f.token(
+            token,
+            &code[span.clone()],
+            props.contains(&i),
+            span.start == prev_end,
+        );
+        prev_end = span.end;
+    }
+    f.gap(&code[prev_end..]);
+
+    f.finish()
+}
+
+// 関数呼び出しのプロパティ name{...}(...) の開き括弧の位置
+fn props_braces(tokens: &[(Token, Range<usize>)]) -> HashSet<usize> {
+    let mut props = HashSet::new();
+    let mut stack = Vec::new();
+    for (i, (token, span)) in tokens.iter().enumerate() {
+        match token {
+            Token::LBrace => stack.push(i),
+            Token::RBrace => {
+                if let Some(open) = stack.pop()
+                    && open > 0
+                    && matches!(tokens[open - 1].0, Token::Ident(_))
+                    && tokens[open - 1].1.end == tokens[open].1.start
+                    && tokens
+                        .get(i + 1)
+                        .is_some_and(|(t, s)| *t == Token::LParen && s.start == span.end)
+                {
+                    props.insert(open);
+                }
+            }
+            _ => {}
+        }
+    }
+    props
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Delimiter {
+    // ブロック { ... }
+    Block,
+    // 関数呼び出しのプロパティ name{...}
+    Props,
+    // ( ... )
+    Paren,
+    // 添字と配列の要素数 [ ... ]
+    Bracket,
+}
+
+#[derive(Debug)]
+struct OpenDelimiter {
+    kind: Delimiter,
+    // 開き括弧の直後で改行されていればインデントする
+    indented: bool,
+}
+
+#[derive(Default, Debug)]
+struct Formatter {
+    out: String,
+    line: String,
+    stack: Vec<OpenDelimiter>,
+    prev: Option<Token>,
+    // 空行を入れるかどうか
+    blank_line: bool,
+    // 現在の行にトークンかコメントがあるか
+    line_started: bool,
+    // 次のトークンの前に改行するか
+    newline_pending: bool,
+    // 直前のマイナスが単項演算子か
+    unary_minus: bool,
+}
+
+impl Formatter {
+    fn indent_level(&self) -> usize {
+        self.stack.iter().filter(|d| d.indented).count()
+    }
+
+    fn newline(&mut self) {
+        if matches!(
+            self.prev,
+            Some(Token::LBrace | Token::LParen | Token::LBracket)
+        ) && let Some(open) = self.stack.last_mut()
+        {
+            open.indented = true;
+        }
+        if self.line_started {
+            self.flush_line();
+        }
+        self.newline_pending = true;
+    }
+
+    fn flush_line(&mut self) {
+        let line = self.line.trim_end();
+        if !line.is_empty() {
+            self.out += line;
+        }
+        self.out.push('\n');
+        self.line.clear();
+        self.line_started = false;
+    }
+
+    fn start_line(&mut self, level: usize) {
+        if self.blank_line && !self.out.is_empty() && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+        self.blank_line = false;
+        self.newline_pending = false;
+        self.line_started = true;
+        for _ in 0..level {
+            self.line += INDENT;
+        }
+    }
+
+    // トークン間の空白とコメント
+    fn gap(&mut self, text: &str) {
+        let mut rest = text;
+        let mut newlines = 0;
+        while !rest.is_empty() {
+            if let Some(comment) = rest.strip_prefix("//") {
+                let end = comment.find('\n').unwrap_or(comment.len());
+                self.comment(&rest[..end + 2], newlines);
+                newlines = 0;
+                rest = &comment[end..];
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                let end = comment.find("*/").map_or(comment.len(), |i| i + 2);
+                self.comment(&rest[..end + 2], newlines);
+                newlines = 0;
+                rest = &comment[end..];
+            } else {
+                let c = rest.chars().next().unwrap();
+                if c == '\n' {
+                    newlines += 1;
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        self.line_break(newlines);
+    }
+
+    fn line_break(&mut self, newlines: usize) {
+        if newlines > 0 || !self.line_started {
+            if newlines > 1 {
+                self.blank_line = true;
+            }
+            self.newline();
+        }
+    }
+
+    fn comment(&mut self, text: &str, newlines_before: usize) {
+        self.line_break(newlines_before);
+        if self.newline_pending {
+            self.start_line(self.indent_level());
+        } else {
+            self.line.push(' ');
+        }
+        self.line += text.trim_end();
+        if text.starts_with("//") {
+            self.newline();
+        }
+    }
+
+    fn token(&mut self, token: &Token, text: &str, props: bool, adjacent: bool) {
+        // 閉じ括弧は先にスタックから取り出してインデントを決める
+        let closing = match token {
+            Token::RBrace | Token::RParen | Token::RBracket => self.stack.pop(),
+            _ => None,
+        };
+
+        // 行頭の演算子は前の行の式の続きなので一段深くインデントする
+        let continuation = self.newline_pending
+            && matches!(
+                token,
+                Token::Plus
+                    | Token::Minus
+                    | Token::Asterisk
+                    | Token::Slash
+                    | Token::Percent
+                    | Token::DoubleAsterisk
+                    | Token::Less
+                    | Token::Greater
+                    | Token::LParen
+            )
+            && self.prev.as_ref().is_some_and(ends_expr);
+
+        if let Token::Minus = token {
+            self.unary_minus = (self.newline_pending && !continuation)
+                || matches!(
+                    self.prev,
+                    None | Some(
+                        Token::LBrace
+                            | Token::LParen
+                            | Token::LBracket
+                            | Token::Comma
+                            | Token::Semicolon
+                            | Token::Colon
+                            | Token::Equal
+                            | Token::DotDot
+                            | Token::Plus
+                            | Token::Minus
+                            | Token::Asterisk
+                            | Token::Slash
+                            | Token::Percent
+                            | Token::DoubleAsterisk
+                            | Token::Less
+                            | Token::Greater
+                            | Token::If
+                            | Token::Else
+                    )
+                );
+        }
+
+        if self.newline_pending {
+            self.start_line(self.indent_level() + continuation as usize);
+            // 閉じ括弧を行頭に置く場合の空行は詰める
+            if closing.is_some() && self.out.ends_with("\n\n") {
+                self.out.pop();
+            }
+        } else if self.needs_space(token, closing.as_ref(), props, adjacent) {
+            self.line.push(' ');
+        }
+        self.line += text;
+
+        match token {
+            Token::LBrace => {
+                let kind = if props {
+                    Delimiter::Props
+                } else {
+                    Delimiter::Block
+                };
+                self.stack.push(OpenDelimiter {
+                    kind,
+                    indented: false,
+                });
+            }
+            Token::LParen => self.stack.push(OpenDelimiter {
+                kind: Delimiter::Paren,
+                indented: false,
+            }),
+            Token::LBracket => self.stack.push(OpenDelimiter {
+                kind: Delimiter::Bracket,
+                indented: false,
+            }),
+            _ => {}
+        }
+        self.prev = Some(token.clone());
+    }
+
+    fn needs_space(
+        &self,
+        token: &Token,
+        closing: Option<&OpenDelimiter>,
+        props: bool,
+        adjacent: bool,
+    ) -> bool {
+        let Some(prev) = &self.prev else {
+            return false;
+        };
+        let prev_open = self.stack.last();
+
+        match (prev, token) {
+            // 括弧の内側
+            (Token::LParen | Token::LBracket, _)
+            | (_, Token::RParen | Token::RBracket)
+            | (Token::LBrace, Token::RBrace) => false,
+            // 添字
+            (_, Token::LBracket) => false,
+            (Token::LBrace, _) if prev_open.is_some_and(|d| d.kind == Delimiter::Props) => false,
+            (_, Token::RBrace) => closing.is_none_or(|d| d.kind != Delimiter::Props),
+            // 区切り
+            (_, Token::Comma | Token::Colon | Token::Dot | Token::Semicolon)
+            | (Token::Dot | Token::At, _) => false,
+            // 範囲 0..8
+            (_, Token::DotDot) | (Token::DotDot, _) => false,
+            // 関数呼び出し
+            (Token::Ident(_), Token::LBrace) => !props,
+            (Token::Ident(_) | Token::Lua, Token::LParen) => false,
+            (Token::RBrace, Token::LParen) => !adjacent,
+            // 単項マイナス
+            (Token::Minus, _) => !self.unary_minus,
+            _ => true,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.line_started {
+            self.flush_line();
+        }
+        while self.out.ends_with("\n\n") {
+            self.out.pop();
+        }
+        self.out
+    }
+}
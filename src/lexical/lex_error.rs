@@ -0,0 +1,54 @@
+// 字句解析のエラー
+#[derive(Default, PartialEq, Clone, Debug)]
+pub enum LexError {
+    // どのトークンにも当てはまらない文字
+    #[default]
+    StrayCharacter,
+    NumberOverflow,
+    UnterminatedString,
+    UnknownEscape {
+        escape: String,
+    },
+    UnterminatedBlockComment,
+}
+
+impl LexError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::StrayCharacter => "Stray Character",
+            Self::NumberOverflow => "Number Overflow",
+            Self::UnterminatedString => "Unterminated String",
+            Self::UnknownEscape { .. } => "Unknown Escape",
+            Self::UnterminatedBlockComment => "Unterminated Block Comment",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Self::StrayCharacter => "This character cannot start a token".into(),
+            Self::NumberOverflow => "This number does not fit in the literal type".into(),
+            Self::UnterminatedString => "This string is never closed".into(),
+            Self::UnknownEscape { escape } => format!("Escape sequence `{}` is unknown", escape),
+            Self::UnterminatedBlockComment => "This comment is never closed".into(),
+        }
+    }
+
+    pub fn help(&self) -> String {
+        match self {
+            Self::StrayCharacter => {
+                "Identifiers consist of letters, digits and `_`, and text must be quoted".into()
+            }
+            Self::NumberOverflow => {
+                "Integers must be within 64 bits, and floats must be finite".into()
+            }
+            Self::UnterminatedString => {
+                "Close it with the quote it starts with, e.g. `\"#` for `r#\"`".into()
+            }
+            Self::UnknownEscape { .. } => {
+                "Use `\\n`, `\\r`, `\\t`, `\\0`, `\\\\`, `\\\"`, `\\'` or `\\u{...}`, or a raw string `r\"...\"`"
+                    .into()
+            }
+            Self::UnterminatedBlockComment => "Close it with `*/`".into(),
+        }
+    }
+}
@@ -1,20 +1,31 @@
+mod lex_error;
 mod token;
+pub use lex_error::LexError;
 pub use token::Token;
 
 use logos::Logos as _;
 
-pub fn tokenize(code: &str) -> Result<Vec<(Token, logos::Span)>, Vec<logos::Span>> {
+type SpannedToken = (Token, logos::Span);
+type SpannedError = (LexError, logos::Span);
+
+pub fn tokenize(code: &str) -> Result<Vec<SpannedToken>, Vec<SpannedError>> {
     // 字句解析
     let lex = Token::lexer(code).spanned().collect::<Vec<_>>();
 
-    let mut tokens: Vec<(Token, logos::Span)> = Vec::with_capacity(lex.len());
-    let mut lex_errors: Vec<logos::Span> = Vec::new();
+    let mut tokens: Vec<SpannedToken> = Vec::with_capacity(lex.len());
+    let mut lex_errors: Vec<SpannedError> = Vec::new();
 
     for (token, span) in lex {
-        if let Ok(token) = token {
-            tokens.push((token, span));
-        } else {
-            lex_errors.push(span);
+        match token {
+            Ok(token) => tokens.push((token, span)),
+            // 閉じられていない文字列やコメントはファイル末尾まで続くので、開始行だけを指す
+            Err(err @ (LexError::UnterminatedString | LexError::UnterminatedBlockComment)) => {
+                let end = code[span.clone()]
+                    .find('\n')
+                    .map_or(span.end, |i| span.start + i);
+                lex_errors.push((err, span.start..end));
+            }
+            Err(err) => lex_errors.push((err, span)),
         }
     }
 
@@ -1,9 +1,13 @@
-use logos::Logos;
+use super::LexError;
+
+use logos::{Filter, FilterResult, Lexer, Logos};
+use std::{iter::Peekable, str::CharIndices};
 
 #[derive(Logos, Debug, PartialEq, PartialOrd, Clone)]
+#[logos(error = LexError)]
 #[logos(skip r"[ \t\r\n\f]+")]
 #[logos(skip r"//[^\n]*")] // 1行コメント
-#[logos(skip r"/\*(?:[^*]|\*[^/])*\*/")] // ブロックコメント
+#[logos(skip(r"/\*", block_comment))] // ブロックコメント
 pub enum Token {
     #[token("composite")]
     Composite,
@@ -25,6 +29,22 @@ pub enum Token {
     Let,
     #[token("null")]
     Null,
+    #[token("lua")]
+    Lua,
+    #[token("fn")]
+    Fn,
+    #[token("const")]
+    Const,
+    #[token("if")]
+    If,
+    #[token("else")]
+    Else,
+    #[token("match")]
+    Match,
+    #[token("for")]
+    For,
+    #[token("in")]
+    In,
 
     #[token("{")]
     LBrace,
@@ -34,16 +54,20 @@ pub enum Token {
     Colon,
     #[token(",")]
     Comma,
-    /*#[token("[")]
+    #[token("[")]
     LBracket,
     #[token("]")]
     RBracket,
     #[token(";")]
-    Semicolon,*/
+    Semicolon,
     #[token(".")]
     Dot,
+    #[token("..")]
+    DotDot,
     #[token("=")]
     Equal,
+    #[token("=>")]
+    FatArrow,
     #[token("+")]
     Plus,
     #[token("-")]
@@ -52,23 +76,43 @@ pub enum Token {
     Asterisk,
     #[token("/")]
     Slash,
+    #[token("%")]
+    Percent,
+    #[token("**")]
+    DoubleAsterisk,
+    #[token("<")]
+    Less,
+    #[token(">")]
+    Greater,
     #[token("(")]
     LParen,
     #[token(")")]
     RParen,
+    #[token("@")]
+    At,
 
-    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
+    #[regex(r"[\p{XID_Start}_]\p{XID_Continue}*", |lex| lex.slice().to_string())]
     Ident(String),
 
     #[token("false", |_| false)]
     #[token("true", |_| true)]
     Bool(bool),
-    #[regex(r"[+-]?(?:0[xX][0-9A-Fa-f]+|0[bB][01]+|0[oO][0-7]+|[1-9]\d*|0)", |lex| parse_int(lex.slice()).unwrap())]
+    #[regex(r"[+-]?(?:0[xX][0-9A-Fa-f]+|0[bB][01]+|0[oO][0-7]+|[1-9][0-9]*|0)", |lex| parse_int(lex.slice()).map_err(|_| LexError::NumberOverflow))]
     Int(i64),
-    #[regex(r"[+-]?(?:(?:\d+\.\d*|\.\d+)(?:[eE][+-]?\d+)?|\d+[eE][+-]?\d+)", |lex| lex.slice().parse::<f64>().unwrap())]
+    #[regex(
+        r"[+-]?(?:(?:[0-9]+\.[0-9]+|\.[0-9]+)(?:[eE][+-]?[0-9]+)?|[0-9]+[eE][+-]?[0-9]+)",
+        parse_float
+    )]
     Float(f64),
-    #[regex(r#"(?:"(?:\\["'\\nrt]|[\x00-\x7F&&[^"\\]])*"|'(?:\\["'\\]|[\x00-\x7F&&[^'\\nrt]])*')"#, |lex| parse_string(lex.slice()).unwrap())]
+    #[token("\"", |lex| lex_string(lex, '"'))]
+    #[token("'", |lex| lex_string(lex, '\''))]
+    #[regex(r##"r#*""##, lex_raw_string)]
     String(String),
+
+    // ドキュメントコメント (/// と /** */)
+    #[regex(r"///[^\n]*", line_doc)]
+    #[token("/**", block_doc)]
+    DocComment(String),
 }
 
 fn parse_int(s: &str) -> Result<i64, std::num::ParseIntError> {
@@ -96,35 +140,147 @@ fn parse_int(s: &str) -> Result<i64, std::num::ParseIntError> {
     Ok(n)
 }
 
-fn parse_string(s: &str) -> Option<String> {
-    let bytes = s.as_bytes();
-    if bytes.len() < 2 {
-        return None;
+fn parse_float(lex: &mut Lexer<Token>) -> Result<f64, LexError> {
+    match lex.slice().parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        _ => Err(LexError::NumberOverflow),
+    }
+}
+
+fn block_comment(lex: &mut Lexer<Token>) -> Result<(), LexError> {
+    if let Some(end) = lex.remainder().find("*/") {
+        lex.bump(end + 2);
+        Ok(())
+    } else {
+        lex.bump(lex.remainder().len());
+        Err(LexError::UnterminatedBlockComment)
+    }
+}
+
+// //// で始まるものは普通のコメント
+fn line_doc(lex: &mut Lexer<Token>) -> Filter<String> {
+    let text = &lex.slice()[3..];
+    if text.starts_with('/') {
+        return Filter::Skip;
+    }
+    let text = text.strip_prefix(' ').unwrap_or(text);
+    Filter::Emit(text.trim_end().to_owned())
+}
+
+// /**/ や /*** で始まるものは普通のコメント
+// 各行の先頭の空白と * を除く
+fn block_doc(lex: &mut Lexer<Token>) -> FilterResult<String, LexError> {
+    let rest = lex.remainder();
+    if rest.starts_with('/') {
+        lex.bump(1);
+        return FilterResult::Skip;
+    }
+    let Some(end) = rest.find("*/") else {
+        lex.bump(rest.len());
+        return FilterResult::Error(LexError::UnterminatedBlockComment);
+    };
+    lex.bump(end + 2);
+    if rest.starts_with('*') {
+        return FilterResult::Skip;
     }
 
-    let mut out = String::with_capacity(bytes.len() - 2);
-    let mut i = 1;
-    while i + 1 < bytes.len() {
-        if bytes[i] == b'\\' {
-            let next = bytes[i + 1];
-            match next {
-                b'\\' => out.push('\\'),
-                b'"' => out.push('"'),
-                b'\'' => out.push('\''),
-                b'n' => out.push('\n'),
-                b'r' => out.push('\r'),
-                b't' => out.push('\t'),
-                _ => {
-                    out.push('\\');
-                    out.push(next as char);
+    let lines = rest[..end]
+        .lines()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect::<Vec<_>>();
+    let first = lines
+        .iter()
+        .position(|l| !l.is_empty())
+        .unwrap_or(lines.len());
+    let last = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(first, |i| i + 1);
+    FilterResult::Emit(lines[first..last].join("\n"))
+}
+
+// 開き引用符の後から閉じ引用符までを読む
+// 改行もそのまま文字列に含める
+fn lex_string(lex: &mut Lexer<Token>, quote: char) -> Result<String, LexError> {
+    let rest = lex.remainder();
+    let mut chars = rest.char_indices().peekable();
+    let mut out = String::new();
+    let mut error = None;
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            lex.bump(i + 1);
+            return error.map_or(Ok(out), Err);
+        } else if c == '\\' {
+            match escape(&mut chars) {
+                Ok(Some(c)) => out.push(c),
+                Ok(None) => {}
+                Err(err) => {
+                    error.get_or_insert(err);
                 }
             }
-            i += 2;
         } else {
-            out.push(bytes[i] as char);
-            i += 1;
+            out.push(c);
         }
     }
+    lex.bump(rest.len());
+    Err(LexError::UnterminatedString)
+}
+
+// \ の後のエスケープシーケンス
+// 行末の \ は改行と次の行の先頭の空白を読み飛ばす (None)
+fn escape(chars: &mut Peekable<CharIndices>) -> Result<Option<char>, LexError> {
+    let unknown = |escape: String| LexError::UnknownEscape { escape };
+    let Some((_, c)) = chars.next() else {
+        return Err(LexError::UnterminatedString);
+    };
+    let c = match c {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '0' => '\0',
+        '\\' | '"' | '\'' => c,
+        '\r' | '\n' => {
+            while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+            return Ok(None);
+        }
+        'u' => {
+            if chars.next_if(|(_, c)| *c == '{').is_none() {
+                return Err(unknown("\\u".to_owned()));
+            }
+            let mut code = String::new();
+            while let Some((_, c)) = chars.next_if(|(_, c)| *c != '}' && *c != '"' && *c != '\'') {
+                code.push(c);
+            }
+            let closed = chars.next_if(|(_, c)| *c == '}').is_some();
+            let text = format!("\\u{{{}{}", code, if closed { "}" } else { "" });
+            if !closed || code.is_empty() || code.len() > 6 {
+                return Err(unknown(text));
+            }
+            return u32::from_str_radix(&code, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .map(Some)
+                .ok_or_else(|| unknown(text));
+        }
+        _ => return Err(unknown(format!("\\{}", c))),
+    };
+    Ok(Some(c))
+}
 
-    Some(out)
+// r"..." や r#"..."# はエスケープせずそのまま読む
+fn lex_raw_string(lex: &mut Lexer<Token>) -> Result<String, LexError> {
+    let hashes = lex.slice().len() - 2;
+    let terminator = format!("\"{}", "#".repeat(hashes));
+    let rest = lex.remainder();
+    if let Some(end) = rest.find(&terminator) {
+        lex.bump(end + terminator.len());
+        Ok(rest[..end].to_owned())
+    } else {
+        lex.bump(rest.len());
+        Err(LexError::UnterminatedString)
+    }
 }
@@ -0,0 +1,20 @@
+#![warn(unused_extern_crates)]
+
+mod compile;
+mod compile_error;
+pub mod decompile;
+pub mod format;
+pub mod lexical;
+pub mod microcontroller;
+pub mod project;
+pub mod render;
+mod semantic;
+pub mod syntax;
+pub mod xml_schema;
+
+pub use compile::{
+    analyze, check_layout, compile_str, layout, lex, optimize, parse, script_files, to_dot, to_svg,
+    to_xml, write_xml,
+};
+pub use compile_error::{CompileErrorType, Diagnostic, WithWarnings};
+pub use semantic::ValueType;
@@ -1,23 +1,34 @@
 use super::{
-    Component, ComponentPosition, InputNode, LinkNode, Microcontroller, Node, OutputNode,
-    PositionedComponent, PositionedMicrocontroller, PositionedNode, UnpositionedMicrocontroller,
+    Component, ComponentPosition, LayoutBounds, LayoutHint, LinkIndex, LinkNode, Microcontroller,
+    Node, PositionedComponent, PositionedMicrocontroller, PositionedNode, StableId,
+    UnpositionedMicrocontroller,
     components::ComponentData as _,
+    layout_check::{NODE_HEIGHT, VERTEX_WIDTH},
 };
 
 use std::{
-    cell::RefCell,
-    collections::{BTreeMap, HashMap, HashSet},
-    rc::{Rc, Weak},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    rc::Rc,
 };
 
+// 列の間隔
+const COLUMN_PITCH: i32 = 5;
+// 交差削減の反復回数
+const ORDERING_SWEEPS: usize = 12;
+
 impl UnpositionedMicrocontroller {
     pub fn auto_layout(self) -> PositionedMicrocontroller {
+        self.auto_layout_within(&LayoutBounds::default())
+    }
+
+    pub fn auto_layout_within(self, bounds: &LayoutBounds) -> PositionedMicrocontroller {
         let name = self.name;
         let description = self.description;
         let width = self.width;
         let length = self.length;
+        let hints = self.hints;
 
-        let (nodes, components) = auto_layout(self.nodes, self.components);
+        let (nodes, components) = auto_layout(self.nodes, self.components, &hints, bounds);
 
         Microcontroller {
             name,
@@ -26,6 +37,7 @@ impl UnpositionedMicrocontroller {
             length,
             nodes,
             components,
+            hints,
         }
     }
 }
@@ -33,383 +45,622 @@ impl UnpositionedMicrocontroller {
 fn auto_layout(
     nodes: Vec<Node>,
     components: Vec<Rc<Component>>,
+    hints: &BTreeMap<usize, LayoutHint>,
+    bounds: &LayoutBounds,
 ) -> (Vec<PositionedNode>, Vec<PositionedComponent>) {
-    let n_nodes = nodes.len();
-    let n_components = components.len();
-    let n_total = n_nodes + n_components;
-
-    // コンポーネントの高さ
-    let mut height_map = HashMap::with_capacity(n_total);
-    for node in &nodes {
-        height_map.insert(ComponentKey::from(node), 2);
-    }
-    for component in &components {
-        height_map.insert(ComponentKey::from(component), component.height());
-    }
-
-    // 連結コンポーネントで島を構築
-    let connection = GraphConnection::new(&nodes, &components);
-    let islands = connection
-        .make_islands()
-        .iter()
-        .map(|i| i.layout(&height_map))
-        .collect::<Vec<_>>();
-
-    // 島を縦に並べる
-    let mut y_offset = 0;
-    let mut position_map = HashMap::with_capacity(n_total);
-    for island in &islands {
-        let bbox = island.bounding_box();
-        for (k, v) in &island.layout_map {
-            let x = v.0 * 5 - bbox.left;
-            let y = y_offset + v.1 - bbox.top;
-            let h = v.2;
-            position_map.insert(*k, (x, -y - h)); // Y座標反転、左下原点
-        }
-        y_offset = bbox.height() + 1;
-    }
-
-    // レイアウト割り当て
-    let mut isolated_y = 0; // 接続のないコンポーネントは左端で縦に並べる
-    let mut pos_nodes = Vec::with_capacity(n_nodes);
-    let mut pos_components = Vec::with_capacity(n_components);
-    for node in nodes {
-        let pos = position_map.remove(&(&node).into()).unwrap_or_else(|| {
-            isolated_y -= *height_map.get(&(&node).into()).unwrap() as i32;
-            (-5, isolated_y)
-        });
-        pos_nodes.push(PositionedNode {
-            inner: node,
-            component_position: ComponentPosition::new(pos.0, pos.1),
-        });
-    }
-    for component in components {
-        let pos = position_map
-            .remove(&(&component).into())
-            .unwrap_or_else(|| {
-                isolated_y -= *height_map.get(&(&component).into()).unwrap() as i32;
-                (-5, isolated_y)
-            });
-        pos_components.push(PositionedComponent {
-            inner: component,
-            position: ComponentPosition::new(pos.0, pos.1),
-        });
-    }
+    let graph = Graph::new(&nodes, &components, hints);
+    let mut positions = graph.layout(bounds).into_iter();
+
+    let pos_nodes = nodes
+        .into_iter()
+        .map(|node| {
+            let (x, y) = positions.next().unwrap();
+            PositionedNode {
+                inner: node,
+                component_position: ComponentPosition::new(x, y),
+            }
+        })
+        .collect();
+    let pos_components = components
+        .into_iter()
+        .map(|component| {
+            let (x, y) = positions.next().unwrap();
+            PositionedComponent {
+                inner: component,
+                position: ComponentPosition::new(x, y),
+            }
+        })
+        .collect();
 
     (pos_nodes, pos_components)
 }
 
-// Rc を HashMap のキーにするために usize に変換
-fn rc_key<T>(v: &Rc<T>) -> usize {
-    Rc::as_ptr(v) as usize
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum VertexKind {
+    InputNode,
+    OutputNode,
+    Component,
 }
 
-fn weak_key<T>(v: &Weak<T>) -> usize {
-    v.as_ptr() as usize
-}
-
-// コンポーネントの識別子
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
-enum ComponentKey {
-    InputNode(usize),
-    OutputNode(usize),
-    Component(usize),
-}
-
-impl From<&Rc<InputNode>> for ComponentKey {
-    fn from(value: &Rc<InputNode>) -> Self {
-        Self::InputNode(rc_key(value))
-    }
-}
-
-impl From<&Rc<RefCell<OutputNode>>> for ComponentKey {
-    fn from(value: &Rc<RefCell<OutputNode>>) -> Self {
-        Self::OutputNode(rc_key(value))
-    }
-}
-
-impl From<&Rc<Component>> for ComponentKey {
-    fn from(value: &Rc<Component>) -> Self {
-        Self::Component(rc_key(value))
-    }
+// ノードとコンポーネントを通し番号で扱うグラフ
+// 0..nodes.len() がノード、それ以降がコンポーネント
+#[derive(Debug)]
+struct Graph {
+    kinds: Vec<VertexKind>,
+    heights: Vec<i32>,
+    // @pos で固定された位置
+    pinned: Vec<Option<(i32, i32)>>,
+    // @group の通し番号
+    groups: Vec<Option<usize>>,
+    // 入力ポート順の接続元 (先頭が主入力)
+    preds: Vec<Vec<usize>>,
+    succs: Vec<Vec<usize>>,
 }
 
-impl From<&Weak<InputNode>> for ComponentKey {
-    fn from(value: &Weak<InputNode>) -> Self {
-        Self::InputNode(weak_key(value))
-    }
-}
+impl Graph {
+    fn new(
+        nodes: &[Node],
+        components: &[Rc<Component>],
+        hints: &BTreeMap<usize, LayoutHint>,
+    ) -> Self {
+        let n_nodes = nodes.len();
+        let n_total = n_nodes + components.len();
+
+        let index = LinkIndex::new(nodes, components);
+        let source = |link: &LinkNode| match index.get(link)? {
+            StableId::Node(i) => Some(i),
+            StableId::Component(i) => Some(n_nodes + i),
+        };
+
+        let mut s = Self {
+            kinds: Vec::with_capacity(n_total),
+            heights: Vec::with_capacity(n_total),
+            pinned: vec![None; n_total],
+            groups: vec![None; n_total],
+            preds: vec![Vec::new(); n_total],
+            succs: vec![Vec::new(); n_total],
+        };
+
+        for (i, node) in nodes.iter().enumerate() {
+            s.heights.push(NODE_HEIGHT);
+            match node {
+                Node::Input(_) => s.kinds.push(VertexKind::InputNode),
+                Node::Output(n) => {
+                    s.kinds.push(VertexKind::OutputNode);
+                    if let Some(from) = n.borrow().input_link_node().as_ref().and_then(source) {
+                        s.connect(from, i);
+                    }
+                }
+            }
+        }
+        for (i, component) in components.iter().enumerate() {
+            s.kinds.push(VertexKind::Component);
+            s.heights.push(component.height() as i32);
+            for link in component.input_links_node().into_iter().flatten() {
+                if let Some(from) = source(link) {
+                    s.connect(from, n_nodes + i);
+                }
+            }
+        }
 
-impl From<&Weak<OutputNode>> for ComponentKey {
-    fn from(value: &Weak<OutputNode>) -> Self {
-        Self::OutputNode(weak_key(value))
-    }
-}
+        let group_names = hints
+            .values()
+            .filter_map(|h| h.group.as_ref())
+            .collect::<BTreeSet<_>>();
+        for (i, hint) in hints.range(..components.len()) {
+            s.pinned[n_nodes + i] = hint.position.as_ref().map(|p| (p.x, p.y));
+            s.groups[n_nodes + i] = hint
+                .group
+                .as_ref()
+                .and_then(|g| group_names.iter().position(|name| *name == g));
+        }
 
-impl From<&Weak<Component>> for ComponentKey {
-    fn from(value: &Weak<Component>) -> Self {
-        Self::Component(weak_key(value))
+        s
     }
-}
 
-impl From<&Node> for ComponentKey {
-    fn from(value: &Node) -> Self {
-        match value {
-            Node::Input(n) => n.into(),
-            Node::Output(n) => n.into(),
+    fn connect(&mut self, from: usize, to: usize) {
+        if !self.preds[to].contains(&from) {
+            self.preds[to].push(from);
+            self.succs[from].push(to);
         }
     }
-}
 
-impl From<&LinkNode> for ComponentKey {
-    fn from(value: &LinkNode) -> Self {
-        match value {
-            LinkNode::Node(n) => n.into(),
-            LinkNode::Component(c, _) => c.into(),
-        }
+    fn len(&self) -> usize {
+        self.kinds.len()
     }
-}
 
-// 各コンポーネントの左右の接続
-#[derive(Default, Debug)]
-struct GraphConnectionItem {
-    left: HashSet<ComponentKey>,
-    right: HashSet<ComponentKey>,
-}
-
-impl GraphConnectionItem {
-    fn to_left(left: ComponentKey) -> Self {
-        Self {
-            left: HashSet::from([left]),
-            right: HashSet::new(),
-        }
+    fn is_isolated(&self, v: usize) -> bool {
+        self.preds[v].is_empty() && self.succs[v].is_empty()
     }
 
-    fn to_right(right: ComponentKey) -> Self {
-        Self {
-            left: HashSet::new(),
-            right: HashSet::from([right]),
+    // 各頂点の位置 (左下原点)
+    fn layout(&self, bounds: &LayoutBounds) -> Vec<(i32, i32)> {
+        let mut positions = vec![(0, 0); self.len()];
+
+        let left = 0.clamp(*bounds.x.start(), *bounds.x.end());
+        let top = 0.clamp(*bounds.y.start(), *bounds.y.end());
+        let max_width = bounds.x.end() - left;
+
+        // 島ごとの (頂点, 左からの位置, 上からの位置) と幅、高さ
+        // 接続のない頂点はそれぞれ 1 つの島として扱う
+        let mut blocks = self
+            .islands()
+            .into_iter()
+            .map(|island| {
+                let (placement, height) = self.layout_island(&island);
+                self.fold_island(placement, height, max_width)
+            })
+            .collect::<Vec<_>>();
+        for v in (0..self.len()).filter(|v| self.is_isolated(*v)) {
+            blocks.push((vec![(v, 0, 0)], VERTEX_WIDTH, self.heights[v]));
         }
-    }
-}
-
-// 全体の接続関係
-#[derive(Default, Debug)]
-struct GraphConnection {
-    inner: HashMap<ComponentKey, GraphConnectionItem>,
-}
-
-impl GraphConnection {
-    fn new(nodes: &[Node], components: &[Rc<Component>]) -> Self {
-        let mut s = Self::with_capacity(nodes.len() + components.len());
 
-        for node in nodes {
-            if let Node::Output(n) = node
-                && let Some(link) = n.borrow().input_link_node().as_ref().map(|i| i.into())
-            {
-                s.connect(link, n.into());
+        // 入出力ノードを含む島を先に、あとは高い順に詰める
+        let has_node = |placement: &[(usize, i32, i32)]| {
+            placement
+                .iter()
+                .any(|(v, ..)| self.kinds[*v] != VertexKind::Component)
+        };
+        blocks.sort_by_key(|(placement, _, height)| (!has_node(placement), -height));
+
+        // 原点から右下に向かって詰める
+        let sizes = blocks.iter().map(|(_, w, h)| (*w, *h)).collect::<Vec<_>>();
+        let offsets = pack(&sizes, max_width);
+        for ((placement, ..), (bx, by)) in blocks.iter().zip(offsets) {
+            for (v, x, y) in placement {
+                let h = self.heights[*v];
+                positions[*v] = (left + bx + x, top - (by + y) - h); // Y座標反転
             }
         }
 
-        for component in components {
-            for link in component.input_links_node().into_iter().flatten() {
-                s.connect(link.into(), component.into());
+        for (v, pinned) in self.pinned.iter().enumerate() {
+            if let Some(p) = pinned {
+                positions[v] = *p;
             }
         }
+        self.avoid_pinned(&mut positions);
 
-        s
+        positions
     }
 
-    fn with_capacity(capacity: usize) -> Self {
-        Self {
-            inner: HashMap::with_capacity(capacity),
+    // 層の並びを左からの位置に直し、幅に収まらない島は層の区切りで折り返して下に積む
+    // 返り値は (頂点, 左からの位置, 上からの位置) と幅、高さ
+    fn fold_island(
+        &self,
+        placement: Vec<(usize, i32, i32)>,
+        height: i32,
+        max_width: i32,
+    ) -> (Vec<(usize, i32, i32)>, i32, i32) {
+        let layers = placement
+            .iter()
+            .map(|(_, layer, _)| layer + 1)
+            .max()
+            .unwrap_or(0);
+        let per_band = ((max_width - VERTEX_WIDTH) / COLUMN_PITCH + 1).max(1);
+        if layers <= per_band {
+            let width = (layers - 1).max(0) * COLUMN_PITCH + VERTEX_WIDTH;
+            let placement = placement
+                .into_iter()
+                .map(|(v, layer, y)| (v, layer * COLUMN_PITCH, y))
+                .collect();
+            return (placement, width, height);
         }
+
+        // 帯ごとの高さを求めてから上に積んだ分だけ下げる
+        let gap = COLUMN_PITCH - VERTEX_WIDTH;
+        let bands = (layers + per_band - 1) / per_band;
+        let mut band_heights = vec![0; bands as usize];
+        for (v, layer, y) in &placement {
+            let band = &mut band_heights[(layer / per_band) as usize];
+            *band = (*band).max(y + self.heights[*v]);
+        }
+        let band_tops = band_heights
+            .iter()
+            .scan(0, |top, h| {
+                let band_top = *top;
+                *top += h + gap;
+                Some(band_top)
+            })
+            .collect::<Vec<_>>();
+        let placement = placement
+            .into_iter()
+            .map(|(v, layer, y)| {
+                let x = (layer % per_band) * COLUMN_PITCH;
+                (v, x, band_tops[(layer / per_band) as usize] + y)
+            })
+            .collect();
+        let width = (per_band - 1) * COLUMN_PITCH + VERTEX_WIDTH;
+        let height = band_tops.last().unwrap() + band_heights.last().unwrap();
+        (placement, width, height)
     }
 
-    fn connect(&mut self, left: ComponentKey, right: ComponentKey) {
-        if let Some(s) = self.inner.get_mut(&right) {
-            s.left.insert(left);
-        } else {
-            self.inner.insert(right, GraphConnectionItem::to_left(left));
+    // 固定位置の頂点を障害物として、重なる頂点を下にずらす
+    fn avoid_pinned(&self, positions: &mut [(i32, i32)]) {
+        let mut placed = (0..self.len())
+            .filter(|v| self.pinned[*v].is_some())
+            .collect::<Vec<_>>();
+        if placed.is_empty() {
+            return;
         }
 
-        if let Some(s) = self.inner.get_mut(&left) {
-            s.right.insert(right);
-        } else {
-            self.inner
-                .insert(left, GraphConnectionItem::to_right(right));
+        // 列ごとに上から順に詰め直す
+        let mut free = (0..self.len())
+            .filter(|v| self.pinned[*v].is_none())
+            .collect::<Vec<_>>();
+        free.sort_by_key(|v| (positions[*v].0, -(positions[*v].1 + self.heights[*v])));
+        for v in free {
+            while let Some(y) = placed
+                .iter()
+                .filter(|w| self.overlaps(positions, v, **w))
+                .map(|w| positions[*w].1)
+                .min()
+            {
+                positions[v].1 = y - self.heights[v];
+            }
+            placed.push(v);
         }
     }
 
-    fn make_islands(&self) -> Vec<Island> {
-        let mut visited = HashSet::new();
-        let mut islands = Vec::new();
+    fn overlaps(&self, positions: &[(i32, i32)], a: usize, b: usize) -> bool {
+        let ((ax, ay), (bx, by)) = (positions[a], positions[b]);
+        ax < bx + VERTEX_WIDTH
+            && bx < ax + VERTEX_WIDTH
+            && ay < by + self.heights[b]
+            && by < ay + self.heights[a]
+    }
 
-        for k in self.inner.keys() {
-            if visited.contains(k) {
+    // 連結成分 (接続のない頂点は除く)
+    fn islands(&self) -> Vec<Vec<usize>> {
+        let mut visited = vec![false; self.len()];
+        let mut islands = Vec::new();
+        for origin in 0..self.len() {
+            if visited[origin] || self.is_isolated(origin) {
                 continue;
             }
-            let island = construct_island(self, *k);
-            for v in &island.all {
-                visited.insert(*v);
+            visited[origin] = true;
+            let mut island = Vec::new();
+            let mut stack = vec![origin];
+            while let Some(v) = stack.pop() {
+                island.push(v);
+                for w in self.preds[v].iter().chain(&self.succs[v]) {
+                    if !visited[*w] {
+                        visited[*w] = true;
+                        stack.push(*w);
+                    }
+                }
             }
+            island.sort();
             islands.push(island);
         }
-
         islands
     }
-}
 
-fn construct_island(connection: &GraphConnection, origin: ComponentKey) -> Island {
-    // item を起点に接続を辿って島を形成する
-    let mut island = Island::default();
-    island.insert(0, origin);
-
-    let c = connection.inner.get(&origin).unwrap();
-    let mut stack = DualStack::new(
-        c.right.iter().map(|v| (1, *v)).collect::<Vec<_>>(),
-        c.left.iter().map(|v| (-1, *v)).collect::<Vec<_>>(),
-    );
-    loop {
-        let p = stack.pop();
-        if p.is_none() {
-            break;
-        }
-        let (x, node) = p.unwrap();
-        if island.contains(&node) {
-            continue;
-        }
-
-        island.insert(x, node);
-        let c = connection.inner.get(&node).unwrap();
-        for v in &c.right {
-            if !island.contains(v) {
-                stack.push_primary((x + 1, *v));
+    // 帰還ループを切るために無視する辺
+    fn back_edges(&self, island: &[usize]) -> BTreeSet<(usize, usize)> {
+        // 0: 未訪問, 1: 探索中, 2: 完了
+        let mut state = vec![0u8; self.len()];
+        let mut back = BTreeSet::new();
+
+        // 入力のない頂点から辿ると信号の流れに沿った向きになる
+        let roots = island
+            .iter()
+            .filter(|v| self.preds[**v].is_empty())
+            .chain(island);
+        for root in roots {
+            if state[*root] != 0 {
+                continue;
             }
-        }
-        for v in &c.left {
-            if !island.contains(v) {
-                stack.push_secondary((x - 1, *v));
+            state[*root] = 1;
+            let mut stack = vec![(*root, 0)];
+            while let Some((v, i)) = stack.pop() {
+                if let Some(w) = self.succs[v].get(i) {
+                    stack.push((v, i + 1));
+                    match state[*w] {
+                        0 => {
+                            state[*w] = 1;
+                            stack.push((*w, 0));
+                        }
+                        1 => {
+                            back.insert((v, *w));
+                        }
+                        _ => {}
+                    }
+                } else {
+                    state[v] = 2;
+                }
             }
         }
+        back
     }
 
-    island
-}
+    // 最長経路法による層の割り当て
+    fn assign_layers(
+        &self,
+        island: &[usize],
+        back: &BTreeSet<(usize, usize)>,
+    ) -> HashMap<usize, i32> {
+        let forward_preds = |v: usize| {
+            self.preds[v]
+                .iter()
+                .copied()
+                .filter(move |p| !back.contains(&(*p, v)))
+        };
+
+        // トポロジカル順に層を決める
+        let mut in_degree = island
+            .iter()
+            .map(|v| (*v, forward_preds(*v).count()))
+            .collect::<HashMap<_, _>>();
+        let mut ready = island
+            .iter()
+            .copied()
+            .filter(|v| in_degree[v] == 0)
+            .collect::<BTreeSet<_>>();
+        let mut order = Vec::with_capacity(island.len());
+        while let Some(v) = ready.pop_first() {
+            order.push(v);
+            for w in &self.succs[v] {
+                if back.contains(&(v, *w)) {
+                    continue;
+                }
+                let d = in_degree.get_mut(w).unwrap();
+                *d -= 1;
+                if *d == 0 {
+                    ready.insert(*w);
+                }
+            }
+        }
 
-// 連結している島
-#[derive(Default, Debug)]
-struct Island {
-    inner: BTreeMap<i32, HashSet<ComponentKey>>,
-    all: HashSet<ComponentKey>,
-}
+        let mut layers = HashMap::with_capacity(island.len());
+        for v in &order {
+            let layer = forward_preds(*v).map(|p| layers[&p] + 1).max().unwrap_or(0);
+            layers.insert(*v, layer);
+        }
 
-impl Island {
-    fn insert(&mut self, k: i32, value: ComponentKey) {
-        if let Some(s) = self.inner.get_mut(&k) {
-            s.insert(value);
-        } else {
-            self.inner.insert(k, HashSet::from([value]));
+        // 定数などの入力のないコンポーネントは接続先の直前に寄せる
+        for v in order.iter().rev() {
+            if self.kinds[*v] == VertexKind::Component && forward_preds(*v).next().is_none() {
+                let nearest = self.succs[*v]
+                    .iter()
+                    .filter(|w| !back.contains(&(*v, **w)))
+                    .map(|w| layers[w] - 1)
+                    .min();
+                if let Some(layer) = nearest {
+                    layers.insert(*v, layer);
+                }
+            }
         }
-        self.all.insert(value);
-    }
 
-    fn contains(&self, value: &ComponentKey) -> bool {
-        self.all.contains(value)
+        // 出力ノードは右端の層にそろえる
+        let max_layer = layers.values().copied().max().unwrap_or(0);
+        for v in island {
+            if self.kinds[*v] == VertexKind::OutputNode {
+                layers.insert(*v, max_layer);
+            }
+        }
+
+        layers
     }
 
-    fn layout(&self, height_map: &HashMap<ComponentKey, u8>) -> LayoutIsland {
-        let mut layout_map = HashMap::with_capacity(self.all.len());
-        let mut x_count = 0;
-        let mut bounding_height = 0;
-        if let Some(min_x) = self.inner.keys().next() {
-            for (x, s) in &self.inner {
-                let x = x - min_x;
-                x_count = x_count.max(x);
-                let mut y = 0;
-                for c in s {
-                    let h = *height_map.get(c).unwrap() as i32;
-                    layout_map.insert(*c, (x, y, h));
-                    y += h;
-                    bounding_height = bounding_height.max(y);
-                }
+    // 島の中の (頂点, 層, 上からの位置) と島の高さ
+    fn layout_island(&self, island: &[usize]) -> (Vec<(usize, i32, i32)>, i32) {
+        let back = self.back_edges(island);
+        let layers = self.assign_layers(island, &back);
+
+        let mut layered = LayeredGraph::new(self, island, &back, &layers);
+        layered.minimize_crossings();
+
+        // 層ごとに上から詰め、主入力と同じ高さにそろえられるならそろえる
+        let mut y_map = HashMap::with_capacity(island.len());
+        let mut placement = Vec::with_capacity(island.len());
+        let mut height = 0;
+        for (layer, order) in layered.layers.iter().enumerate() {
+            let mut cursor = 0;
+            for v in order.iter().copied().filter(|v| *v < self.len()) {
+                let main_input = self.preds[v]
+                    .iter()
+                    .find(|p| !back.contains(&(**p, v)))
+                    .and_then(|p| y_map.get(p).copied());
+                let y = main_input.map_or(cursor, |y: i32| y.max(cursor));
+                cursor = y + self.heights[v];
+                height = height.max(cursor);
+                y_map.insert(v, y);
+                placement.push((v, layer as i32, y));
             }
         }
 
-        LayoutIsland { layout_map }
+        (placement, height)
     }
 }
 
+// 交差削減用の層構造
+// 複数の層をまたぐ辺には中継用の仮の頂点 (番号が graph.len() 以上) を置く
 #[derive(Debug)]
-struct LayoutIsland {
-    layout_map: HashMap<ComponentKey, (i32, i32, i32)>,
+struct LayeredGraph {
+    layers: Vec<Vec<usize>>,
+    // 左の層への接続、右の層への接続
+    up: HashMap<usize, Vec<usize>>,
+    down: HashMap<usize, Vec<usize>>,
+    // 頂点の @group (仮の頂点は None)
+    groups: Vec<Option<usize>>,
 }
 
-impl LayoutIsland {
-    fn bounding_box(&self) -> BoundingBox {
-        let mut left = 0;
-        let mut top = 0;
-        let mut right = 0;
-        let mut bottom = 0;
-        for (x, y, h) in self.layout_map.values() {
-            left = left.min(x * 5);
-            top = top.min(*y);
-            right = right.max(x * 5 + 4);
-            bottom = bottom.max(y + h);
+impl LayeredGraph {
+    fn new(
+        graph: &Graph,
+        island: &[usize],
+        back: &BTreeSet<(usize, usize)>,
+        layers: &HashMap<usize, i32>,
+    ) -> Self {
+        let n_layers = layers.values().copied().max().unwrap_or(0) as usize + 1;
+        let mut s = Self {
+            layers: vec![Vec::new(); n_layers],
+            up: HashMap::new(),
+            down: HashMap::new(),
+            groups: graph.groups.clone(),
+        };
+        for v in island {
+            s.layers[layers[v] as usize].push(*v);
         }
-        BoundingBox {
-            left,
-            top,
-            right,
-            bottom,
+
+        let mut next_dummy = graph.len();
+        for v in island {
+            for w in &graph.succs[*v] {
+                if back.contains(&(*v, *w)) {
+                    continue;
+                }
+                let (from, to) = (layers[v], layers[w]);
+                let mut prev = *v;
+                for layer in from + 1..to {
+                    let dummy = next_dummy;
+                    next_dummy += 1;
+                    s.layers[layer as usize].push(dummy);
+                    s.link(prev, dummy);
+                    prev = dummy;
+                }
+                s.link(prev, *w);
+            }
         }
-    }
-}
 
-#[derive(Debug)]
-struct BoundingBox {
-    left: i32,
-    top: i32,
-    right: i32,
-    bottom: i32,
-}
+        for layer in 0..s.layers.len() {
+            s.cluster_groups(layer);
+        }
+        s
+    }
 
-impl BoundingBox {
-    #[expect(dead_code)]
-    fn width(&self) -> i32 {
-        self.right - self.left
+    // 同じグループの頂点を最初に現れる位置にまとめる
+    fn cluster_groups(&mut self, layer: usize) {
+        let group = |v: &usize| self.groups.get(*v).copied().flatten();
+        let order = &self.layers[layer];
+        let mut clustered = Vec::with_capacity(order.len());
+        let mut done = BTreeSet::new();
+        for v in order {
+            match group(v) {
+                Some(g) if done.insert(g) => {
+                    clustered.extend(order.iter().filter(|w| group(w) == Some(g)));
+                }
+                Some(_) => {}
+                None => clustered.push(*v),
+            }
+        }
+        self.layers[layer] = clustered;
     }
 
-    fn height(&self) -> i32 {
-        self.bottom - self.top
+    fn link(&mut self, from: usize, to: usize) {
+        self.down.entry(from).or_default().push(to);
+        self.up.entry(to).or_default().push(from);
     }
-}
 
-// primary を消費しきってから secondary に行く優先度つき stack
-#[derive(Default, Debug)]
-struct DualStack<T> {
-    primary: Vec<T>,
-    secondary: Vec<T>,
-}
+    // 重心法で各層の並びを決める
+    fn minimize_crossings(&mut self) {
+        let mut best = self.layers.clone();
+        let mut best_crossings = self.crossings();
+
+        for sweep in 0..ORDERING_SWEEPS {
+            if sweep % 2 == 0 {
+                for layer in 1..self.layers.len() {
+                    self.reorder(layer, layer - 1, true);
+                }
+            } else {
+                for layer in (0..self.layers.len().saturating_sub(1)).rev() {
+                    self.reorder(layer, layer + 1, false);
+                }
+            }
+
+            let crossings = self.crossings();
+            if crossings < best_crossings {
+                best = self.layers.clone();
+                best_crossings = crossings;
+            }
+            if best_crossings == 0 {
+                break;
+            }
+        }
 
-impl<T> DualStack<T> {
-    fn new(primary: Vec<T>, secondary: Vec<T>) -> Self {
-        Self { primary, secondary }
+        self.layers = best;
     }
 
-    fn push_primary(&mut self, value: T) {
-        self.primary.push(value);
+    fn reorder(&mut self, layer: usize, fixed: usize, use_up: bool) {
+        let position = position_map(&self.layers[fixed]);
+        let neighbors = if use_up { &self.up } else { &self.down };
+
+        let mut keyed = self.layers[layer]
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let positions = neighbors
+                    .get(v)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|w| position.get(w))
+                    .collect::<Vec<_>>();
+                // 接続のない頂点は今の位置を保つ
+                let barycenter = if positions.is_empty() {
+                    i as f32
+                } else {
+                    positions.iter().map(|p| **p as f32).sum::<f32>() / positions.len() as f32
+                };
+                (barycenter, *v)
+            })
+            .collect::<Vec<_>>();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.layers[layer] = keyed.into_iter().map(|(_, v)| v).collect();
+        self.cluster_groups(layer);
     }
 
-    fn push_secondary(&mut self, value: T) {
-        self.secondary.push(value);
+    fn crossings(&self) -> usize {
+        let mut count = 0;
+        for pair in self.layers.windows(2) {
+            let left = position_map(&pair[0]);
+            let right = position_map(&pair[1]);
+            let edges = pair[0]
+                .iter()
+                .flat_map(|v| {
+                    self.down
+                        .get(v)
+                        .into_iter()
+                        .flatten()
+                        .map(|w| (left[v], right[w]))
+                })
+                .collect::<Vec<_>>();
+            for (i, a) in edges.iter().enumerate() {
+                for b in &edges[i + 1..] {
+                    if (a.0 < b.0 && a.1 > b.1) || (a.0 > b.0 && a.1 < b.1) {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
     }
+}
 
-    fn pop(&mut self) -> Option<T> {
-        if !self.primary.is_empty() {
-            self.primary.pop()
-        } else {
-            self.secondary.pop()
+// 棚詰めで矩形を順に並べ、各矩形の左上の位置 (右・下向き) を返す
+// 行の幅は全体が正方形に近くなるように決め、max_width を超えないようにする
+fn pack(sizes: &[(i32, i32)], max_width: i32) -> Vec<(i32, i32)> {
+    let gap = COLUMN_PITCH - VERTEX_WIDTH;
+    let area = sizes
+        .iter()
+        .map(|(w, h)| (w + gap) as f64 * (h + gap) as f64)
+        .sum::<f64>();
+    let widest = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+    let row_width = (area.sqrt().ceil() as i32).min(max_width).max(widest);
+
+    let mut positions = Vec::with_capacity(sizes.len());
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for (w, h) in sizes {
+        if x > 0 && x + w > row_width {
+            x = 0;
+            y += row_height + gap;
+            row_height = 0;
         }
+        positions.push((x, y));
+        x += w + gap;
+        row_height = row_height.max(*h);
     }
+    positions
+}
+
+fn position_map(order: &[usize]) -> HashMap<usize, usize> {
+    order.iter().enumerate().map(|(i, v)| (*v, i)).collect()
 }
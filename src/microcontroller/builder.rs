@@ -0,0 +1,107 @@
+use super::{
+    Component, LayoutHint, Link, Node, NodeInner, NodePosition, NodeType, OutputNode,
+    UnpositionedMicrocontroller,
+};
+use crate::compile_error::CompileErrorType;
+
+use std::{cell::RefCell, rc::Rc};
+
+// Rust から直接コンポーネントのグラフを組み立てる
+// リンクは Weak で持つため、コンポーネントはビルダーに追加して生存させる
+#[derive(Default, Debug)]
+pub struct MicrocontrollerBuilder {
+    mc: UnpositionedMicrocontroller,
+}
+
+impl MicrocontrollerBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            mc: UnpositionedMicrocontroller {
+                name: name.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn description(&mut self, description: impl Into<String>) -> &mut Self {
+        self.mc.description = description.into();
+        self
+    }
+
+    pub fn size(&mut self, width: u8, length: u8) -> &mut Self {
+        self.mc.width = width;
+        self.mc.length = length;
+        self
+    }
+
+    pub fn input(
+        &mut self,
+        label: impl Into<String>,
+        node_type: NodeType,
+        position: NodePosition,
+    ) -> Link {
+        let node = Node::new_input(node_inner(label, position), node_type);
+        let Node::Input(n) = &node else {
+            unreachable!()
+        };
+        let link = Link::node(n);
+        self.mc.nodes.push(node);
+        link
+    }
+
+    // link が None なら未接続の出力ノードになる
+    pub fn output(
+        &mut self,
+        label: impl Into<String>,
+        node_type: NodeType,
+        position: NodePosition,
+        link: Option<Link>,
+    ) -> Result<Rc<RefCell<OutputNode>>, CompileErrorType> {
+        let node = Node::new_output(node_inner(label, position), node_type);
+        let Node::Output(n) = &node else {
+            unreachable!()
+        };
+        let n = n.clone();
+        if let Some(link) = link {
+            let found_type = link.node_type();
+            if !n.borrow_mut().set_input_link(link) {
+                return Err(CompileErrorType::IncompatibleNodeType {
+                    expected_type: node_type,
+                    found_type,
+                });
+            }
+        }
+        self.mc.nodes.push(node);
+        Ok(n)
+    }
+
+    pub fn component(&mut self, component: Component) -> Rc<Component> {
+        let c = Rc::new(component);
+        self.mc.components.push(c.clone());
+        c
+    }
+
+    pub fn hint(&mut self, component: &Rc<Component>, hint: LayoutHint) -> &mut Self {
+        if let Some(i) = self
+            .mc
+            .components
+            .iter()
+            .position(|c| Rc::ptr_eq(c, component))
+        {
+            self.mc.hints.entry(i).or_default().merge(hint);
+        }
+        self
+    }
+
+    pub fn build(self) -> UnpositionedMicrocontroller {
+        self.mc
+    }
+}
+
+fn node_inner(label: impl Into<String>, position: NodePosition) -> NodeInner {
+    NodeInner {
+        label: label.into(),
+        description: String::new(),
+        position,
+    }
+}
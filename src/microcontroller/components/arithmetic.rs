@@ -1,9 +1,8 @@
-use super::{ComponentData, LinkNode, NodeType, NumberLink, single_attr};
+use super::{BoolLink, ComponentData, LinkNode, NodeType, NumberLink, single_attr};
 use crate::xml_schema::{ObjectValue, ObjectValueTag};
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-#[expect(dead_code)]
 #[derive(strum::Display, Debug)]
 #[repr(u8)]
 pub enum ArithmeticComponent {
@@ -42,6 +41,22 @@ pub enum ArithmeticComponent {
     ConstantNumber {
         value: f32,
     },
+    #[strum(to_string = "Greater Than")]
+    GreaterThan {
+        input_a: NumberLink,
+        input_b: NumberLink,
+    },
+    #[strum(to_string = "Less Than")]
+    LessThan {
+        input_a: NumberLink,
+        input_b: NumberLink,
+    },
+    #[strum(to_string = "Numerical Switchbox")]
+    NumericalSwitchbox {
+        on_value: NumberLink,
+        off_value: NumberLink,
+        switch: BoolLink,
+    },
     Delta {
         input: NumberLink,
     },
@@ -74,6 +89,83 @@ pub enum ArithmeticComponent {
     },
 }
 
+impl ArithmeticComponent {
+    // 種類だけを合わせた未接続のコンポーネント
+    pub fn placeholder(component_type: u8) -> Option<Self> {
+        let n = NumberLink::default;
+        Some(match component_type {
+            6 => Self::Add {
+                input_a: n(),
+                input_b: n(),
+            },
+            7 => Self::Subtract {
+                input_a: n(),
+                input_b: n(),
+            },
+            8 => Self::Multiply {
+                input_a: n(),
+                input_b: n(),
+            },
+            9 => Self::Divide {
+                input_a: n(),
+                input_b: n(),
+            },
+            10 => Self::Function3 {
+                input_x: n(),
+                input_y: n(),
+                input_z: n(),
+                function: String::new(),
+            },
+            11 => Self::Clamp {
+                input: n(),
+                min: 0.0,
+                max: 0.0,
+            },
+            14 => Self::Abs { input: n() },
+            15 => Self::ConstantNumber { value: 0.0 },
+            17 => Self::GreaterThan {
+                input_a: n(),
+                input_b: n(),
+            },
+            18 => Self::LessThan {
+                input_a: n(),
+                input_b: n(),
+            },
+            22 => Self::NumericalSwitchbox {
+                on_value: n(),
+                off_value: n(),
+                switch: BoolLink::default(),
+            },
+            35 => Self::Delta { input: n() },
+            36 => Self::Function8 {
+                input_x: n(),
+                input_y: n(),
+                input_z: n(),
+                input_w: n(),
+                input_a: n(),
+                input_b: n(),
+                input_c: n(),
+                input_d: n(),
+                function: String::new(),
+            },
+            38 => Self::Modulo {
+                input_a: n(),
+                input_b: n(),
+            },
+            42 => Self::Equal {
+                input_a: n(),
+                input_b: n(),
+                epsilon: 0.0,
+            },
+            45 => Self::Function1 {
+                input_x: n(),
+                function: String::new(),
+            },
+            _ => return None,
+        })
+    }
+}
+
 impl ComponentData for ArithmeticComponent {
     fn component_type(&self) -> u8 {
         match self {
@@ -85,6 +177,9 @@ impl ComponentData for ArithmeticComponent {
             Self::Clamp { .. } => 11,
             Self::Abs { .. } => 14,
             Self::ConstantNumber { .. } => 15,
+            Self::GreaterThan { .. } => 17,
+            Self::LessThan { .. } => 18,
+            Self::NumericalSwitchbox { .. } => 22,
             Self::Delta { .. } => 35,
             Self::Function8 { .. } => 36,
             Self::Modulo { .. } => 38,
@@ -105,8 +200,10 @@ impl ComponentData for ArithmeticComponent {
             | Self::Multiply { .. }
             | Self::Divide { .. }
             | Self::Modulo { .. }
-            | Self::Equal { .. } => 3,
-            Self::Function3 { .. } => 4,
+            | Self::Equal { .. }
+            | Self::GreaterThan { .. }
+            | Self::LessThan { .. } => 3,
+            Self::Function3 { .. } | Self::NumericalSwitchbox { .. } => 4,
             Self::Function8 { .. } => 9,
         }
     }
@@ -118,9 +215,16 @@ impl ComponentData for ArithmeticComponent {
             | Self::Multiply { input_a, input_b }
             | Self::Divide { input_a, input_b }
             | Self::Modulo { input_a, input_b }
+            | Self::GreaterThan { input_a, input_b }
+            | Self::LessThan { input_a, input_b }
             | Self::Equal {
                 input_a, input_b, ..
             } => vec![input_a, input_b],
+            Self::NumericalSwitchbox {
+                on_value,
+                off_value,
+                switch,
+            } => vec![on_value, off_value, switch],
             Self::Function3 {
                 input_x,
                 input_y,
@@ -148,7 +252,7 @@ impl ComponentData for ArithmeticComponent {
         }
     }
 
-    fn attrs(&self) -> Option<HashMap<String, String>> {
+    fn attrs(&self) -> Option<BTreeMap<String, String>> {
         match self {
             Self::Function1 { function, .. }
             | Self::Function3 { function, .. }
@@ -185,13 +289,16 @@ impl ComponentData for ArithmeticComponent {
             | Self::Delta { .. }
             | Self::Function8 { .. }
             | Self::Modulo { .. }
+            | Self::NumericalSwitchbox { .. }
             | Self::Function1 { .. } => (index == 0).then_some(NodeType::Number),
             Self::Divide { .. } => match index {
                 0 => Some(NodeType::Number),
                 1 => Some(NodeType::Bool),
                 _ => None,
             },
-            Self::Equal { .. } => (index == 0).then_some(NodeType::Bool),
+            Self::Equal { .. } | Self::GreaterThan { .. } | Self::LessThan { .. } => {
+                (index == 0).then_some(NodeType::Bool)
+            }
         }
     }
 
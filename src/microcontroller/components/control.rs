@@ -0,0 +1,118 @@
+use super::{ComponentData, LinkNode, NodeType};
+use crate::{
+    microcontroller::{BoolLink, NumberLink},
+    xml_schema::{ObjectValue, ObjectValueTag},
+};
+
+use std::collections::BTreeMap;
+
+#[derive(strum::Display, Debug)]
+pub enum ControlComponent {
+    // 係数を入力で受け取る
+    #[strum(to_string = "PID Controller")]
+    Pid {
+        setpoint: NumberLink,
+        process_variable: NumberLink,
+        proportional: NumberLink,
+        integral: NumberLink,
+        derivative: NumberLink,
+        active: BoolLink,
+    },
+    // 係数をプロパティで持つ
+    #[strum(to_string = "PID Controller (Advanced)")]
+    PidAdvanced {
+        setpoint: NumberLink,
+        process_variable: NumberLink,
+        active: BoolLink,
+        kp: f32,
+        ki: f32,
+        kd: f32,
+    },
+}
+
+impl ControlComponent {
+    // 種類だけを合わせた未接続のコンポーネント
+    pub fn placeholder(component_type: u8) -> Option<Self> {
+        let n = NumberLink::default;
+        Some(match component_type {
+            23 => Self::Pid {
+                setpoint: n(),
+                process_variable: n(),
+                proportional: n(),
+                integral: n(),
+                derivative: n(),
+                active: BoolLink::default(),
+            },
+            39 => Self::PidAdvanced {
+                setpoint: n(),
+                process_variable: n(),
+                active: BoolLink::default(),
+                kp: 0.0,
+                ki: 0.0,
+                kd: 0.0,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl ComponentData for ControlComponent {
+    fn component_type(&self) -> u8 {
+        match self {
+            Self::Pid { .. } => 23,
+            Self::PidAdvanced { .. } => 39,
+        }
+    }
+
+    fn height(&self) -> u8 {
+        match self {
+            Self::Pid { .. } => 7,
+            Self::PidAdvanced { .. } => 4,
+        }
+    }
+
+    fn input_links_node(&self) -> Vec<&Option<LinkNode>> {
+        match self {
+            Self::Pid {
+                setpoint,
+                process_variable,
+                proportional,
+                integral,
+                derivative,
+                active,
+            } => vec![
+                setpoint,
+                process_variable,
+                proportional,
+                integral,
+                derivative,
+                active,
+            ],
+            Self::PidAdvanced {
+                setpoint,
+                process_variable,
+                active,
+                ..
+            } => vec![setpoint, process_variable, active],
+        }
+    }
+
+    fn attrs(&self) -> Option<BTreeMap<String, String>> {
+        None
+    }
+
+    fn value_list(&self) -> Option<Vec<(ObjectValueTag, ObjectValue)>> {
+        match self {
+            Self::Pid { .. } => None,
+            Self::PidAdvanced { kp, ki, kd, .. } => Some(vec![
+                (ObjectValueTag::Kp, ObjectValue::new(*kp)),
+                (ObjectValueTag::Ki, ObjectValue::new(*ki)),
+                (ObjectValueTag::Kd, ObjectValue::new(*kd)),
+            ]),
+        }
+    }
+
+    fn output_type(&self, index: usize) -> Option<NodeType> {
+        (index == 0).then_some(NodeType::Number)
+    }
+}
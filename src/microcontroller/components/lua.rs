@@ -0,0 +1,52 @@
+use super::{ComponentData, LinkNode, NodeType, single_attr};
+use crate::{
+    microcontroller::{CompositeLink, VideoLink},
+    xml_schema::{ObjectValue, ObjectValueTag},
+};
+
+use std::{collections::BTreeMap, fmt::Display};
+
+// Lua スクリプト
+// コンポジットと映像を受け取り、コンポジットと映像を出力する
+#[derive(Debug)]
+pub struct LuaComponent {
+    pub composite: CompositeLink,
+    pub video: VideoLink,
+    pub script: String,
+}
+
+impl Display for LuaComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Lua Script")
+    }
+}
+
+impl ComponentData for LuaComponent {
+    fn component_type(&self) -> u8 {
+        56
+    }
+
+    fn height(&self) -> u8 {
+        3
+    }
+
+    fn input_links_node(&self) -> Vec<&Option<LinkNode>> {
+        vec![&self.composite, &self.video]
+    }
+
+    fn attrs(&self) -> Option<BTreeMap<String, String>> {
+        Some(single_attr("script", self.script.clone()))
+    }
+
+    fn value_list(&self) -> Option<Vec<(ObjectValueTag, ObjectValue)>> {
+        None
+    }
+
+    fn output_type(&self, index: usize) -> Option<NodeType> {
+        match index {
+            0 => Some(NodeType::Composite),
+            1 => Some(NodeType::Video),
+            _ => None,
+        }
+    }
+}
@@ -1,18 +1,24 @@
 mod arithmetic;
+mod composite;
+mod control;
+mod lua;
 pub use arithmetic::ArithmeticComponent;
+pub use composite::CompositeComponent;
+pub use control::ControlComponent;
+pub use lua::LuaComponent;
 
-use super::{LinkNode, NodeType, NumberLink};
+use super::{BoolLink, LinkNode, NodeType, NumberLink};
 use crate::xml_schema::{ObjectValue, ObjectValueTag};
 
 use enum_dispatch::enum_dispatch;
-use std::{borrow::Cow, collections::HashMap, fmt::Display};
+use std::{borrow::Cow, collections::BTreeMap, fmt::Display};
 
 #[enum_dispatch]
 pub trait ComponentData {
     fn component_type(&self) -> u8;
     fn height(&self) -> u8;
     fn input_links_node(&self) -> Vec<&Option<LinkNode>>;
-    fn attrs(&self) -> Option<HashMap<String, String>>;
+    fn attrs(&self) -> Option<BTreeMap<String, String>>;
     fn value_list(&self) -> Option<Vec<(ObjectValueTag, ObjectValue)>>;
     fn output_type(&self, index: usize) -> Option<NodeType>;
     //fn inputs(&self) -> Cow<'static, [ComponentNode<'static>]>;
@@ -23,21 +29,44 @@ pub trait ComponentData {
 #[enum_dispatch(ComponentData)]
 pub enum Component {
     Arithmetic(ArithmeticComponent),
+    Composite(CompositeComponent),
+    Control(ControlComponent),
+    Lua(LuaComponent),
+}
+
+impl Component {
+    // XML の type から、表示用の未接続のコンポーネントを作る
+    pub fn placeholder(component_type: u8) -> Option<Self> {
+        match component_type {
+            56 => Some(Self::Lua(LuaComponent {
+                composite: Default::default(),
+                video: Default::default(),
+                script: String::new(),
+            })),
+            _ => ArithmeticComponent::placeholder(component_type)
+                .map(Self::Arithmetic)
+                .or_else(|| CompositeComponent::placeholder(component_type).map(Self::Composite))
+                .or_else(|| ControlComponent::placeholder(component_type).map(Self::Control)),
+        }
+    }
 }
 
 impl Display for Component {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Arithmetic(c) => Display::fmt(c, f),
+            Self::Composite(c) => Display::fmt(c, f),
+            Self::Control(c) => Display::fmt(c, f),
+            Self::Lua(c) => Display::fmt(c, f),
         }
     }
 }
 
-fn single_attr(name: &str, value: String) -> HashMap<String, String> {
+fn single_attr(name: &str, value: String) -> BTreeMap<String, String> {
     if value.is_empty() {
-        HashMap::new()
+        BTreeMap::new()
     } else {
-        HashMap::from([(name.to_owned(), value)])
+        BTreeMap::from([(name.to_owned(), value)])
     }
 }
 
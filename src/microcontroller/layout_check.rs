@@ -0,0 +1,88 @@
+use super::{ComponentData as _, PositionedMicrocontroller, StableId};
+
+use std::ops::RangeInclusive;
+
+// ComponentPosition の単位 (エディタの 1/4 マス) で表す
+pub const VERTEX_WIDTH: i32 = 4;
+pub const NODE_HEIGHT: i32 = 2;
+
+// 自動配置で使う範囲 (左下原点)
+// ゲーム側の上限は確かめられていないので、エディタで見渡せる ±64 マスを目安にしている
+// 範囲外に置いてもコンパイルは止めず、警告にとどめる
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct LayoutBounds {
+    pub x: RangeInclusive<i32>,
+    pub y: RangeInclusive<i32>,
+}
+
+impl Default for LayoutBounds {
+    fn default() -> Self {
+        Self {
+            x: -256..=256,
+            y: -256..=256,
+        }
+    }
+}
+
+impl LayoutBounds {
+    // (x, y) を左下とする幅 w 高さ h の矩形が収まるか
+    pub fn contains(&self, x: i32, y: i32, w: i32, h: i32) -> bool {
+        self.x.contains(&x)
+            && self.x.contains(&(x + w))
+            && self.y.contains(&y)
+            && self.y.contains(&(y + h))
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum LayoutError {
+    Overlap(StableId, StableId),
+    OutOfBounds(StableId),
+}
+
+impl PositionedMicrocontroller {
+    // 重なりと範囲外の配置を調べる
+    pub fn check_layout(&self, bounds: &LayoutBounds) -> Vec<LayoutError> {
+        let mut rects = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let p = &n.component_position;
+                (StableId::Node(i), p.x, p.y, NODE_HEIGHT)
+            })
+            .chain(self.components.iter().enumerate().map(|(i, c)| {
+                let p = &c.position;
+                (StableId::Component(i), p.x, p.y, c.height() as i32)
+            }))
+            .collect::<Vec<_>>();
+
+        let mut errors = rects
+            .iter()
+            .filter(|(_, x, y, h)| !bounds.contains(*x, *y, VERTEX_WIDTH, *h))
+            .map(|(id, ..)| LayoutError::OutOfBounds(*id))
+            .collect::<Vec<_>>();
+
+        // x 順に並べて、幅の範囲内にあるものだけ比べる
+        rects.sort_by_key(|(id, x, ..)| (*x, *id));
+        for (i, (a, ax, ay, ah)) in rects.iter().enumerate() {
+            for (b, bx, by, bh) in &rects[i + 1..] {
+                if *bx >= ax + VERTEX_WIDTH {
+                    break;
+                }
+                if ay < &(by + bh) && by < &(ay + ah) {
+                    errors.push(LayoutError::Overlap((*a).min(*b), (*a).max(*b)));
+                }
+            }
+        }
+        errors
+    }
+
+    // 診断用の表示名
+    pub fn vertex_name(&self, id: StableId) -> String {
+        match id {
+            StableId::Node(i) => format!("node {:?}", self.nodes[i].label_owned()),
+            StableId::Component(i) => format!("component c{} {}", i, *self.components[i].inner),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use super::ComponentPosition;
+
+// ソースの @pos, @group, @note で指定されたレイアウトの指示
+#[derive(PartialEq, Default, Clone, Debug)]
+pub struct LayoutHint {
+    // 固定する位置 (左下原点)
+    pub position: Option<ComponentPosition>,
+    pub group: Option<String>,
+    pub note: Option<String>,
+}
+
+impl LayoutHint {
+    // 後から指定された値で上書きする
+    pub fn merge(&mut self, other: LayoutHint) {
+        if other.position.is_some() {
+            self.position = other.position;
+        }
+        if other.group.is_some() {
+            self.group = other.group;
+        }
+        if other.note.is_some() {
+            self.note = other.note;
+        }
+    }
+}
@@ -71,6 +71,16 @@ impl Link {
         }
     }
 
+    pub fn link_node(&self) -> &Option<LinkNode> {
+        match self {
+            Self::Bool(l) => l,
+            Self::Number(l) => l,
+            Self::Composite(l) => l,
+            Self::Video(l) => l,
+            Self::Audio(l) => l,
+        }
+    }
+
     pub fn node_type(&self) -> NodeType {
         match self {
             Self::Bool(_) => NodeType::Bool,
@@ -0,0 +1,52 @@
+use super::{Component, InputNode, LinkNode, Node};
+
+use std::{collections::HashMap, rc::Rc};
+
+// ノード・コンポーネントの並び順で決まる番号
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub enum StableId {
+    Node(usize),
+    Component(usize),
+}
+
+// リンク先の StableId を引く
+// ポインタは検索のキーにだけ使い、走査は常に番号順で行う
+#[derive(Debug)]
+pub struct LinkIndex {
+    nodes: HashMap<*const InputNode, usize>,
+    components: HashMap<*const Component, usize>,
+}
+
+impl LinkIndex {
+    pub fn new<'a>(
+        nodes: impl IntoIterator<Item = &'a Node>,
+        components: impl IntoIterator<Item = &'a Rc<Component>>,
+    ) -> Self {
+        Self {
+            nodes: nodes
+                .into_iter()
+                .enumerate()
+                .filter_map(|(i, n)| match n {
+                    Node::Input(n) => Some((Rc::as_ptr(n), i)),
+                    Node::Output(_) => None,
+                })
+                .collect(),
+            components: components
+                .into_iter()
+                .enumerate()
+                .map(|(i, c)| (Rc::as_ptr(c), i))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, link: &LinkNode) -> Option<StableId> {
+        match link {
+            LinkNode::Node(n) => self.nodes.get(&n.as_ptr()).copied().map(StableId::Node),
+            LinkNode::Component(c, _) => self
+                .components
+                .get(&c.as_ptr())
+                .copied()
+                .map(StableId::Component),
+        }
+    }
+}
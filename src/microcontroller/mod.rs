@@ -1,11 +1,26 @@
 mod auto_layout;
+mod builder;
 mod components;
+mod layout_check;
+mod layout_hint;
 mod link;
+mod link_index;
 mod node;
-
-pub use components::{ArithmeticComponent, Component, ComponentData};
+mod optimize;
+mod simulate;
+
+pub use builder::MicrocontrollerBuilder;
+pub use components::{
+    ArithmeticComponent, Component, ComponentData, CompositeComponent, ControlComponent,
+    LuaComponent,
+};
+pub use layout_check::{LayoutBounds, LayoutError, NODE_HEIGHT, VERTEX_WIDTH};
+pub use layout_hint::LayoutHint;
 pub use link::{AudioLink, BoolLink, CompositeLink, Link, LinkNode, NumberLink, VideoLink};
+pub use link_index::{LinkIndex, StableId};
 pub use node::{InputNode, Node, NodeInner, NodeMode, NodePosition, NodeType, OutputNode};
+pub use optimize::OptLevel;
+pub use simulate::{SignalValue, SimulationError, Simulator};
 
 use crate::xml_schema;
 
@@ -23,6 +38,8 @@ pub struct Microcontroller<N, C> {
     pub length: u8,
     pub nodes: Vec<N>,
     pub components: Vec<C>,
+    // components の添字 -> レイアウトの指示
+    pub hints: BTreeMap<usize, LayoutHint>,
 }
 
 impl<N, C> Default for Microcontroller<N, C> {
@@ -34,6 +51,7 @@ impl<N, C> Default for Microcontroller<N, C> {
             length: 1,
             nodes: Vec::new(),
             components: Vec::new(),
+            hints: BTreeMap::new(),
         }
     }
 }
@@ -107,7 +125,7 @@ fn option_f32(value: f32) -> Option<f32> {
     to_option(value, 0.0)
 }
 
-fn option_component_pos(value: ComponentPosition) -> Option<xml_schema::ComponentPos> {
+pub(crate) fn option_component_pos(value: ComponentPosition) -> Option<xml_schema::ComponentPos> {
     if value.x == 0 && value.y == 0 {
         None
     } else {
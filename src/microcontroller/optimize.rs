@@ -0,0 +1,79 @@
+use super::{
+    Component, LinkIndex, Node, StableId, UnpositionedMicrocontroller,
+    components::ComponentData as _,
+};
+
+use num_enum::TryFromPrimitive;
+use serde::Deserialize;
+use std::rc::Rc;
+
+#[derive(TryFromPrimitive, Deserialize, PartialEq, PartialOrd, Default, Clone, Copy, Debug)]
+#[serde(try_from = "u8")]
+#[repr(u8)]
+pub enum OptLevel {
+    // 生成したコンポーネントをそのまま出力
+    #[default]
+    None = 0,
+    // 出力ノードに繋がっていないコンポーネントを削除
+    DeadComponents = 1,
+}
+
+impl UnpositionedMicrocontroller {
+    pub fn optimize(mut self, level: OptLevel) -> Self {
+        if level >= OptLevel::DeadComponents {
+            let alive = alive_components(&self.nodes, &self.components);
+
+            // 削除で詰めた添字に合わせてレイアウトの指示を付け替える
+            let mut new_index = vec![None; alive.len()];
+            let mut count = 0;
+            for (i, alive) in alive.iter().enumerate() {
+                if *alive {
+                    new_index[i] = Some(count);
+                    count += 1;
+                }
+            }
+            self.hints = std::mem::take(&mut self.hints)
+                .into_iter()
+                .filter_map(|(i, hint)| Some((new_index.get(i).copied().flatten()?, hint)))
+                .collect();
+            self.components = self
+                .components
+                .into_iter()
+                .zip(alive)
+                .filter_map(|(c, alive)| alive.then_some(c))
+                .collect();
+        }
+        self
+    }
+}
+
+// 出力ノードに繋がっているコンポーネント
+fn alive_components(nodes: &[Node], components: &[Rc<Component>]) -> Vec<bool> {
+    let index = LinkIndex::new(nodes, components);
+
+    // 出力ノードから入力側へ辿る
+    let mut stack = Vec::new();
+    for node in nodes {
+        if let Node::Output(n) = node
+            && let Some(link) = n.borrow().input_link_node()
+            && let Some(StableId::Component(i)) = index.get(link)
+        {
+            stack.push(i);
+        }
+    }
+
+    let mut alive = vec![false; components.len()];
+    while let Some(i) = stack.pop() {
+        if alive[i] {
+            continue;
+        }
+        alive[i] = true;
+        for link in components[i].input_links_node().into_iter().flatten() {
+            if let Some(StableId::Component(j)) = index.get(link) {
+                stack.push(j);
+            }
+        }
+    }
+
+    alive
+}
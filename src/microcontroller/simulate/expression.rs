@@ -0,0 +1,205 @@
+// f(x) コンポーネントの数式
+#[derive(Debug)]
+pub enum FunctionExpr {
+    Number(f32),
+    Variable(usize),
+    Neg(Box<FunctionExpr>),
+    Binary(char, Box<FunctionExpr>, Box<FunctionExpr>),
+    Call(String, Vec<FunctionExpr>),
+}
+
+const VARIABLES: [&str; 8] = ["x", "y", "z", "w", "a", "b", "c", "d"];
+
+impl FunctionExpr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut p = ExprParser {
+            chars: s.chars().filter(|c| !c.is_whitespace()).collect(),
+            pos: 0,
+        };
+        let e = p.add()?;
+        if p.pos < p.chars.len() {
+            return Err(format!("Unexpected `{}`", p.chars[p.pos]));
+        }
+        Ok(e)
+    }
+
+    pub fn evaluate(&self, vars: &[f32]) -> Result<f32, String> {
+        Ok(match self {
+            Self::Number(v) => *v,
+            Self::Variable(i) => vars.get(*i).copied().unwrap_or(0.0),
+            Self::Neg(e) => -e.evaluate(vars)?,
+            Self::Binary(op, l, r) => {
+                let (l, r) = (l.evaluate(vars)?, r.evaluate(vars)?);
+                match op {
+                    '+' => l + r,
+                    '-' => l - r,
+                    '*' => l * r,
+                    '/' => l / r,
+                    '%' => l % r,
+                    '^' => l.powf(r),
+                    _ => unreachable!(),
+                }
+            }
+            Self::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|a| a.evaluate(vars))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(name, &args)?
+            }
+        })
+    }
+}
+
+fn call(name: &str, args: &[f32]) -> Result<f32, String> {
+    let v = match (name, args) {
+        ("abs", [x]) => x.abs(),
+        ("sin", [x]) => x.sin(),
+        ("cos", [x]) => x.cos(),
+        ("tan", [x]) => x.tan(),
+        ("asin", [x]) => x.asin(),
+        ("acos", [x]) => x.acos(),
+        ("atan", [x]) => x.atan(),
+        ("atan2", [y, x]) => y.atan2(*x),
+        ("sqrt", [x]) => x.sqrt(),
+        ("exp", [x]) => x.exp(),
+        ("log", [x]) => x.ln(),
+        ("floor", [x]) => x.floor(),
+        ("ceil", [x]) => x.ceil(),
+        ("round", [x]) => x.round(),
+        ("sgn", [x]) => {
+            if *x == 0.0 {
+                0.0
+            } else {
+                x.signum()
+            }
+        }
+        ("min", [a, b]) => a.min(*b),
+        ("max", [a, b]) => a.max(*b),
+        ("pow", [a, b]) => a.powf(*b),
+        ("fmod", [a, b]) => a % b,
+        ("clamp", [x, min, max]) => x.max(*min).min(*max),
+        ("lerp", [a, b, t]) => a + (b - a) * t,
+        _ => {
+            return Err(format!(
+                "Unknown function `{}` with {} arguments",
+                name,
+                args.len()
+            ));
+        }
+    };
+    Ok(v)
+}
+
+struct ExprParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn add(&mut self) -> Result<FunctionExpr, String> {
+        let mut lhs = self.mul()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            lhs = FunctionExpr::Binary(op, Box::new(lhs), Box::new(self.mul()?));
+        }
+        Ok(lhs)
+    }
+
+    fn mul(&mut self) -> Result<FunctionExpr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.pos += 1;
+            lhs = FunctionExpr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<FunctionExpr, String> {
+        if self.eat('-') {
+            Ok(FunctionExpr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.pow()
+        }
+    }
+
+    // べき乗は右結合
+    fn pow(&mut self) -> Result<FunctionExpr, String> {
+        let base = self.primary()?;
+        if self.eat('^') {
+            Ok(FunctionExpr::Binary(
+                '^',
+                Box::new(base),
+                Box::new(self.unary()?),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn primary(&mut self) -> Result<FunctionExpr, String> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let e = self.add()?;
+                if !self.eat(')') {
+                    return Err("Expected `)`".to_owned());
+                }
+                Ok(e)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                let s = self.chars[start..self.pos].iter().collect::<String>();
+                s.parse()
+                    .map(FunctionExpr::Number)
+                    .map_err(|_| format!("Invalid number `{}`", s))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                    self.pos += 1;
+                }
+                let name = self.chars[start..self.pos].iter().collect::<String>();
+                if self.eat('(') {
+                    let mut args = Vec::new();
+                    if !self.eat(')') {
+                        loop {
+                            args.push(self.add()?);
+                            if self.eat(')') {
+                                break;
+                            }
+                            if !self.eat(',') {
+                                return Err("Expected `,` or `)`".to_owned());
+                            }
+                        }
+                    }
+                    Ok(FunctionExpr::Call(name, args))
+                } else if let Some(i) = VARIABLES.iter().position(|v| *v == name) {
+                    Ok(FunctionExpr::Variable(i))
+                } else if name == "pi" {
+                    Ok(FunctionExpr::Number(std::f32::consts::PI))
+                } else {
+                    Err(format!("Unknown variable `{}`", name))
+                }
+            }
+            Some(c) => Err(format!("Unexpected `{}`", c)),
+            None => Err("Unexpected end of expression".to_owned()),
+        }
+    }
+}
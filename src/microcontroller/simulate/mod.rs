@@ -0,0 +1,330 @@
+mod expression;
+use expression::FunctionExpr;
+
+use super::{
+    ArithmeticComponent, Component, ControlComponent, LinkIndex, LinkNode, Node, NodeType,
+    StableId, UnpositionedMicrocontroller, components::ComponentData as _,
+};
+
+use std::fmt::Display;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum SignalValue {
+    Bool(bool),
+    Number(f32),
+}
+
+impl SignalValue {
+    fn default_of(node_type: NodeType) -> Self {
+        match node_type {
+            NodeType::Bool => Self::Bool(false),
+            _ => Self::Number(0.0),
+        }
+    }
+
+    fn number(self) -> f32 {
+        match self {
+            Self::Number(v) => v,
+            Self::Bool(v) => v as u8 as f32,
+        }
+    }
+}
+
+impl Display for SignalValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(v) => write!(f, "{}", v),
+            Self::Number(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SimulationError {
+    UnknownInput { label: String },
+    IncompatibleValue { label: String, node_type: NodeType },
+    InvalidFunction { function: String, message: String },
+    UnsupportedComponent { component: String },
+}
+
+impl Display for SimulationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownInput { label } => write!(f, "Input node `{}` does not exist", label),
+            Self::IncompatibleValue { label, node_type } => {
+                write!(f, "Input node `{}` expects a {} value", label, node_type)
+            }
+            Self::InvalidFunction { function, message } => {
+                write!(f, "Cannot evaluate function `{}`: {}", function, message)
+            }
+            Self::UnsupportedComponent { component } => {
+                write!(f, "Component `{}` cannot be simulated", component)
+            }
+        }
+    }
+}
+
+// コンポーネントごとの tick をまたぐ状態
+#[derive(Debug)]
+enum ComponentState {
+    None,
+    Function(FunctionExpr),
+    Delta { previous: f32 },
+    Pid { integral: f32, previous_error: f32 },
+}
+
+#[derive(Debug)]
+pub struct Simulator<'a> {
+    mc: &'a UnpositionedMicrocontroller,
+    index: LinkIndex,
+    // 依存先が先に来るように並べたコンポーネントの添字
+    order: Vec<usize>,
+    node_values: Vec<SignalValue>,
+    component_values: Vec<Vec<SignalValue>>,
+    states: Vec<ComponentState>,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(mc: &'a UnpositionedMicrocontroller) -> Result<Self, SimulationError> {
+        let index = LinkIndex::new(&mc.nodes, &mc.components);
+
+        let mut states = Vec::with_capacity(mc.components.len());
+        for component in &mc.components {
+            states.push(initial_state(component)?);
+        }
+
+        let mut s = Self {
+            mc,
+            index,
+            order: Vec::with_capacity(mc.components.len()),
+            node_values: mc
+                .nodes
+                .iter()
+                .map(|n| SignalValue::default_of(n.node_type()))
+                .collect(),
+            component_values: mc
+                .components
+                .iter()
+                .map(|c| {
+                    (0..)
+                        .map_while(|i| c.output_type(i))
+                        .map(SignalValue::default_of)
+                        .collect()
+                })
+                .collect(),
+            states,
+        };
+        s.sort_components();
+        Ok(s)
+    }
+
+    // 帰還ループは前の tick の値を使う
+    fn sort_components(&mut self) {
+        let mut visited = vec![false; self.mc.components.len()];
+        for i in 0..self.mc.components.len() {
+            self.visit(i, &mut visited);
+        }
+    }
+
+    fn visit(&mut self, i: usize, visited: &mut Vec<bool>) {
+        if visited[i] {
+            return;
+        }
+        visited[i] = true;
+        for link in self.mc.components[i]
+            .input_links_node()
+            .into_iter()
+            .flatten()
+        {
+            if let Some(StableId::Component(j)) = self.index.get(link) {
+                self.visit(j, visited);
+            }
+        }
+        self.order.push(i);
+    }
+
+    pub fn set_input(&mut self, label: &str, value: SignalValue) -> Result<(), SimulationError> {
+        let (i, node) = self
+            .mc
+            .nodes
+            .iter()
+            .enumerate()
+            .find(|(_, n)| n.mode().is_input() && n.label_owned() == label)
+            .ok_or_else(|| SimulationError::UnknownInput {
+                label: label.to_owned(),
+            })?;
+        let node_type = node.node_type();
+        let value = match (node_type, value) {
+            (NodeType::Bool, SignalValue::Bool(_)) | (NodeType::Number, SignalValue::Number(_)) => {
+                value
+            }
+            // 整数や小数で指定された on/off は 0 以外を true とする
+            (NodeType::Bool, SignalValue::Number(v)) => SignalValue::Bool(v != 0.0),
+            _ => {
+                return Err(SimulationError::IncompatibleValue {
+                    label: label.to_owned(),
+                    node_type,
+                });
+            }
+        };
+        self.node_values[i] = value;
+        Ok(())
+    }
+
+    pub fn tick(&mut self) {
+        for k in 0..self.order.len() {
+            let i = self.order[k];
+            let component = &self.mc.components[i];
+            let inputs = component
+                .input_links_node()
+                .into_iter()
+                .map(|l| self.link_value(l))
+                .collect::<Vec<_>>();
+            let outputs = evaluate(component, &inputs, &mut self.states[i]);
+            self.component_values[i] = outputs;
+        }
+    }
+
+    pub fn outputs(&self) -> Vec<(String, SignalValue)> {
+        self.mc
+            .nodes
+            .iter()
+            .filter_map(|n| match n {
+                Node::Output(o) => {
+                    let o = o.borrow();
+                    let value = o
+                        .input_link_node()
+                        .as_ref()
+                        .and_then(|l| self.link_node_value(l))
+                        .unwrap_or(SignalValue::default_of(o.node_type()));
+                    Some((o.label.clone(), value))
+                }
+                Node::Input(_) => None,
+            })
+            .collect()
+    }
+
+    fn link_value(&self, link: &Option<LinkNode>) -> f32 {
+        link.as_ref()
+            .and_then(|l| self.link_node_value(l))
+            .map_or(0.0, SignalValue::number)
+    }
+
+    fn link_node_value(&self, link: &LinkNode) -> Option<SignalValue> {
+        match (self.index.get(link)?, link) {
+            (StableId::Node(i), _) => Some(self.node_values[i]),
+            (StableId::Component(i), LinkNode::Component(_, index)) => {
+                self.component_values[i].get(*index).copied()
+            }
+            _ => None,
+        }
+    }
+}
+
+fn initial_state(component: &Component) -> Result<ComponentState, SimulationError> {
+    let state = match component {
+        Component::Arithmetic(
+            ArithmeticComponent::Function1 { function, .. }
+            | ArithmeticComponent::Function3 { function, .. }
+            | ArithmeticComponent::Function8 { function, .. },
+        ) => FunctionExpr::parse(function)
+            .map(ComponentState::Function)
+            .map_err(|message| SimulationError::InvalidFunction {
+                function: function.clone(),
+                message,
+            })?,
+        Component::Arithmetic(ArithmeticComponent::Delta { .. }) => {
+            ComponentState::Delta { previous: 0.0 }
+        }
+        Component::Control(_) => ComponentState::Pid {
+            integral: 0.0,
+            previous_error: 0.0,
+        },
+        Component::Composite(_) | Component::Lua(_) => {
+            return Err(SimulationError::UnsupportedComponent {
+                component: component.to_string(),
+            });
+        }
+        _ => ComponentState::None,
+    };
+    Ok(state)
+}
+
+fn evaluate(component: &Component, inputs: &[f32], state: &mut ComponentState) -> Vec<SignalValue> {
+    use SignalValue::{Bool, Number};
+
+    let input = |i: usize| inputs.get(i).copied().unwrap_or(0.0);
+    match component {
+        Component::Arithmetic(c) => match c {
+            ArithmeticComponent::Add { .. } => vec![Number(input(0) + input(1))],
+            ArithmeticComponent::Subtract { .. } => vec![Number(input(0) - input(1))],
+            ArithmeticComponent::Multiply { .. } => vec![Number(input(0) * input(1))],
+            ArithmeticComponent::Divide { .. } => {
+                let divisor = input(1);
+                if divisor == 0.0 {
+                    vec![Number(0.0), Bool(true)]
+                } else {
+                    vec![Number(input(0) / divisor), Bool(false)]
+                }
+            }
+            ArithmeticComponent::Modulo { .. } => vec![Number(input(0) % input(1))],
+            ArithmeticComponent::Clamp { min, max, .. } => {
+                vec![Number(input(0).max(*min).min(*max))]
+            }
+            ArithmeticComponent::Abs { .. } => vec![Number(input(0).abs())],
+            ArithmeticComponent::ConstantNumber { value } => vec![Number(*value)],
+            ArithmeticComponent::Equal { epsilon, .. } => {
+                vec![Bool((input(0) - input(1)).abs() <= *epsilon)]
+            }
+            ArithmeticComponent::GreaterThan { .. } => vec![Bool(input(0) > input(1))],
+            ArithmeticComponent::LessThan { .. } => vec![Bool(input(0) < input(1))],
+            ArithmeticComponent::NumericalSwitchbox { .. } => {
+                vec![Number(if input(2) != 0.0 { input(0) } else { input(1) })]
+            }
+            ArithmeticComponent::Delta { .. } => {
+                let ComponentState::Delta { previous } = state else {
+                    unreachable!()
+                };
+                let v = input(0);
+                let delta = v - *previous;
+                *previous = v;
+                vec![Number(delta)]
+            }
+            ArithmeticComponent::Function1 { .. }
+            | ArithmeticComponent::Function3 { .. }
+            | ArithmeticComponent::Function8 { .. } => {
+                let ComponentState::Function(f) = state else {
+                    unreachable!()
+                };
+                // 評価できない関数は 0 を出力する
+                vec![Number(f.evaluate(inputs).unwrap_or(0.0))]
+            }
+        },
+        Component::Control(c) => {
+            let (kp, ki, kd, active) = match c {
+                ControlComponent::Pid { .. } => (input(2), input(3), input(4), input(5)),
+                ControlComponent::PidAdvanced { kp, ki, kd, .. } => (*kp, *ki, *kd, input(2)),
+            };
+            let ComponentState::Pid {
+                integral,
+                previous_error,
+            } = state
+            else {
+                unreachable!()
+            };
+            // 無効な間は 0 を出力し、積分をリセットする
+            if active == 0.0 {
+                *integral = 0.0;
+                *previous_error = 0.0;
+                return vec![Number(0.0)];
+            }
+            let error = input(0) - input(1);
+            *integral += error;
+            let derivative = error - *previous_error;
+            *previous_error = error;
+            vec![Number(kp * error + ki * *integral + kd * derivative)]
+        }
+        // initial_state で弾いている
+        Component::Composite(_) | Component::Lua(_) => unreachable!(),
+    }
+}
@@ -0,0 +1,318 @@
+use super::{BuildError, Manifest};
+use crate::{
+    compile::{compile_str, script_files},
+    compile_error::Diagnostic,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+const CACHE_FILE_NAME: &str = ".miconlang-cache.json";
+
+#[derive(Default, Debug)]
+pub struct BuildSummary {
+    pub built: usize,
+    pub up_to_date: usize,
+    pub failed: usize,
+    // 内容が変わって書き換えたファイル
+    pub written: Vec<PathBuf>,
+    // ソースファイルごとのエラーと警告 (表示は呼び出し側で行う)
+    pub diagnostics: Vec<SourceDiagnostics>,
+    // 失敗したソースファイルのエラー
+    pub errors: Vec<BuildError>,
+}
+
+#[derive(Debug)]
+pub struct SourceDiagnostics {
+    pub path: PathBuf,
+    pub code: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// マニフェストの設定を上書きする
+#[derive(Default, Debug)]
+pub struct BuildOptions {
+    pub out_dir: Option<PathBuf>,
+    pub export_dir: Option<PathBuf>,
+}
+
+// ソースファイルごとのビルド結果
+#[derive(Deserialize, Serialize, Debug)]
+struct CacheEntry {
+    hash: String,
+    outputs: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+struct BuildCache {
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+impl BuildCache {
+    fn load(out_dir: &Path) -> Self {
+        // 壊れたキャッシュはフルビルドで作り直す
+        fs::read_to_string(out_dir.join(CACHE_FILE_NAME))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, out_dir: &Path) -> Result<(), BuildError> {
+        let path = out_dir.join(CACHE_FILE_NAME);
+        let content = serde_json::to_string_pretty(self).expect("Unexpected Error: Cache Error");
+        fs::write(&path, content).map_err(|err| BuildError::Write { path, err })
+    }
+}
+
+pub fn build(manifest_path: &Path, options: &BuildOptions) -> Result<BuildSummary, BuildError> {
+    let manifest = Manifest::load(manifest_path)?;
+    let root = manifest_path.parent().unwrap_or(Path::new(""));
+
+    let out_dir = options
+        .out_dir
+        .clone()
+        .unwrap_or_else(|| root.join(&manifest.build.out_dir));
+    create_dir(&out_dir)?;
+    let export_dir = options.export_dir.clone().or_else(|| {
+        manifest
+            .export
+            .as_ref()
+            .map(|e| root.join(&e.microprocessors_dir))
+    });
+    if let Some(dir) = &export_dir {
+        create_dir(dir)?;
+    }
+
+    let mut cache = BuildCache::load(&out_dir);
+    let mut new_cache = BuildCache::default();
+    let mut summary = BuildSummary::default();
+    let mut targets: HashMap<String, PathBuf> = HashMap::new();
+
+    for source in manifest.source_files(root)? {
+        let key = source
+            .strip_prefix(root)
+            .unwrap_or(&source)
+            .to_string_lossy()
+            .into_owned();
+        let old_entry = cache.entries.remove(&key);
+
+        match build_source(
+            &manifest,
+            &source,
+            old_entry.as_ref(),
+            &out_dir,
+            export_dir.as_deref(),
+            &mut targets,
+            &mut summary,
+        ) {
+            Ok((entry, rebuilt)) => {
+                if rebuilt {
+                    summary.built += 1;
+                } else {
+                    summary.up_to_date += 1;
+                }
+                new_cache.entries.insert(key, entry);
+            }
+            Err(err) => {
+                summary.errors.push(err);
+                summary.failed += 1;
+                // 前回の出力は残っているので次回のビルドまで追跡する
+                if let Some(entry) = old_entry {
+                    new_cache.entries.insert(key, entry);
+                }
+            }
+        }
+    }
+
+    // sources から外れたファイルの出力を削除
+    for entry in cache.entries.into_values() {
+        for output in entry.outputs {
+            remove_output(&out_dir, export_dir.as_deref(), &output)?;
+        }
+    }
+
+    new_cache.save(&out_dir)?;
+    Ok(summary)
+}
+
+fn build_source(
+    manifest: &Manifest,
+    source: &Path,
+    old_entry: Option<&CacheEntry>,
+    out_dir: &Path,
+    export_dir: Option<&Path>,
+    targets: &mut HashMap<String, PathBuf>,
+    summary: &mut BuildSummary,
+) -> Result<(CacheEntry, bool), BuildError> {
+    let code = fs::read_to_string(source).map_err(|err| BuildError::Read {
+        path: source.to_owned(),
+        err,
+    })?;
+    let filename = source.to_string_lossy();
+    let hash = fingerprint(manifest, &code, &script_files(&code, &filename));
+
+    if let Some(entry) = old_entry
+        && entry.hash == hash
+        && entry
+            .outputs
+            .iter()
+            .all(|o| out_dir.join(format!("{}.xml", o)).is_file())
+    {
+        for output in &entry.outputs {
+            register_target(targets, output, source)?;
+            // ゲーム側のフォルダが書き換えられていれば戻す
+            if let Some(dir) = export_dir {
+                let from = out_dir.join(format!("{}.xml", output));
+                let content = fs::read_to_string(&from)
+                    .map_err(|err| BuildError::Read { path: from, err })?;
+                let path = dir.join(format!("{}.xml", output));
+                if write_if_changed(&path, &content)? {
+                    summary.written.push(path);
+                }
+            }
+        }
+        return Ok((
+            CacheEntry {
+                hash,
+                outputs: entry.outputs.clone(),
+            },
+            false,
+        ));
+    }
+
+    let (xml_files, diagnostics) = match compile_str(&code, &filename, manifest.build.opt_level) {
+        Ok(xml_files) => (Some(xml_files.value), xml_files.warnings),
+        Err(diagnostics) => (None, diagnostics),
+    };
+    if !diagnostics.is_empty() {
+        summary.diagnostics.push(SourceDiagnostics {
+            path: source.to_owned(),
+            code,
+            diagnostics,
+        });
+    }
+    let xml_files = xml_files.ok_or_else(|| BuildError::Compile {
+        path: source.to_owned(),
+    })?;
+
+    let mut outputs = Vec::with_capacity(xml_files.len());
+    for (name, content) in xml_files {
+        let target = manifest.target_name(&name, source);
+        register_target(targets, &target, source)?;
+
+        let dirs = std::iter::once(out_dir).chain(export_dir);
+        for path in dirs.map(|dir| dir.join(format!("{}.xml", target))) {
+            if write_if_changed(&path, &content)? {
+                summary.written.push(path);
+            }
+        }
+        outputs.push(target);
+    }
+
+    // 前回のビルドにしか存在しない出力を削除
+    if let Some(entry) = old_entry {
+        for output in &entry.outputs {
+            if !outputs.contains(output) {
+                remove_output(out_dir, export_dir, output)?;
+            }
+        }
+    }
+
+    Ok((CacheEntry { hash, outputs }, true))
+}
+
+fn register_target(
+    targets: &mut HashMap<String, PathBuf>,
+    target: &str,
+    source: &Path,
+) -> Result<(), BuildError> {
+    if let Some(first) = targets.get(target) {
+        return Err(BuildError::DuplicateTarget {
+            target: target.to_owned(),
+            first: first.clone(),
+            second: source.to_owned(),
+        });
+    }
+    targets.insert(target.to_owned(), source.to_owned());
+    Ok(())
+}
+
+fn create_dir(path: &Path) -> Result<(), BuildError> {
+    fs::create_dir_all(path).map_err(|err| BuildError::Write {
+        path: path.to_owned(),
+        err,
+    })
+}
+
+// 内容が同じなら書き込まない (更新日時を変えない)
+pub fn write_if_changed(path: &Path, content: &str) -> Result<bool, BuildError> {
+    if fs::read(path).is_ok_and(|old| old == content.as_bytes()) {
+        return Ok(false);
+    }
+    fs::write(path, content).map_err(|err| BuildError::Write {
+        path: path.to_owned(),
+        err,
+    })?;
+    Ok(true)
+}
+
+// 出力先とゲーム側のフォルダの両方から削除する
+fn remove_output(
+    out_dir: &Path,
+    export_dir: Option<&Path>,
+    output: &str,
+) -> Result<(), BuildError> {
+    for dir in std::iter::once(out_dir).chain(export_dir) {
+        let path = dir.join(format!("{}.xml", output));
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(BuildError::Write { path, err });
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// コンパイラのバージョン、ビルド設定、ソースと読み込むスクリプトの内容から出力が変わるかを判定
+fn fingerprint(manifest: &Manifest, code: &str, scripts: &[PathBuf]) -> String {
+    let mut hash = Fnv1a::default();
+    hash.write(env!("CARGO_PKG_VERSION").as_bytes());
+    hash.write(&[manifest.build.opt_level as u8]);
+    hash.write(manifest.build.target_name.as_bytes());
+    hash.write(code.as_bytes());
+    for script in scripts {
+        hash.write(script.to_string_lossy().as_bytes());
+        // 読めないファイルは内容が空のファイルと区別する
+        match fs::read(script) {
+            Ok(content) => hash.write(&content),
+            Err(_) => hash.write(&[0xff]),
+        }
+    }
+    format!("{:016x}", hash.0)
+}
+
+// 実行環境によらず同じ値になるハッシュ関数
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+        // 区切りを入れて連結の曖昧さをなくす
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x100000001b3);
+    }
+}
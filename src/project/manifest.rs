@@ -0,0 +1,110 @@
+use super::BuildError;
+use crate::microcontroller::OptLevel;
+
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST_FILE_NAME: &str = "miconlang.toml";
+
+// ディレクトリを sources に指定した場合に読み込む拡張子
+pub const SOURCE_EXTENSION: &str = "mcl";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub build: BuildConfig,
+    pub export: Option<ExportConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BuildConfig {
+    pub sources: Vec<PathBuf>,
+    #[serde(default = "default_out_dir")]
+    pub out_dir: PathBuf,
+    #[serde(default)]
+    pub opt_level: OptLevel,
+    // {name} はマイコンの識別子、{file} はソースファイル名 (拡張子なし) に置換
+    #[serde(default = "default_target_name")]
+    pub target_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ExportConfig {
+    // Stormworks の data/microprocessors フォルダ
+    pub microprocessors_dir: PathBuf,
+}
+
+fn default_out_dir() -> PathBuf {
+    PathBuf::from("out")
+}
+
+fn default_target_name() -> String {
+    "{name}".to_owned()
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, BuildError> {
+        let content = fs::read_to_string(path).map_err(|err| BuildError::Read {
+            path: path.to_owned(),
+            err,
+        })?;
+        toml::from_str(&content).map_err(|err| BuildError::ManifestParse {
+            path: path.to_owned(),
+            content,
+            err: Box::new(err),
+        })
+    }
+
+    pub fn target_name(&self, name: &str, source: &Path) -> String {
+        let file = source
+            .file_stem()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        self.build
+            .target_name
+            .replace("{name}", name)
+            .replace("{file}", &file)
+    }
+
+    // sources に含まれるファイルを列挙 (ディレクトリは再帰的に探索)
+    pub fn source_files(&self, root: &Path) -> Result<Vec<PathBuf>, BuildError> {
+        let mut files = Vec::new();
+        for source in &self.build.sources {
+            let path = root.join(source);
+            if path.is_dir() {
+                collect_sources(&path, &mut files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+}
+
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), BuildError> {
+    let read_dir = |err| BuildError::Read {
+        path: dir.to_owned(),
+        err,
+    };
+
+    let mut entries = fs::read_dir(dir)
+        .map_err(read_dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_dir)?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_sources(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == SOURCE_EXTENSION) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+mod build;
+mod manifest;
+mod watch;
+pub use build::{BuildOptions, BuildSummary, SourceDiagnostics, build, write_if_changed};
+pub use manifest::{MANIFEST_FILE_NAME, Manifest};
+pub use watch::{manifest_paths, source_paths, watch};
+
+use crate::{
+    compile_error::char_span, decompile::DecompileError, microcontroller::SimulationError,
+};
+
+use ariadne::{Color, Label, Report, ReportKind, Source};
+use std::{fmt::Display, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum BuildError {
+    Read {
+        path: PathBuf,
+        err: io::Error,
+    },
+    Write {
+        path: PathBuf,
+        err: io::Error,
+    },
+    ManifestParse {
+        path: PathBuf,
+        content: String,
+        err: Box<toml::de::Error>,
+    },
+    DuplicateTarget {
+        target: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    Compile {
+        path: PathBuf,
+    },
+    InvalidXml {
+        path: PathBuf,
+        err: quick_xml::DeError,
+    },
+    Decompile {
+        path: PathBuf,
+        err: DecompileError,
+    },
+    Simulation {
+        path: PathBuf,
+        err: SimulationError,
+    },
+    InvalidInput {
+        input: String,
+    },
+    XmlGeneration {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read { path, err } => write!(f, "Cannot read `{}`: {}", path.display(), err),
+            Self::Write { path, err } => write!(f, "Cannot write `{}`: {}", path.display(), err),
+            Self::ManifestParse { path, err, .. } => {
+                write!(
+                    f,
+                    "Invalid manifest `{}`: {}",
+                    path.display(),
+                    err.message()
+                )
+            }
+            Self::DuplicateTarget {
+                target,
+                first,
+                second,
+            } => write!(
+                f,
+                "Target `{}` is generated by both `{}` and `{}`",
+                target,
+                first.display(),
+                second.display()
+            ),
+            Self::Compile { path } => write!(
+                f,
+                "Could not compile `{}` due to previous errors",
+                path.display()
+            ),
+            Self::InvalidXml { path, err } => {
+                write!(
+                    f,
+                    "Invalid microcontroller XML `{}`: {}",
+                    path.display(),
+                    err
+                )
+            }
+            Self::Decompile { path, err } => {
+                write!(f, "Cannot decompile `{}`: {}", path.display(), err)
+            }
+            Self::Simulation { path, err } => {
+                write!(f, "Cannot simulate `{}`: {}", path.display(), err)
+            }
+            Self::InvalidInput { input } => write!(
+                f,
+                "Invalid input `{}`: expected LABEL=VALUE with a number, true or false",
+                input
+            ),
+            Self::XmlGeneration { path, message } => {
+                write!(
+                    f,
+                    "Cannot generate the XML of `{}`: {}",
+                    path.display(),
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl BuildError {
+    pub fn print(&self) {
+        match self {
+            Self::ManifestParse { path, content, err } => {
+                let filename = path.to_string_lossy();
+                let filename = filename.as_ref();
+                let span = char_span(content, &err.span().unwrap_or(0..0));
+                Report::build(ReportKind::Error, (filename, span.clone()))
+                    .with_message("Invalid Manifest")
+                    .with_label(
+                        Label::new((filename, span))
+                            .with_message(err.message())
+                            .with_color(Color::Red),
+                    )
+                    .finish()
+                    .eprint((filename, Source::from(content.as_str())))
+                    .unwrap();
+            }
+            _ => {
+                // ソース上の位置を持たないエラー
+                Report::build(ReportKind::Error, ("", 0..0))
+                    .with_message(self)
+                    .finish()
+                    .eprint(("", Source::from("")))
+                    .unwrap();
+            }
+        }
+    }
+}
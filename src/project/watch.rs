@@ -0,0 +1,62 @@
+use crate::compile::script_files;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// 更新日時とサイズで変更を検出する
+type Snapshot = BTreeMap<PathBuf, Option<(SystemTime, u64)>>;
+
+fn snapshot(paths: &[PathBuf]) -> Snapshot {
+    paths
+        .iter()
+        .map(|path| {
+            let stamp = fs::metadata(path)
+                .ok()
+                .and_then(|m| Some((m.modified().ok()?, m.len())));
+            (path.clone(), stamp)
+        })
+        .collect()
+}
+
+// paths が返すファイルのどれかが変わるたびに rebuild を呼ぶ
+// 監視対象はソースの追加・削除に追従するため毎回取り直す
+pub fn watch(paths: impl Fn() -> Vec<PathBuf>, mut rebuild: impl FnMut()) -> ! {
+    let mut last = snapshot(&paths());
+    rebuild();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = snapshot(&paths());
+        if current != last {
+            // ビルド中の変更を取りこぼさないよう先に記録する
+            last = current;
+            rebuild();
+        }
+    }
+}
+
+// マニフェストと、その時点でのソースファイルとそれが読み込むスクリプト
+pub fn manifest_paths(manifest_path: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![manifest_path.to_owned()];
+    if let Ok(manifest) = super::Manifest::load(manifest_path) {
+        let root = manifest_path.parent().unwrap_or(Path::new(""));
+        for source in manifest.source_files(root).unwrap_or_default() {
+            paths.extend(source_paths(&source));
+        }
+    }
+    paths
+}
+
+// ソースファイルと、lua(file = ...) で読み込むスクリプト
+pub fn source_paths(source: &Path) -> Vec<PathBuf> {
+    let scripts = fs::read_to_string(source)
+        .map(|code| script_files(&code, &source.to_string_lossy()))
+        .unwrap_or_default();
+    std::iter::once(source.to_owned()).chain(scripts).collect()
+}
@@ -0,0 +1,111 @@
+use crate::microcontroller::{
+    Component, ComponentData as _, LinkIndex, LinkNode, Node, NodeType, StableId,
+    UnpositionedMicrocontroller,
+};
+
+use std::fmt::Write as _;
+
+fn quote(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+fn node_id(id: StableId) -> String {
+    match id {
+        StableId::Node(i) => format!("n{}", i),
+        StableId::Component(i) => format!("c{}", i),
+    }
+}
+
+// 名前と value_list, attrs を 1 行ずつ並べる
+fn component_label(component: &Component) -> String {
+    let mut lines = vec![component.to_string()];
+    for (tag, value) in component.value_list().unwrap_or_default() {
+        let value = value.value.or(value.text).unwrap_or_default();
+        lines.push(format!("{} = {}", tag.as_str(), value));
+    }
+    for (name, value) in component.attrs().unwrap_or_default() {
+        lines.push(format!("{} = {}", name.trim_start_matches('@'), value));
+    }
+    lines.join("\n")
+}
+
+// 配置前のデータフローを Graphviz の DOT 形式で出力する
+pub fn to_dot(mc: &UnpositionedMicrocontroller) -> String {
+    let index = LinkIndex::new(&mc.nodes, &mc.components);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "digraph {} {{", quote(&mc.name));
+    let _ = writeln!(out, "    rankdir=LR;");
+    let _ = writeln!(out, "    node [shape=box, fontname=\"sans-serif\"];");
+    let _ = writeln!(out, "    edge [fontname=\"sans-serif\", fontsize=10];");
+
+    // 入力ノードを左端、出力ノードを右端にそろえる
+    for (rank, shape, is_input) in [("source", "invhouse", true), ("sink", "house", false)] {
+        let _ = writeln!(out, "    {{");
+        let _ = writeln!(out, "        rank={};", rank);
+        for (i, node) in mc.nodes.iter().enumerate() {
+            if node.mode().is_input() != is_input {
+                continue;
+            }
+            let label = format!("{}\n{}", node.label_owned(), node.node_type());
+            let _ = writeln!(
+                out,
+                "        n{} [label={}, shape={}];",
+                i,
+                quote(&label),
+                shape
+            );
+        }
+        let _ = writeln!(out, "    }}");
+    }
+
+    for (i, component) in mc.components.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "    c{} [label={}];",
+            i,
+            quote(&component_label(component))
+        );
+    }
+
+    // リンク元、接続先、接続先の入力の番号
+    let mut edge = |link: &LinkNode, to: String, port: usize| {
+        let (from, node_type, from_port) = match (index.get(link), link) {
+            (Some(StableId::Node(i)), _) => (StableId::Node(i), mc.nodes[i].node_type(), 0),
+            (Some(StableId::Component(i)), LinkNode::Component(_, p)) => (
+                StableId::Component(i),
+                mc.components[i].output_type(*p).unwrap_or(NodeType::Number),
+                *p,
+            ),
+            _ => return,
+        };
+        let mut attrs = format!("label={}", quote(&format!("{}: {}", port, node_type)));
+        // 2 番目以降の出力からの接続は出力の番号も表示する
+        if from_port != 0 {
+            attrs += &format!(", taillabel={}", quote(&from_port.to_string()));
+        }
+        let _ = writeln!(out, "    {} -> {} [{}];", node_id(from), to, attrs);
+    };
+    for (i, node) in mc.nodes.iter().enumerate() {
+        if let Node::Output(n) = node
+            && let Some(link) = n.borrow().input_link_node()
+        {
+            edge(link, format!("n{}", i), 0);
+        }
+    }
+    for (i, component) in mc.components.iter().enumerate() {
+        for (port, link) in component.input_links_node().into_iter().enumerate() {
+            if let Some(link) = link {
+                edge(link, format!("c{}", i), port);
+            }
+        }
+    }
+
+    out += "}\n";
+    out
+}
@@ -0,0 +1,267 @@
+mod dot;
+mod svg;
+pub use dot::to_dot;
+
+use crate::{
+    microcontroller::{
+        Component, ComponentData as _, LinkIndex, LinkNode, NODE_HEIGHT, Node, NodeMode,
+        NodePosition, NodeType, PositionedMicrocontroller, StableId,
+    },
+    xml_schema::{ComponentObject, Microprocessor},
+};
+
+use std::collections::HashMap;
+
+// 描画用に配置済みのマイコンを平たくしたもの
+// PositionedMicrocontroller と XML のどちらからでも作れる
+#[derive(Debug)]
+pub struct Scene {
+    pub name: String,
+    pub width: u8,
+    pub length: u8,
+    pub vertices: Vec<Vertex>,
+    pub wires: Vec<Wire>,
+}
+
+// ロジック上の箱 (入出力ノードかコンポーネント)
+#[derive(Debug)]
+pub struct Vertex {
+    pub label: String,
+    // 左下の位置と高さ (ComponentPosition の単位)
+    pub x: i32,
+    pub y: i32,
+    pub height: i32,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub node: Option<SceneNode>,
+}
+
+#[derive(Debug)]
+pub struct SceneNode {
+    pub mode: NodeMode,
+    pub node_type: NodeType,
+    pub position: NodePosition,
+}
+
+// vertices の添字と入出力の番号で表す配線
+#[derive(Debug)]
+pub struct Wire {
+    pub from: usize,
+    pub from_port: usize,
+    pub to: usize,
+    pub to_port: usize,
+    pub node_type: NodeType,
+}
+
+impl Scene {
+    pub fn to_svg(&self) -> String {
+        svg::render(self)
+    }
+}
+
+fn count_outputs(component: &Component) -> usize {
+    (0..)
+        .take_while(|i| component.output_type(*i).is_some())
+        .count()
+}
+
+impl From<&PositionedMicrocontroller> for Scene {
+    fn from(mc: &PositionedMicrocontroller) -> Self {
+        let index = LinkIndex::new(
+            mc.nodes.iter().map(|n| &n.inner),
+            mc.components.iter().map(|c| &c.inner),
+        );
+        let n_nodes = mc.nodes.len();
+
+        // リンク元の頂点、出力の番号、信号の種類
+        let source = |link: &LinkNode| match (index.get(link)?, link) {
+            (StableId::Node(i), _) => Some((i, 0, mc.nodes[i].node_type())),
+            (StableId::Component(i), LinkNode::Component(_, port)) => {
+                let node_type = mc.components[i].output_type(*port)?;
+                Some((n_nodes + i, *port, node_type))
+            }
+            _ => None,
+        };
+
+        let mut vertices = Vec::with_capacity(n_nodes + mc.components.len());
+        let mut wires = Vec::new();
+        for (i, node) in mc.nodes.iter().enumerate() {
+            let pos = &node.component_position;
+            let mode = node.mode();
+            vertices.push(Vertex {
+                label: node.label_owned(),
+                x: pos.x,
+                y: pos.y,
+                height: NODE_HEIGHT,
+                inputs: mode.is_output() as usize,
+                outputs: mode.is_input() as usize,
+                node: Some(SceneNode {
+                    mode,
+                    node_type: node.node_type(),
+                    position: node.position(),
+                }),
+            });
+            if let Node::Output(n) = &node.inner
+                && let Some((from, from_port, node_type)) =
+                    n.borrow().input_link_node().as_ref().and_then(source)
+            {
+                wires.push(Wire {
+                    from,
+                    from_port,
+                    to: i,
+                    to_port: 0,
+                    node_type,
+                });
+            }
+        }
+        for (i, component) in mc.components.iter().enumerate() {
+            let links = component.input_links_node();
+            for (port, link) in links.iter().enumerate() {
+                if let Some((from, from_port, node_type)) = link.as_ref().and_then(source) {
+                    wires.push(Wire {
+                        from,
+                        from_port,
+                        to: n_nodes + i,
+                        to_port: port,
+                        node_type,
+                    });
+                }
+            }
+            vertices.push(Vertex {
+                label: component.inner.to_string(),
+                x: component.position.x,
+                y: component.position.y,
+                height: component.height() as i32,
+                inputs: links.len(),
+                outputs: count_outputs(&component.inner),
+                node: None,
+            });
+        }
+
+        Self {
+            name: mc.name.clone(),
+            width: mc.width,
+            length: mc.length,
+            vertices,
+            wires,
+        }
+    }
+}
+
+// XML の座標はエディタのマス単位なので ComponentPosition の単位に戻す
+fn object_position(object: &ComponentObject) -> (i32, i32) {
+    let pos = object.pos.as_ref();
+    let to_units = |v: Option<f32>| (v.unwrap_or(0.0) * 4.0).round() as i32;
+    (
+        to_units(pos.and_then(|p| p.x)),
+        to_units(pos.and_then(|p| p.y)),
+    )
+}
+
+impl From<&Microprocessor> for Scene {
+    fn from(mp: &Microprocessor) -> Self {
+        let nodes = mp
+            .nodes
+            .n
+            .iter()
+            .map(|n| (n.component_id, &n.node))
+            .collect::<HashMap<_, _>>();
+        let bridges = &mp.group.components_bridge.c;
+        let components = &mp.group.components.c;
+
+        let mut vertices = Vec::with_capacity(bridges.len() + components.len());
+        // ID -> (頂点, 出力の信号の種類)
+        let mut ids = HashMap::new();
+        for item in bridges {
+            let object = &item.object;
+            let (x, y) = object_position(object);
+            let node = nodes.get(&object.id);
+            let mode = node
+                .and_then(|n| NodeMode::try_from(n.mode.unwrap_or(0)).ok())
+                .unwrap_or(NodeMode::Output);
+            let node_type = node
+                .and_then(|n| NodeType::try_from(n.node_type.unwrap_or(0)).ok())
+                .unwrap_or(NodeType::Number);
+            let position = node.and_then(|n| n.position.as_ref());
+            ids.insert(object.id, (vertices.len(), vec![node_type]));
+            vertices.push(Vertex {
+                label: node.and_then(|n| n.label.clone()).unwrap_or_default(),
+                x,
+                y,
+                height: NODE_HEIGHT,
+                inputs: mode.is_output() as usize,
+                outputs: mode.is_input() as usize,
+                node: Some(SceneNode {
+                    mode,
+                    node_type,
+                    position: NodePosition::new(
+                        position.and_then(|p| p.x).unwrap_or(0),
+                        position.and_then(|p| p.z).unwrap_or(0),
+                    ),
+                }),
+            });
+        }
+        for item in components {
+            let object = &item.object;
+            let (x, y) = object_position(object);
+            let component_type = item.component_type.unwrap_or(0);
+            let inputs = object.in_map.keys().copied().max().unwrap_or(0);
+            let vertex = match Component::placeholder(component_type) {
+                Some(c) => {
+                    let outputs = count_outputs(&c);
+                    let types = (0..outputs).filter_map(|i| c.output_type(i)).collect();
+                    ids.insert(object.id, (vertices.len(), types));
+                    Vertex {
+                        label: c.to_string(),
+                        x,
+                        y,
+                        height: c.height() as i32,
+                        inputs: c.input_links_node().len().max(inputs),
+                        outputs,
+                        node: None,
+                    }
+                }
+                // 未対応のコンポーネントは種類の番号だけ表示する
+                None => {
+                    ids.insert(object.id, (vertices.len(), vec![NodeType::Number]));
+                    Vertex {
+                        label: format!("type {}", component_type),
+                        x,
+                        y,
+                        height: inputs.max(1) as i32 + 1,
+                        inputs,
+                        outputs: 1,
+                        node: None,
+                    }
+                }
+            };
+            vertices.push(vertex);
+        }
+
+        let mut wires = Vec::new();
+        for object in bridges.iter().chain(components).map(|item| &item.object) {
+            let to = ids[&object.id].0;
+            for (port, input) in &object.in_map {
+                let Some((from, types)) = input.component_id.and_then(|id| ids.get(&id)) else {
+                    continue;
+                };
+                let from_port = input.node_index.unwrap_or(0);
+                wires.push(Wire {
+                    from: *from,
+                    from_port,
+                    to,
+                    to_port: port.saturating_sub(1),
+                    node_type: types.get(from_port).copied().unwrap_or(NodeType::Number),
+                });
+            }
+        }
+
+        Self {
+            name: mp.name.clone().unwrap_or_default(),
+            width: mp.width,
+            length: mp.length,
+            vertices,
+            wires,
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use super::{Scene, Vertex};
+use crate::microcontroller::{NodeMode, NodeType, VERTEX_WIDTH};
+
+use std::fmt::Write as _;
+
+// ComponentPosition の 1 単位の大きさ (px)
+const SCALE: f32 = 16.0;
+// 入出力ノードの配置図の 1 マスの大きさ (px)
+const CELL: f32 = 56.0;
+const MARGIN: f32 = 24.0;
+const TITLE_HEIGHT: f32 = 24.0;
+const PORT_RADIUS: f32 = 3.0;
+
+fn color(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::Bool => "#d9534f",
+        NodeType::Number => "#3a7bd5",
+        NodeType::Composite => "#8e44ad",
+        NodeType::Video => "#d4a017",
+        NodeType::Audio => "#e67e22",
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// ロジックの座標から SVG の座標への変換
+struct Canvas {
+    left: f32,
+    top: f32,
+    min_x: i32,
+    max_y: i32,
+}
+
+impl Canvas {
+    fn x(&self, x: f32) -> f32 {
+        self.left + (x - self.min_x as f32) * SCALE
+    }
+
+    fn y(&self, y: f32) -> f32 {
+        self.top + (self.max_y as f32 - y) * SCALE
+    }
+
+    // n 個の入力 (出力) のうち i 番目の位置
+    fn port(&self, v: &Vertex, i: usize, n: usize, output: bool) -> (f32, f32) {
+        let x = if output { v.x + VERTEX_WIDTH } else { v.x };
+        let step = v.height as f32 / (n + 1) as f32;
+        let y = (v.y + v.height) as f32 - step * (i + 1) as f32;
+        (self.x(x as f32), self.y(y))
+    }
+}
+
+pub(super) fn render(scene: &Scene) -> String {
+    let min_x = scene.vertices.iter().map(|v| v.x).min().unwrap_or(0);
+    let max_x = scene
+        .vertices
+        .iter()
+        .map(|v| v.x + VERTEX_WIDTH)
+        .max()
+        .unwrap_or(0);
+    let min_y = scene.vertices.iter().map(|v| v.y).min().unwrap_or(0);
+    let max_y = scene
+        .vertices
+        .iter()
+        .map(|v| v.y + v.height)
+        .max()
+        .unwrap_or(0);
+
+    // 左に入出力ノードの配置図、右にロジックを描く
+    let grid_width = scene.width as f32 * CELL;
+    let grid_height = scene.length as f32 * CELL;
+    let canvas = Canvas {
+        left: MARGIN * 2.0 + grid_width,
+        top: MARGIN + TITLE_HEIGHT,
+        min_x,
+        max_y,
+    };
+    let logic_width = (max_x - min_x) as f32 * SCALE;
+    let logic_height = (max_y - min_y) as f32 * SCALE;
+    let width = canvas.left + logic_width + MARGIN;
+    let height = canvas.top + grid_height.max(logic_height) + MARGIN;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="9">"#,
+        w = width,
+        h = height
+    );
+    let _ = writeln!(
+        out,
+        r##"<rect width="{}" height="{}" fill="#ffffff"/>"##,
+        width, height
+    );
+    let _ = writeln!(
+        out,
+        r#"<text x="{}" y="{}" font-size="14" font-weight="bold">{}</text>"#,
+        MARGIN,
+        MARGIN + 4.0,
+        escape(&scene.name)
+    );
+
+    node_grid(&mut out, scene, MARGIN, canvas.top);
+
+    // 配線は箱の下に描く
+    for wire in &scene.wires {
+        let (from, to) = (&scene.vertices[wire.from], &scene.vertices[wire.to]);
+        let (x1, y1) = canvas.port(from, wire.from_port, from.outputs, true);
+        let (x2, y2) = canvas.port(to, wire.to_port, to.inputs, false);
+        let dx = ((x2 - x1).abs() / 2.0).max(SCALE * 2.0);
+        let _ = writeln!(
+            out,
+            r#"<path d="M {} {} C {} {}, {} {}, {} {}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+            x1,
+            y1,
+            x1 + dx,
+            y1,
+            x2 - dx,
+            y2,
+            x2,
+            y2,
+            color(wire.node_type)
+        );
+    }
+
+    for v in &scene.vertices {
+        vertex(&mut out, &canvas, v);
+    }
+
+    out += "</svg>\n";
+    out
+}
+
+fn vertex(out: &mut String, canvas: &Canvas, v: &Vertex) {
+    let (fill, stroke) = match &v.node {
+        Some(n) if n.mode.is_input() => ("#e8f5e9", "#43a047"),
+        Some(_) => ("#fdecea", "#e53935"),
+        None => ("#f2f2f2", "#555555"),
+    };
+    let x = canvas.x(v.x as f32);
+    let y = canvas.y((v.y + v.height) as f32);
+    let w = VERTEX_WIDTH as f32 * SCALE;
+    let h = v.height as f32 * SCALE;
+    let _ = writeln!(
+        out,
+        r#"<g><title>{title}</title><rect x="{x}" y="{y}" width="{w}" height="{h}" rx="3" fill="{fill}" stroke="{stroke}"/><text x="{tx}" y="{ty}" text-anchor="middle">{label}</text></g>"#,
+        title = escape(&format!("{} at ({}, {})", v.label, v.x, v.y)),
+        tx = x + w / 2.0,
+        ty = y + 11.0,
+        label = escape(&v.label),
+    );
+
+    for (n, output) in [(v.inputs, false), (v.outputs, true)] {
+        for i in 0..n {
+            let (px, py) = canvas.port(v, i, n, output);
+            let _ = writeln!(
+                out,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                px, py, PORT_RADIUS, stroke
+            );
+        }
+    }
+}
+
+// マイコン本体の入出力ノードの配置 (NodePosition)
+fn node_grid(out: &mut String, scene: &Scene, left: f32, top: f32) {
+    for x in 0..scene.width {
+        for z in 0..scene.length {
+            let _ = writeln!(
+                out,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#fafafa" stroke="#bbbbbb"/>"##,
+                left + x as f32 * CELL,
+                top + (scene.length - 1 - z) as f32 * CELL,
+                CELL,
+                CELL
+            );
+        }
+    }
+    for node in scene
+        .vertices
+        .iter()
+        .filter_map(|v| Some((v, v.node.as_ref()?)))
+    {
+        let (v, n) = node;
+        let cx = left + (n.position.x as f32 + 0.5) * CELL;
+        let cy = top + (scene.length.saturating_sub(n.position.z + 1) as f32 + 0.5) * CELL;
+        let arrow = match n.mode {
+            NodeMode::Input => "▶",
+            NodeMode::Output => "◀",
+        };
+        let _ = writeln!(
+            out,
+            r#"<circle cx="{cx}" cy="{cy}" r="6" fill="{}"/><text x="{cx}" y="{}" text-anchor="middle">{} {}</text>"#,
+            color(n.node_type),
+            cy + 18.0,
+            arrow,
+            escape(&v.label),
+        );
+    }
+}
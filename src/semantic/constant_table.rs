@@ -0,0 +1,53 @@
+use super::evaluate_expr::{ConstantValue, Constants, evaluate_expr};
+use crate::{
+    compile_error::{CompileError, CompileErrorType},
+    syntax::Constant,
+};
+
+use std::collections::{HashMap, HashSet};
+
+// const で定義した定数 (名前 -> 値)
+// マイクロコントローラー内の定義は同じ名前のファイルの定義を隠す
+#[derive(Clone, Default, Debug)]
+pub(super) struct ConstantTable {
+    constants: HashMap<String, ConstantValue>,
+}
+
+impl ConstantTable {
+    // 同じ階層の定義を順に求めて加えた表 (値には前の定義だけを使える)
+    pub(super) fn with<'t, 'f>(
+        &self,
+        constants: impl IntoIterator<Item = &'t Constant>,
+        filename: &'f str,
+        errors: &mut Vec<CompileError<'f>>,
+    ) -> Self {
+        let mut table = self.clone();
+        let mut defined = HashSet::new();
+        for constant in constants {
+            let name = &constant.name;
+            if !defined.insert(name.inner.as_str()) {
+                errors.push(CompileError::new(
+                    filename,
+                    name.span.clone(),
+                    CompileErrorType::ConstantAlreadyDefined {
+                        name: name.inner.clone(),
+                    },
+                ));
+                continue;
+            }
+            match evaluate_expr(&constant.value, &table, filename) {
+                Ok(value) => {
+                    table.constants.insert(name.inner.clone(), value.into());
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        table
+    }
+}
+
+impl Constants for ConstantTable {
+    fn get_constant(&self, name: &str) -> Option<&ConstantValue> {
+        self.constants.get(name)
+    }
+}
@@ -0,0 +1,252 @@
+use super::{
+    ConstantTable,
+    evaluate_expr::{ConstantValue, Constants, evaluate_expr},
+    interface::NodeArrays,
+};
+use crate::{
+    compile_error::{CompileError, CompileErrorType},
+    microcontroller::{InputNode, OutputNode},
+    syntax::{AssignmentTarget, Expr, Spanned},
+};
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+pub(super) type Inputs = HashMap<String, Rc<InputNode>>;
+pub(super) type Outputs = HashMap<String, Rc<RefCell<OutputNode>>>;
+
+#[derive(Debug)]
+struct ContextRoot {
+    inputs: Inputs,
+    outputs: Outputs,
+    arrays: NodeArrays,
+    constants: ConstantTable,
+}
+
+#[derive(Debug)]
+pub(super) struct ContextScope<V> {
+    variables: HashMap<String, V>,
+    // 関数のプロパティなど、スコープの中だけの定数
+    constants: HashMap<String, ConstantValue>,
+    // 関数の本体は外側の変数を見ない
+    isolated: bool,
+}
+
+impl<V> Default for ContextScope<V> {
+    fn default() -> Self {
+        Self {
+            variables: HashMap::new(),
+            constants: HashMap::new(),
+            isolated: false,
+        }
+    }
+}
+
+// 変数のスコープとインターフェースのノード
+// V は変数の値 (バックエンドごとに異なる)
+#[derive(Debug)]
+pub(super) struct Context<V> {
+    root: Option<ContextRoot>,
+    stack: Vec<ContextScope<V>>,
+}
+
+impl<V: Clone> Context<V> {
+    pub(super) fn new(
+        inputs: Inputs,
+        outputs: Outputs,
+        arrays: NodeArrays,
+        constants: ConstantTable,
+    ) -> Self {
+        Self {
+            root: Some(ContextRoot {
+                inputs,
+                outputs,
+                arrays,
+                constants,
+            }),
+            stack: Vec::new(),
+        }
+    }
+
+    pub(super) fn get_variable(&self, ident: &str) -> Option<V> {
+        for scope in self.stack.iter().rev() {
+            if let Some(v) = scope.variables.get(ident) {
+                return Some(v.clone());
+            }
+            if scope.isolated {
+                break;
+            }
+        }
+        None
+    }
+
+    pub(super) fn define_variable(&mut self, ident: String, value: V) {
+        self.current_scope().variables.insert(ident, value);
+    }
+
+    pub(super) fn define_constant(&mut self, ident: String, value: ConstantValue) {
+        self.current_scope().constants.insert(ident, value);
+    }
+
+    fn current_scope(&mut self) -> &mut ContextScope<V> {
+        if self.stack.is_empty() {
+            self.stack.push(ContextScope::default());
+        }
+        self.stack.last_mut().unwrap()
+    }
+
+    pub(super) fn get_variable_err(&self, ident: &str) -> Result<V, CompileErrorType> {
+        self.get_variable(ident)
+            .ok_or(CompileErrorType::UnknownName {
+                name: ident.to_owned(),
+            })
+    }
+
+    pub(super) fn get_input(&self, name: &str) -> Result<&Rc<InputNode>, CompileErrorType> {
+        if let Some(root) = &self.root {
+            if root.arrays.inputs.contains_key(name) {
+                return Err(CompileErrorType::IndexRequired {
+                    name: name.to_owned(),
+                });
+            }
+            root.inputs.get(name).ok_or(CompileErrorType::UnknownField {
+                ident: name.to_owned(),
+            })
+        } else {
+            todo!()
+            //Err(CompileErrorType::InputsAreNotAvailable)
+        }
+    }
+
+    pub(super) fn get_output(
+        &self,
+        name: &str,
+    ) -> Result<&Rc<RefCell<OutputNode>>, CompileErrorType> {
+        if let Some(root) = &self.root {
+            if root.arrays.outputs.contains_key(name) {
+                return Err(CompileErrorType::IndexRequired {
+                    name: name.to_owned(),
+                });
+            }
+            root.outputs
+                .get(name)
+                .ok_or(CompileErrorType::UnknownField {
+                    ident: name.to_owned(),
+                })
+        } else {
+            todo!()
+            //Err(CompileErrorType::OutputsAreNotAvailable)
+        }
+    }
+
+    // inputs.name[index] の入力ノード名
+    pub(super) fn input_element<'a>(
+        &self,
+        object: &Spanned<Expr>,
+        index: &Spanned<Expr>,
+        filename: &'a str,
+    ) -> Result<String, CompileError<'a>> {
+        match &object.inner {
+            Expr::MemberAccess(inputs, name) if matches!(inputs.inner, Expr::Inputs) => {
+                let arrays = &self.root.as_ref().unwrap().arrays.inputs;
+                element(arrays.get(name), name, &object.span, index, self, filename)
+            }
+            _ => Err(CompileError::new(
+                filename,
+                object.span.clone(),
+                CompileErrorType::NotAnArray,
+            )),
+        }
+    }
+
+    // 代入先 outputs.name / outputs.name[index] の出力ノード名
+    pub(super) fn output_name<'a>(
+        &self,
+        target: &Spanned<AssignmentTarget>,
+        filename: &'a str,
+    ) -> Result<String, CompileError<'a>> {
+        match &target.inner {
+            AssignmentTarget::FieldAccess(outputs, name)
+                if matches!(outputs.inner, AssignmentTarget::Outputs) =>
+            {
+                Ok(name.clone())
+            }
+            AssignmentTarget::Index(object, index) => match &object.inner {
+                AssignmentTarget::FieldAccess(outputs, name)
+                    if matches!(outputs.inner, AssignmentTarget::Outputs) =>
+                {
+                    let arrays = &self.root.as_ref().unwrap().arrays.outputs;
+                    element(arrays.get(name), name, &object.span, index, self, filename)
+                }
+                _ => Err(CompileError::new(
+                    filename,
+                    object.span.clone(),
+                    CompileErrorType::NotAnArray,
+                )),
+            },
+            _ => Err(CompileError::new(
+                filename,
+                target.span.clone(),
+                CompileErrorType::InvalidAssignment,
+            )),
+        }
+    }
+
+    pub(super) fn push_scope(&mut self) {
+        self.stack.push(ContextScope::default());
+    }
+
+    pub(super) fn push_isolated_scope(&mut self) {
+        self.stack.push(ContextScope {
+            isolated: true,
+            ..Default::default()
+        });
+    }
+
+    pub(super) fn pop_scope(&mut self) -> Option<ContextScope<V>> {
+        self.stack.pop()
+    }
+}
+
+// 変数と同じく内側のスコープから探す (同じ名前の変数があればそちらが優先)
+impl<V> Constants for Context<V> {
+    fn get_constant(&self, name: &str) -> Option<&ConstantValue> {
+        for scope in self.stack.iter().rev() {
+            if scope.variables.contains_key(name) {
+                return None;
+            }
+            if let Some(v) = scope.constants.get(name) {
+                return Some(v);
+            }
+            if scope.isolated {
+                break;
+            }
+        }
+        self.root.as_ref()?.constants.get_constant(name)
+    }
+}
+
+// 配列の要素のノード名 (添字は定数)
+fn element<'a>(
+    length: Option<&usize>,
+    name: &str,
+    span: &std::ops::Range<usize>,
+    index: &Spanned<Expr>,
+    constants: &dyn Constants,
+    filename: &'a str,
+) -> Result<String, CompileError<'a>> {
+    let Some(length) = length else {
+        return Err(CompileError::new(
+            filename,
+            span.clone(),
+            CompileErrorType::NotAnArray,
+        ));
+    };
+    let i: i64 = evaluate_expr(index, constants, filename)?
+        .int_ranged(0..=*length as i64 - 1)?
+        .try_into()?;
+    Ok(element_name(name, i as usize))
+}
+
+pub(super) fn element_name(name: &str, index: usize) -> String {
+    format!("{}[{}]", name, index)
+}
@@ -1,7 +1,7 @@
-use super::ValueType;
+use super::{ValueType, signature::STR};
 use crate::{
     compile_error::{CompileError, CompileErrorType},
-    syntax::{Expr, Spanned},
+    syntax::{BinaryOp, Expr, Spanned, UnaryOp},
 };
 
 use std::ops::{Range, RangeInclusive};
@@ -13,39 +13,173 @@ fn to_tuple_2<T>(values: Vec<T>) -> Option<(T, T)> {
     Some((v0, v1))
 }
 
+// 名前から定数を引く
+pub(super) trait Constants {
+    fn get_constant(&self, name: &str) -> Option<&ConstantValue>;
+}
+
 pub(super) fn evaluate_expr<'a>(
     spanned_expr: &Spanned<Expr>,
+    constants: &dyn Constants,
     filename: &'a str,
 ) -> Result<EvaluatedValue<'a>, CompileError<'a>> {
+    let span = &spanned_expr.span;
     let inner = match &spanned_expr.inner {
-        Expr::Null => todo!(),
         Expr::BoolLiteral(v) => EvaluatedValueInner::Bool(*v),
         Expr::IntLiteral(v) => EvaluatedValueInner::Int(*v),
         Expr::FloatLiteral(v) => EvaluatedValueInner::Float(*v),
         Expr::StringLiteral(v) => EvaluatedValueInner::String(v.clone()),
-        Expr::Ident(_) => todo!(),
-        Expr::Inputs => todo!(),
-        Expr::Outputs => todo!(),
+        Expr::Ident(name) => {
+            return match constants.get_constant(name) {
+                Some(value) => Ok(value.evaluated(filename, span)),
+                None => Err(CompileError::new(
+                    filename,
+                    span.clone(),
+                    CompileErrorType::UnknownName { name: name.clone() },
+                )),
+            };
+        }
         Expr::Tuple(items) => {
             let mut values: Vec<EvaluatedValue<'_>> = Vec::with_capacity(items.len());
             for item in items {
-                values.push(evaluate_expr(item, filename)?);
+                values.push(evaluate_expr(item, constants, filename)?);
             }
             EvaluatedValueInner::Tuple(values)
         }
-        Expr::MemberAccess(_, _) => todo!(),
-        Expr::BinaryOp(_) => todo!(),
-        Expr::UnaryOp(_) => todo!(),
-        Expr::Block { .. } => todo!(),
-        Expr::FunctionCall { .. } => todo!(),
+        Expr::BinaryOp(op) => binary_operation(op, constants, filename)?,
+        Expr::UnaryOp(UnaryOp::Neg(x)) => match evaluate_expr(x, constants, filename)?.number()? {
+            Number::Int(v) => v.checked_neg().map_or(
+                EvaluatedValueInner::Float(-(v as f64)),
+                EvaluatedValueInner::Int,
+            ),
+            Number::Float(v) => EvaluatedValueInner::Float(-v),
+        },
+        Expr::Block {
+            statements,
+            return_value: Some(value),
+        } if statements.is_empty() => return evaluate_expr(value, constants, filename),
+        Expr::If {
+            condition,
+            then,
+            otherwise,
+        } => {
+            let condition: bool = evaluate_expr(condition, constants, filename)?.try_into()?;
+            let value = if condition { then } else { otherwise };
+            return evaluate_expr(value, constants, filename);
+        }
+        // str(value) で文字列にする
+        Expr::FunctionCall { ident, props, args } if ident == "str" => {
+            let ([], [value]) = STR.bind(props, args, span).map_err(|errors| {
+                let (span, error_type) = errors.into_iter().next().unwrap();
+                CompileError::new(filename, span, error_type)
+            })?;
+            EvaluatedValueInner::String(evaluate_expr(value, constants, filename)?.into_text()?)
+        }
+        // レイアウトの指示は値に影響しない
+        Expr::Annotated { expr, .. } => return evaluate_expr(expr, constants, filename),
+        Expr::Null
+        | Expr::Inputs
+        | Expr::Outputs
+        | Expr::MemberAccess(_, _)
+        | Expr::Index(_, _)
+        | Expr::Block { .. }
+        | Expr::Match { .. }
+        | Expr::FunctionCall { .. }
+        | Expr::Lua { .. } => {
+            return Err(CompileError::new(
+                filename,
+                span.clone(),
+                CompileErrorType::NotConstant,
+            ));
+        }
     };
     Ok(EvaluatedValue {
         inner,
         filename,
-        span: spanned_expr.span.clone(),
+        span: span.clone(),
     })
 }
 
+// 整数どうしの演算は整数 (桁あふれしたら浮動小数点数)
+// / は論理と同じく常に浮動小数点数、+ は文字列の連結にも使う
+fn binary_operation<'a>(
+    op: &BinaryOp,
+    constants: &dyn Constants,
+    filename: &'a str,
+) -> Result<EvaluatedValueInner<'a>, CompileError<'a>> {
+    let (lhs, rhs) = match op {
+        BinaryOp::Add(lhs, rhs)
+        | BinaryOp::Sub(lhs, rhs)
+        | BinaryOp::Mul(lhs, rhs)
+        | BinaryOp::Div(lhs, rhs)
+        | BinaryOp::Mod(lhs, rhs)
+        | BinaryOp::Pow(lhs, rhs)
+        | BinaryOp::Lt(lhs, rhs)
+        | BinaryOp::Gt(lhs, rhs) => (lhs, rhs),
+    };
+    let lhs = evaluate_expr(lhs, constants, filename)?;
+    let rhs = evaluate_expr(rhs, constants, filename)?;
+
+    if let BinaryOp::Add(..) = op
+        && (matches!(lhs.inner, EvaluatedValueInner::String(_))
+            || matches!(rhs.inner, EvaluatedValueInner::String(_)))
+    {
+        let lhs: String = lhs.try_into()?;
+        let rhs: String = rhs.try_into()?;
+        return Ok(EvaluatedValueInner::String(lhs + &rhs));
+    }
+
+    if let BinaryOp::Mod(..) = op
+        && matches!(rhs.inner, EvaluatedValueInner::Int(0))
+    {
+        return Err(CompileError::new(
+            filename,
+            rhs.span,
+            CompileErrorType::ModuloByZero,
+        ));
+    }
+    let (a, b) = (lhs.number()?, rhs.number()?);
+    if let (Number::Int(a), Number::Int(b)) = (a, b) {
+        let v = match op {
+            BinaryOp::Add(..) => a.checked_add(b),
+            BinaryOp::Sub(..) => a.checked_sub(b),
+            BinaryOp::Mul(..) => a.checked_mul(b),
+            BinaryOp::Mod(..) => a.checked_rem(b),
+            BinaryOp::Pow(..) => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+            _ => None,
+        };
+        if let Some(v) = v {
+            return Ok(EvaluatedValueInner::Int(v));
+        }
+    }
+    let (a, b) = (a.to_f64(), b.to_f64());
+    Ok(match op {
+        BinaryOp::Add(..) => EvaluatedValueInner::Float(a + b),
+        BinaryOp::Sub(..) => EvaluatedValueInner::Float(a - b),
+        BinaryOp::Mul(..) => EvaluatedValueInner::Float(a * b),
+        BinaryOp::Div(..) => EvaluatedValueInner::Float(a / b),
+        BinaryOp::Mod(..) => EvaluatedValueInner::Float(a % b),
+        BinaryOp::Pow(..) => EvaluatedValueInner::Float(a.powf(b)),
+        BinaryOp::Lt(..) => EvaluatedValueInner::Bool(a < b),
+        BinaryOp::Gt(..) => EvaluatedValueInner::Bool(a > b),
+    })
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn to_f64(self) -> f64 {
+        match self {
+            Self::Int(v) => v as f64,
+            Self::Float(v) => v,
+        }
+    }
+}
+
 #[derive(Debug)]
 enum EvaluatedValueInner<'a> {
     Bool(bool),
@@ -69,6 +203,52 @@ impl<'a> EvaluatedValueInner<'a> {
     }
 }
 
+// 定数として覚えておく値 (使う位置で EvaluatedValue に戻す)
+#[derive(Clone, Debug)]
+pub(super) enum ConstantValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Tuple(Vec<ConstantValue>),
+}
+
+impl ConstantValue {
+    fn evaluated<'a>(&self, filename: &'a str, span: &Range<usize>) -> EvaluatedValue<'a> {
+        let inner = match self {
+            Self::Bool(v) => EvaluatedValueInner::Bool(*v),
+            Self::Int(v) => EvaluatedValueInner::Int(*v),
+            Self::Float(v) => EvaluatedValueInner::Float(*v),
+            Self::String(v) => EvaluatedValueInner::String(v.clone()),
+            Self::Tuple(items) => EvaluatedValueInner::Tuple(
+                items
+                    .iter()
+                    .map(|item| item.evaluated(filename, span))
+                    .collect(),
+            ),
+        };
+        EvaluatedValue {
+            inner,
+            filename,
+            span: span.clone(),
+        }
+    }
+}
+
+impl<'a> From<EvaluatedValue<'a>> for ConstantValue {
+    fn from(value: EvaluatedValue<'a>) -> Self {
+        match value.inner {
+            EvaluatedValueInner::Bool(v) => Self::Bool(v),
+            EvaluatedValueInner::Int(v) => Self::Int(v),
+            EvaluatedValueInner::Float(v) => Self::Float(v),
+            EvaluatedValueInner::String(v) => Self::String(v),
+            EvaluatedValueInner::Tuple(items) => {
+                Self::Tuple(items.into_iter().map(Self::from).collect())
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct EvaluatedValue<'a> {
     inner: EvaluatedValueInner<'a>,
@@ -77,6 +257,44 @@ pub(super) struct EvaluatedValue<'a> {
 }
 
 impl<'a> EvaluatedValue<'a> {
+    fn number(self) -> Result<Number, CompileError<'a>> {
+        match self.inner {
+            EvaluatedValueInner::Int(v) => Ok(Number::Int(v)),
+            EvaluatedValueInner::Float(v) => Ok(Number::Float(v)),
+            _ => Err(CompileError::new(
+                self.filename,
+                self.span,
+                CompileErrorType::IncompatibleType {
+                    expected_types: vec![ValueType::Int, ValueType::Float],
+                    found_type: self.inner.value_type(),
+                },
+            )),
+        }
+    }
+
+    // str(value) の結果
+    fn into_text(self) -> Result<String, CompileError<'a>> {
+        match self.inner {
+            EvaluatedValueInner::Bool(v) => Ok(v.to_string()),
+            EvaluatedValueInner::Int(v) => Ok(v.to_string()),
+            EvaluatedValueInner::Float(v) => Ok(v.to_string()),
+            EvaluatedValueInner::String(v) => Ok(v),
+            EvaluatedValueInner::Tuple(_) => Err(CompileError::new(
+                self.filename,
+                self.span,
+                CompileErrorType::IncompatibleType {
+                    expected_types: vec![
+                        ValueType::Bool,
+                        ValueType::Int,
+                        ValueType::Float,
+                        ValueType::String,
+                    ],
+                    found_type: self.inner.value_type(),
+                },
+            )),
+        }
+    }
+
     pub(super) fn int_ranged(self, range: RangeInclusive<i64>) -> Result<Self, CompileError<'a>> {
         match &self.inner {
             EvaluatedValueInner::Int(v) => {
@@ -184,8 +402,9 @@ impl<'a> TryFrom<EvaluatedValue<'a>> for f64 {
 impl<'a> TryFrom<EvaluatedValue<'a>> for f32 {
     type Error = CompileError<'a>;
 
+    // 整数も数値として受け付ける
     fn try_from(value: EvaluatedValue<'a>) -> Result<Self, Self::Error> {
-        f64::try_from(value).map(|v| v as f32)
+        value.number().map(|v| v.to_f64() as f32)
     }
 }
 
@@ -244,3 +463,24 @@ impl<'a> TryFrom<EvaluatedValue<'a>> for (u8, u8) {
         ))
     }
 }
+
+impl<'a> TryFrom<EvaluatedValue<'a>> for (f32, f32) {
+    type Error = CompileError<'a>;
+
+    fn try_from(value: EvaluatedValue<'a>) -> Result<Self, Self::Error> {
+        let found_type = value.inner.value_type();
+        if let EvaluatedValueInner::Tuple(items) = value.inner
+            && let Some((v0, v1)) = to_tuple_2(items)
+        {
+            return Ok((v0.try_into()?, v1.try_into()?));
+        }
+        Err(CompileError::new(
+            value.filename,
+            value.span,
+            CompileErrorType::IncompatibleType {
+                expected_types: vec![ValueType::Tuple(vec![ValueType::Float, ValueType::Float])],
+                found_type,
+            },
+        ))
+    }
+}
@@ -0,0 +1,42 @@
+use super::evaluate_expr::{ConstantValue, Constants, evaluate_expr};
+use crate::{compile_error::CompileError, syntax::ForLoop};
+
+use std::ops::Range;
+
+// ループ変数の値 (start..end、end は含まない)
+pub(super) fn iterations<'f, T>(
+    for_loop: &ForLoop<T>,
+    constants: &dyn Constants,
+    filename: &'f str,
+) -> Result<Range<i64>, CompileError<'f>> {
+    let start: i64 = evaluate_expr(&for_loop.start, constants, filename)?.try_into()?;
+    let end: i64 = evaluate_expr(&for_loop.end, constants, filename)?.try_into()?;
+    Ok(start..end)
+}
+
+// 本体で起きたエラーに何回目の展開かを添える
+pub(super) fn note_iteration(errors: &mut [CompileError], variable: &str, value: i64) {
+    for error in errors {
+        error.add_note(format!(
+            "In the iteration where `{}` is {}",
+            variable, value
+        ));
+    }
+}
+
+// 外側の定数にループ変数を 1 つ加えたもの
+pub(super) struct IterationConstants<'c> {
+    pub(super) outer: &'c dyn Constants,
+    pub(super) variable: &'c str,
+    pub(super) value: ConstantValue,
+}
+
+impl Constants for IterationConstants<'_> {
+    fn get_constant(&self, name: &str) -> Option<&ConstantValue> {
+        if name == self.variable {
+            Some(&self.value)
+        } else {
+            self.outer.get_constant(name)
+        }
+    }
+}
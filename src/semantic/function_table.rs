@@ -0,0 +1,106 @@
+use super::signature::{BUILTIN_FUNCTIONS, FunctionSignature};
+use crate::{
+    compile_error::{CompileError, CompileErrorType},
+    syntax::{AssignmentTarget, Expr, Function, Spanned},
+};
+
+use std::collections::{HashMap, HashSet};
+
+// ユーザー定義関数 (名前 -> 定義)
+// マイクロコントローラー内の定義は同じ名前のファイルの定義を隠す
+#[derive(Clone, Default, Debug)]
+pub(super) struct FunctionTable<'t> {
+    functions: HashMap<&'t str, &'t Function>,
+}
+
+impl<'t> FunctionTable<'t> {
+    // 同じ階層の定義を加えた表
+    pub(super) fn with<'f>(
+        &self,
+        functions: impl IntoIterator<Item = (&'t Function, &'t std::ops::Range<usize>)>,
+        filename: &'f str,
+        errors: &mut Vec<CompileError<'f>>,
+    ) -> Self {
+        let mut table = self.clone();
+        let mut defined = HashSet::new();
+        for (function, span) in functions {
+            if !defined.insert(function.name.as_str()) {
+                errors.push(CompileError::new(
+                    filename,
+                    span.clone(),
+                    CompileErrorType::FunctionAlreadyDefined {
+                        name: function.name.clone(),
+                    },
+                ));
+                continue;
+            }
+
+            // プロパティと引数で同じ名前は使えない
+            let mut names = HashSet::new();
+            for name in function.props.iter().chain(&function.params) {
+                if !names.insert(name.inner.as_str()) {
+                    errors.push(CompileError::new(
+                        filename,
+                        name.span.clone(),
+                        CompileErrorType::DuplicateParameter {
+                            name: name.inner.clone(),
+                            signature: FunctionSignature(function).to_string(),
+                        },
+                    ));
+                }
+            }
+
+            table.functions.insert(&function.name, function);
+        }
+        table
+    }
+
+    pub(super) fn get(&self, name: &str) -> Option<&'t Function> {
+        self.functions.get(name).copied()
+    }
+
+    // 見つからない関数の呼び出し (組み込み関数と定義済みの関数を候補に挙げる)
+    pub(super) fn unknown(&self, name: &str) -> CompileErrorType {
+        let mut defined = self.functions.keys().copied().collect::<Vec<_>>();
+        defined.sort();
+        CompileErrorType::UnknownFunction {
+            name: name.to_owned(),
+            available: BUILTIN_FUNCTIONS
+                .into_iter()
+                .chain(defined)
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+}
+
+// 関数の本体では入出力を使えない (値は引数と戻り値で受け渡す)
+// inputs.name と inputs.name[i] を読む式か
+pub(super) fn reads_inputs(expr: &Expr) -> bool {
+    match expr {
+        Expr::Inputs => true,
+        Expr::MemberAccess(object, _) | Expr::Index(object, _) => reads_inputs(&object.inner),
+        _ => false,
+    }
+}
+
+// outputs.name と outputs.name[i] への代入か
+pub(super) fn assigns_outputs(target: &AssignmentTarget) -> bool {
+    match target {
+        AssignmentTarget::Outputs => true,
+        AssignmentTarget::FieldAccess(object, _) | AssignmentTarget::Index(object, _) => {
+            assigns_outputs(&object.inner)
+        }
+        _ => false,
+    }
+}
+
+// 関数定義とその位置を取り出す
+pub(super) fn functions<'t, T>(
+    elements: &'t [Spanned<T>],
+    f: impl Fn(&'t T) -> Option<&'t Function>,
+) -> impl Iterator<Item = (&'t Function, &'t std::ops::Range<usize>)> {
+    elements
+        .iter()
+        .filter_map(move |e| f(&e.inner).map(|function| (function, &e.span)))
+}
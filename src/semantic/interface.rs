@@ -1,14 +1,25 @@
-use super::{FieldAnalyzer, ValueType};
+use super::{
+    FieldAnalyzer, ValueType,
+    context::element_name,
+    evaluate_expr::{ConstantValue, Constants},
+    for_loop::{IterationConstants, iterations, note_iteration},
+    has_errors,
+    interval::Interval,
+    unit::Unit,
+};
 use crate::{
     compile_error::{CompileError, CompileErrorType},
     microcontroller::{InputNode, Node, NodeInner, NodeMode, NodePosition, NodeType, OutputNode},
     semantic::evaluate_expr,
-    syntax::{MicrocontrollerInterface, MicrocontrollerInterfaceNode, Spanned},
+    syntax::{
+        ForLoop, InterfaceItem, MicrocontrollerInterface, MicrocontrollerInterfaceNode, Spanned,
+    },
 };
 
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    ops::Range,
     rc::Rc,
 };
 
@@ -18,6 +29,8 @@ pub(super) struct InterfaceAnalyzer<'a> {
     inputs: bool,
     outputs: bool,
     node_placement: NodePlacement,
+    annotations: Annotations,
+    arrays: NodeArrays,
 }
 
 impl<'a> InterfaceAnalyzer<'a> {
@@ -27,12 +40,15 @@ impl<'a> InterfaceAnalyzer<'a> {
             inputs: false,
             outputs: false,
             node_placement: NodePlacement::new(size),
+            annotations: Annotations::default(),
+            arrays: NodeArrays::default(),
         }
     }
 
     pub(super) fn element(
         &mut self,
         element: &Spanned<MicrocontrollerInterface>,
+        constants: &dyn Constants,
         errors: &mut Vec<CompileError<'a>>,
     ) {
         let (nodes, mode) = match &element.inner {
@@ -64,10 +80,128 @@ impl<'a> InterfaceAnalyzer<'a> {
             }
         };
 
-        for node in nodes {
-            match analyze_node(mode, node, self.filename) {
-                Ok(n) => self.node_placement.add(n),
-                Err(err) => errors.push(err),
+        let mut elements = BTreeMap::new();
+        self.items(nodes, mode, constants, &mut elements, errors);
+
+        // 要素ごとに宣言した配列は、添字が 0 から欠けずに並んでいなければならない
+        let arrays = match mode {
+            NodeMode::Input => &mut self.arrays.inputs,
+            NodeMode::Output => &mut self.arrays.outputs,
+        };
+        for (name, (mut indices, span)) in elements {
+            indices.sort();
+            if let Some(index) = (0..indices.len()).find(|i| indices[*i] != *i) {
+                errors.push(CompileError::new(
+                    self.filename,
+                    span,
+                    CompileErrorType::MissingElement { name, index },
+                ));
+                continue;
+            }
+            arrays.insert(name, indices.len());
+        }
+    }
+
+    // elements は要素ごとに宣言した配列の名前 -> 添字と最初の宣言の位置
+    fn items(
+        &mut self,
+        items: &[Spanned<InterfaceItem>],
+        mode: NodeMode,
+        constants: &dyn Constants,
+        elements: &mut BTreeMap<String, (Vec<usize>, Range<usize>)>,
+        errors: &mut Vec<CompileError<'a>>,
+    ) {
+        for item in items {
+            let node = match &item.inner {
+                InterfaceItem::Node(node) => node,
+                InterfaceItem::For(for_loop) => {
+                    self.for_loop(for_loop, mode, constants, elements, errors);
+                    continue;
+                }
+            };
+            let (n, annotation, shape) =
+                match analyze_node(mode, node, &item.span, constants, self.filename) {
+                    Ok(analyzed) => analyzed,
+                    Err(err) => {
+                        errors.push(err);
+                        continue;
+                    }
+                };
+            let (annotations, arrays) = match mode {
+                NodeMode::Input => (&mut self.annotations.inputs, &mut self.arrays.inputs),
+                NodeMode::Output => (&mut self.annotations.outputs, &mut self.arrays.outputs),
+            };
+            match shape {
+                NodeShape::Scalar => {
+                    annotations.insert(n.name.clone(), annotation);
+                    self.node_placement.add(n);
+                }
+                // 配列は要素ごとに name[i] のノードにする
+                NodeShape::Array(length) => {
+                    if elements.contains_key(&n.name) {
+                        errors.push(CompileError::new(
+                            self.filename,
+                            item.span.clone(),
+                            CompileErrorType::ElementAlreadyDeclared,
+                        ));
+                        continue;
+                    }
+                    for i in 0..length {
+                        let element = n.element(i);
+                        annotations.insert(element.name.clone(), annotation.clone());
+                        self.node_placement.add(element);
+                    }
+                    arrays.insert(n.name, length);
+                }
+                NodeShape::Element(index) => {
+                    let (indices, _) = elements
+                        .entry(n.name.clone())
+                        .or_insert_with(|| (Vec::new(), item.span.clone()));
+                    if arrays.contains_key(&n.name) || indices.contains(&index) {
+                        errors.push(CompileError::new(
+                            self.filename,
+                            item.span.clone(),
+                            CompileErrorType::ElementAlreadyDeclared,
+                        ));
+                        continue;
+                    }
+                    indices.push(index);
+                    let element = n.element(index);
+                    annotations.insert(element.name.clone(), annotation);
+                    self.node_placement.add(element);
+                }
+            }
+        }
+    }
+
+    // 本体を回数分展開する (エラーが出たら残りは展開しない)
+    fn for_loop(
+        &mut self,
+        for_loop: &ForLoop<InterfaceItem>,
+        mode: NodeMode,
+        constants: &dyn Constants,
+        elements: &mut BTreeMap<String, (Vec<usize>, Range<usize>)>,
+        errors: &mut Vec<CompileError<'a>>,
+    ) {
+        let range = match iterations(for_loop, constants, self.filename) {
+            Ok(range) => range,
+            Err(err) => {
+                errors.push(err);
+                return;
+            }
+        };
+        let variable = &for_loop.variable.inner;
+        for i in range {
+            let count = errors.len();
+            let constants = IterationConstants {
+                outer: constants,
+                variable,
+                value: ConstantValue::Int(i),
+            };
+            self.items(&for_loop.body, mode, &constants, elements, errors);
+            note_iteration(&mut errors[count..], variable, i);
+            if has_errors(&errors[count..]) {
+                break;
             }
         }
     }
@@ -95,6 +229,8 @@ impl<'a> InterfaceAnalyzer<'a> {
             inputs,
             outputs,
             nodes,
+            annotations: self.annotations,
+            arrays: self.arrays,
         }
     }
 }
@@ -105,35 +241,65 @@ pub(super) struct Interface {
     pub(super) inputs: HashMap<String, Rc<InputNode>>,
     pub(super) outputs: HashMap<String, Rc<RefCell<OutputNode>>>,
     pub(super) nodes: Vec<Node>,
+    pub(super) annotations: Annotations,
+    pub(super) arrays: NodeArrays,
+}
+
+// 数値ノードに宣言された範囲と単位
+#[derive(Clone, Default, Debug)]
+pub(super) struct NodeAnnotation {
+    pub(super) range: Option<Interval>,
+    pub(super) unit: Option<Unit>,
+    // 範囲を外れうる値の前に Clamp を入れる (出力のみ)
+    pub(super) clamp: bool,
+}
+
+// ノード名 -> 範囲と単位
+#[derive(Default, Debug)]
+pub(super) struct Annotations {
+    pub(super) inputs: HashMap<String, NodeAnnotation>,
+    pub(super) outputs: HashMap<String, NodeAnnotation>,
+}
+
+// 配列のノード名 -> 要素数
+#[derive(Default, Debug)]
+pub(super) struct NodeArrays {
+    pub(super) inputs: HashMap<String, usize>,
+    pub(super) outputs: HashMap<String, usize>,
+}
+
+// 1 つのノードか、配列全体か、配列の要素 1 つか
+#[derive(Clone, Copy, Debug)]
+enum NodeShape {
+    Scalar,
+    Array(usize),
+    Element(usize),
 }
 
 fn analyze_node<'a>(
     mode: NodeMode,
-    node: &Spanned<MicrocontrollerInterfaceNode>,
+    node: &MicrocontrollerInterfaceNode,
+    span: &Range<usize>,
+    constants: &dyn Constants,
     filename: &'a str,
-) -> Result<FloatingNode, CompileError<'a>> {
-    let node_type = match ValueType::from_str(&node.type_name) {
-        Ok(ValueType::Bool) => NodeType::Bool,
-        Ok(ValueType::Float) => NodeType::Number,
-        Ok(found_type) => {
-            return Err(CompileError::new(
-                filename,
-                node.span.clone(),
-                CompileErrorType::IncompatibleType {
-                    expected_types: vec![ValueType::Bool, ValueType::Float],
-                    found_type,
-                },
-            ));
+) -> Result<(FloatingNode, NodeAnnotation, NodeShape), CompileError<'a>> {
+    let node_type = ValueType::node_type(&node.type_name)
+        .map_err(|err| CompileError::new(filename, span.clone(), err))?;
+
+    let shape = match (&node.length, &node.index) {
+        (Some(expr), _) => {
+            let length: i64 = evaluate_expr(expr, constants, filename)?
+                .int_ranged(1..=36)?
+                .try_into()?;
+            NodeShape::Array(length as usize)
         }
-        Err(err) => {
-            return Err(CompileError::new(
-                filename,
-                node.span.clone(),
-                CompileErrorType::UnknownType {
-                    type_name: err.to_owned(),
-                },
-            ));
+        (None, Some(expr)) => {
+            let index: i64 = evaluate_expr(expr, constants, filename)?
+                .int_ranged(0..=35)?
+                .try_into()?;
+            NodeShape::Element(index as usize)
         }
+        (None, None) => NodeShape::Scalar,
     };
 
     let mut fields = FieldAnalyzer::new(filename);
@@ -141,20 +307,71 @@ fn analyze_node<'a>(
     let mut label = None;
     let mut description = None;
     let mut position = None;
+    let mut annotation = NodeAnnotation::default();
+
+    // 範囲と単位は数値ノードにだけ付けられる
+    let number_only = |field, expr: &Spanned<_>| {
+        if node_type == NodeType::Number {
+            Ok(())
+        } else {
+            Err(CompileError::new(
+                filename,
+                expr.span.clone(),
+                CompileErrorType::NumberNodeOnly { field },
+            ))
+        }
+    };
 
     if let Some(f) = &node.fields {
         for assignment in f {
             fields.assignment(assignment, |ident, expr| {
                 match ident.as_str() {
-                    "name" => label = Some(evaluate_expr(expr, filename)?.try_into()?),
-                    "description" => description = Some(evaluate_expr(expr, filename)?.try_into()?),
+                    "name" => label = Some(evaluate_expr(expr, constants, filename)?.try_into()?),
+                    "description" => {
+                        description = Some(evaluate_expr(expr, constants, filename)?.try_into()?)
+                    }
                     "position" => {
+                        if !matches!(shape, NodeShape::Scalar) {
+                            return Err(CompileError::new(
+                                filename,
+                                expr.span.clone(),
+                                CompileErrorType::ScalarNodeOnly { field: "position" },
+                            ));
+                        }
                         position = Some(
-                            evaluate_expr(expr, filename)?
+                            evaluate_expr(expr, constants, filename)?
                                 .tuple_int_ranged(vec![0..=5, 0..=5])?
                                 .try_into()?,
                         )
                     }
+                    "range" => {
+                        number_only("range", expr)?;
+                        let (min, max): (f32, f32) =
+                            evaluate_expr(expr, constants, filename)?.try_into()?;
+                        if min > max {
+                            return Err(CompileError::new(
+                                filename,
+                                expr.span.clone(),
+                                CompileErrorType::EmptyRange { min, max },
+                            ));
+                        }
+                        annotation.range = Some(Interval::new(min as f64, max as f64));
+                    }
+                    "unit" => {
+                        number_only("unit", expr)?;
+                        let unit: String = evaluate_expr(expr, constants, filename)?.try_into()?;
+                        annotation.unit = Some(unit.parse().map_err(|_| {
+                            CompileError::new(
+                                filename,
+                                expr.span.clone(),
+                                CompileErrorType::InvalidUnit { unit },
+                            )
+                        })?);
+                    }
+                    "clamp" if mode.is_output() => {
+                        number_only("clamp", expr)?;
+                        annotation.clamp = evaluate_expr(expr, constants, filename)?.try_into()?;
+                    }
                     _ => return Ok(false),
                 }
                 Ok(true)
@@ -162,14 +379,30 @@ fn analyze_node<'a>(
         }
     }
 
-    Ok(FloatingNode {
+    if annotation.clamp && annotation.range.is_none() {
+        return Err(CompileError::new(
+            filename,
+            span.clone(),
+            CompileErrorType::PropertyRequired {
+                expect_str: "range",
+            },
+        ));
+    }
+
+    // 配列の名前の {i} は要素の添字に置き換える
+    let default_label = match shape {
+        NodeShape::Scalar => node.name.clone(),
+        NodeShape::Array(_) | NodeShape::Element(_) => format!("{}[{{i}}]", node.name),
+    };
+    let floating_node = FloatingNode {
         name: node.name.clone(),
         mode,
-        label: label.unwrap_or_else(|| node.name.clone()),
-        description: description.unwrap_or_default(),
+        label: label.unwrap_or(default_label),
+        description: description.or_else(|| node.doc.clone()).unwrap_or_default(),
         node_type,
         position,
-    })
+    };
+    Ok((floating_node, annotation, shape))
 }
 
 // 位置を決める前のノード情報
@@ -183,6 +416,21 @@ struct FloatingNode {
     position: Option<(u8, u8)>,
 }
 
+impl FloatingNode {
+    // 配列の i 番目の要素
+    fn element(&self, i: usize) -> Self {
+        let index = i.to_string();
+        Self {
+            name: element_name(&self.name, i),
+            mode: self.mode,
+            label: self.label.replace("{i}", &index),
+            description: self.description.replace("{i}", &index),
+            node_type: self.node_type,
+            position: None,
+        }
+    }
+}
+
 // ノードの自動配置
 #[derive(Debug)]
 struct NodePlacement {
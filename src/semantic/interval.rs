@@ -0,0 +1,93 @@
+use std::fmt::Display;
+
+// 数値がとりうる値の範囲 (閉区間)
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(super) struct Interval {
+    pub(super) min: f64,
+    pub(super) max: f64,
+}
+
+impl Interval {
+    pub(super) fn new(min: f64, max: f64) -> Self {
+        Self { min, max }
+    }
+
+    pub(super) fn point(value: f64) -> Self {
+        Self::new(value, value)
+    }
+
+    // 端点の組み合わせのうち最小と最大
+    fn hull(values: [f64; 4]) -> Self {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Self::new(min, max)
+    }
+
+    pub(super) fn contains_zero(&self) -> bool {
+        self.min <= 0.0 && 0.0 <= self.max
+    }
+
+    pub(super) fn is_within(&self, other: &Self) -> bool {
+        other.min <= self.min && self.max <= other.max
+    }
+
+    pub(super) fn add(self, rhs: Self) -> Self {
+        Self::new(self.min + rhs.min, self.max + rhs.max)
+    }
+
+    pub(super) fn sub(self, rhs: Self) -> Self {
+        Self::new(self.min - rhs.max, self.max - rhs.min)
+    }
+
+    pub(super) fn mul(self, rhs: Self) -> Self {
+        Self::hull([
+            self.min * rhs.min,
+            self.min * rhs.max,
+            self.max * rhs.min,
+            self.max * rhs.max,
+        ])
+    }
+
+    // 0 で割りうるときは結果が 0 にもなるので範囲を求めない
+    pub(super) fn div(self, rhs: Self) -> Option<Self> {
+        if rhs.contains_zero() {
+            None
+        } else {
+            Some(self.mul(Self::new(1.0 / rhs.max, 1.0 / rhs.min)))
+        }
+    }
+
+    // fmod の結果は被除数と同じ符号で、除数の絶対値より小さい
+    pub(super) fn modulo(self, rhs: Self) -> Self {
+        let m = rhs.abs().max;
+        Self::new(self.min.min(0.0).max(-m), self.max.max(0.0).min(m))
+    }
+
+    pub(super) fn neg(self) -> Self {
+        Self::new(-self.max, -self.min)
+    }
+
+    pub(super) fn abs(self) -> Self {
+        if self.contains_zero() {
+            Self::new(0.0, self.max.max(-self.min))
+        } else {
+            let (a, b) = (self.min.abs(), self.max.abs());
+            Self::new(a.min(b), a.max(b))
+        }
+    }
+
+    pub(super) fn clamp(self, min: f64, max: f64) -> Self {
+        Self::new(self.min.clamp(min, max), self.max.clamp(min, max))
+    }
+
+    // 両方を含む最小の区間
+    pub(super) fn union(self, rhs: Self) -> Self {
+        Self::new(self.min.min(rhs.min), self.max.max(rhs.max))
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.min, self.max)
+    }
+}
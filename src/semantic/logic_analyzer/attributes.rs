@@ -0,0 +1,125 @@
+use super::LogicAnalyzer;
+use crate::{
+    compile_error::CompileErrorType,
+    microcontroller::{ComponentPosition, LayoutBounds, LayoutHint, Link, LinkNode},
+    semantic::{to_tuple1, to_tuple2},
+    syntax::{Attribute, Expr, Spanned},
+};
+
+use std::{ops::RangeInclusive, rc::Rc};
+
+impl<'f, 'e, 't> LogicAnalyzer<'f, 'e, 't> {
+    // @pos, @group, @note 付きの式
+    pub(super) fn annotated(
+        &mut self,
+        attrs: &[Spanned<Attribute>],
+        expr: &Spanned<Expr>,
+    ) -> Option<Link> {
+        let first = self.components.len();
+        let link = self.expr_to_components(expr)?;
+
+        // 式の結果を出力するコンポーネント
+        let result = match link.link_node() {
+            Some(LinkNode::Component(c, _)) => c.upgrade().and_then(|c| {
+                self.components
+                    .iter()
+                    .rposition(|component| Rc::ptr_eq(component, &c))
+            }),
+            _ => None,
+        };
+
+        for attr in attrs {
+            let Some(hint) = self.attribute(attr) else {
+                continue;
+            };
+            if hint.group.is_some()
+                && let Some(i) = result
+            {
+                // グループは式の中で作られたコンポーネント全体に付ける
+                let members = if first < self.components.len() {
+                    first..self.components.len()
+                } else {
+                    i..i + 1
+                };
+                for i in members {
+                    self.hints.entry(i).or_default().merge(LayoutHint {
+                        group: hint.group.clone(),
+                        ..Default::default()
+                    });
+                }
+            }
+            match result {
+                Some(i) => self.hints.entry(i).or_default().merge(LayoutHint {
+                    group: None,
+                    ..hint
+                }),
+                None => self.push_error(
+                    attr.span.clone(),
+                    CompileErrorType::AttributeOnNonComponent {
+                        name: attr.name.clone(),
+                    },
+                ),
+            }
+        }
+
+        Some(link)
+    }
+
+    fn attribute(&mut self, attr: &Spanned<Attribute>) -> Option<LayoutHint> {
+        let args = &attr.args;
+        match attr.name.as_str() {
+            "pos" => {
+                let (x, y) = self.result_to_option(to_tuple2(&args.inner, "(x, y)"), &args.span)?;
+                let bounds = LayoutBounds::default();
+                let x = self.coordinate(x, &bounds.x);
+                let y = self.coordinate(y, &bounds.y);
+                Some(LayoutHint {
+                    position: Some(ComponentPosition::new(x?, y?)),
+                    ..Default::default()
+                })
+            }
+            "group" => {
+                let (name,) =
+                    self.result_to_option(to_tuple1(&args.inner, "(name)"), &args.span)?;
+                Some(LayoutHint {
+                    group: Some(self.evaluate_expr(name)?),
+                    ..Default::default()
+                })
+            }
+            "note" => {
+                let (text,) =
+                    self.result_to_option(to_tuple1(&args.inner, "(text)"), &args.span)?;
+                Some(LayoutHint {
+                    note: Some(self.evaluate_expr(text)?),
+                    ..Default::default()
+                })
+            }
+            _ => {
+                self.push_error(
+                    attr.span.clone(),
+                    CompileErrorType::UnknownAttribute {
+                        name: attr.name.clone(),
+                    },
+                );
+                None
+            }
+        }
+    }
+
+    // エディタの範囲外の座標はエラー
+    fn coordinate(&mut self, expr: &Spanned<Expr>, bounds: &RangeInclusive<i32>) -> Option<i32> {
+        let value: i64 = self.evaluate_expr(expr)?;
+        match i32::try_from(value) {
+            Ok(value) if bounds.contains(&value) => Some(value),
+            _ => {
+                self.push_error(
+                    expr.span.clone(),
+                    CompileErrorType::OutOfBounds {
+                        bounds: *bounds.start() as i64..=*bounds.end() as i64,
+                    },
+                );
+                None
+            }
+        }
+    }
+}
@@ -1,137 +1,191 @@
-use super::{LogicAnalyzer, Span};
+use super::{LogicAnalyzer, Span, Variable};
+use crate::semantic::{
+    evaluate_expr::{ConstantValue, evaluate_expr},
+    signature::{ABS, Bound, CLAMP, FunctionSignature, PID, PID_ADVANCED, PropValue, Signature},
+};
 use crate::{
     compile_error::CompileErrorType,
-    microcontroller::{ArithmeticComponent, Component, Link},
-    syntax::{Expr, Spanned},
+    microcontroller::{ArithmeticComponent, Component, ControlComponent, Link},
+    syntax::{Argument, Function, Spanned},
 };
 
-impl<'f, 'e> LogicAnalyzer<'f, 'e> {
+type Props = Option<Spanned<Vec<Spanned<Argument>>>>;
+type Args = Spanned<Vec<Spanned<Argument>>>;
+
+impl<'f, 'e, 't> LogicAnalyzer<'f, 'e, 't> {
     pub(super) fn function_call(
         &mut self,
         func_type: &str,
-        props: &Option<Spanned<Vec<Spanned<Expr>>>>,
-        args: &Spanned<Vec<Spanned<Expr>>>,
+        props: &Props,
+        args: &Args,
         span: &Span,
     ) -> Option<Result<Link, CompileErrorType>> {
+        // ユーザー定義関数は組み込み関数より優先する
+        if let Some(function) = self.functions.get(func_type) {
+            return self.user_function(function, props, args, span);
+        }
+
         let (component, index) = match func_type {
             "clamp" => {
-                let (input,) = self.args_tuple1(args, "(input)")?;
-                let (min, max) = self.props_tuple2(props, "{min, max}", span)?;
+                let ([min, max], [input]) = self.bind(&CLAMP, props, args, span)?;
                 (
                     Component::Arithmetic(ArithmeticComponent::Clamp {
-                        input,
-                        min: self.evaluate_expr(min)?,
-                        max: self.evaluate_expr(max)?,
+                        input: self.expr_to_typed_link(input)?,
+                        min: self.prop(min)?,
+                        max: self.prop(max)?,
                     }),
                     0,
                 )
             }
             "abs" => {
-                let (input,) = self.args_tuple1(args, "(input)")?;
-                (Component::Arithmetic(ArithmeticComponent::Abs { input }), 0)
+                let ([], [input]) = self.bind(&ABS, props, args, span)?;
+                (
+                    Component::Arithmetic(ArithmeticComponent::Abs {
+                        input: self.expr_to_typed_link(input)?,
+                    }),
+                    0,
+                )
+            }
+            // 係数がプロパティなら PID Controller (Advanced)
+            "pid" if props.is_some() => {
+                let ([kp, ki, kd], [setpoint, process_variable, active]) =
+                    self.bind(&PID_ADVANCED, props, args, span)?;
+                (
+                    Component::Control(ControlComponent::PidAdvanced {
+                        setpoint: self.expr_to_typed_link(setpoint)?,
+                        process_variable: self.expr_to_typed_link(process_variable)?,
+                        active: self.expr_to_typed_link(active)?,
+                        kp: self.prop(kp)?,
+                        ki: self.prop(ki)?,
+                        kd: self.prop(kd)?,
+                    }),
+                    0,
+                )
             }
-            _ => todo!(),
+            "pid" => {
+                let (
+                    [],
+                    [
+                        setpoint,
+                        process_variable,
+                        proportional,
+                        integral,
+                        derivative,
+                        active,
+                    ],
+                ) = self.bind(&PID, props, args, span)?;
+                (
+                    Component::Control(ControlComponent::Pid {
+                        setpoint: self.expr_to_typed_link(setpoint)?,
+                        process_variable: self.expr_to_typed_link(process_variable)?,
+                        proportional: self.expr_to_typed_link(proportional)?,
+                        integral: self.expr_to_typed_link(integral)?,
+                        derivative: self.expr_to_typed_link(derivative)?,
+                        active: self.expr_to_typed_link(active)?,
+                    }),
+                    0,
+                )
+            }
+            _ => return Some(Err(self.functions.unknown(func_type))),
         };
         Some(self.add_component(component, index))
     }
 
-    fn result_to_option<T>(&mut self, val: Result<T, CompileErrorType>, span: &Span) -> Option<T> {
-        match val {
-            Ok(v) => Some(v),
-            Err(err) => {
-                self.push_error(span.clone(), err);
-                None
-            }
-        }
-    }
-
-    #[expect(dead_code)]
-    fn props_tuple1<'a>(
+    // 引数を変数として、本体を呼び出した位置に展開する
+    fn user_function(
         &mut self,
-        props: &'a Option<Spanned<Vec<Spanned<Expr>>>>,
-        expect_str: &'static str,
+        function: &'t Function,
+        props: &Props,
+        args: &Args,
         span: &Span,
-    ) -> Option<(&'a Spanned<Expr>,)> {
-        match props {
-            Some(props) => self.result_to_option(to_tuple1(&props.inner, expect_str), &props.span),
-            None => {
-                self.result_to_option(Err(CompileErrorType::PropertyRequired { expect_str }), span)
+    ) -> Option<Result<Link, CompileErrorType>> {
+        if self.calls.contains(&function.name.as_str()) {
+            return Some(Err(CompileErrorType::RecursiveFunction {
+                name: function.name.clone(),
+            }));
+        }
+        let (prop_values, arg_values) = match FunctionSignature(function).bind(props, args, span) {
+            Ok(bound) => bound,
+            Err(errors) => {
+                for (span, err) in errors {
+                    self.push_error(span, err);
+                }
+                return None;
             }
+        };
+
+        // 値は呼び出し側のスコープで求める (エラーはすべて報告する)
+        let prop_values = prop_values
+            .into_iter()
+            .map(|v| match evaluate_expr(v, &self.context, self.filename) {
+                Ok(value) => Some(ConstantValue::from(value)),
+                Err(err) => {
+                    self.errors.push(err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let arg_values = arg_values
+            .into_iter()
+            .map(|v| self.expr_to_components(v))
+            .collect::<Vec<_>>();
+        let prop_values = prop_values.into_iter().collect::<Option<Vec<_>>>()?;
+        let arg_values = arg_values.into_iter().collect::<Option<Vec<_>>>()?;
+
+        self.context.push_isolated_scope();
+        for (name, value) in function.props.iter().zip(prop_values) {
+            self.context.define_constant(name.inner.clone(), value);
+        }
+        for (name, link) in function.params.iter().zip(arg_values) {
+            self.context
+                .define_variable(name.inner.clone(), Variable::Link(link));
         }
+        self.calls.push(&function.name);
+        let ret = self.expr_to_components(&function.body);
+        self.calls.pop();
+        self.context.pop_scope().unwrap();
+        ret.map(Ok)
     }
 
-    fn props_tuple2<'a>(
+    pub(super) fn result_to_option<T>(
         &mut self,
-        props: &'a Option<Spanned<Vec<Spanned<Expr>>>>,
-        expect_str: &'static str,
+        val: Result<T, CompileErrorType>,
         span: &Span,
-    ) -> Option<(&'a Spanned<Expr>, &'a Spanned<Expr>)> {
-        match props {
-            Some(props) => self.result_to_option(to_tuple2(&props.inner, expect_str), &props.span),
-            None => {
-                self.result_to_option(Err(CompileErrorType::PropertyRequired { expect_str }), span)
+    ) -> Option<T> {
+        match val {
+            Ok(v) => Some(v),
+            Err(err) => {
+                self.push_error(span.clone(), err);
+                None
             }
         }
     }
 
-    fn args_tuple1<T1>(
+    fn bind<'a, const P: usize, const A: usize>(
         &mut self,
-        args: &Spanned<Vec<Spanned<Expr>>>,
-        expect_str: &'static str,
-    ) -> Option<(T1,)>
-    where
-        T1: TryFrom<Link>,
-        <T1 as TryFrom<Link>>::Error: Into<CompileErrorType>,
-    {
-        if let Some((a,)) = self.result_to_option(to_tuple1(&args.inner, expect_str), &args.span) {
-            let a = self.expr_to_typed_link(a)?;
-            Some((a,))
-        } else {
-            None
+        signature: &Signature<P, A>,
+        props: &'a Props,
+        args: &'a Args,
+        span: &Span,
+    ) -> Option<Bound<'a, P, A>> {
+        match signature.bind(props, args, span) {
+            Ok(bound) => Some(bound),
+            Err(errors) => {
+                for (span, err) in errors {
+                    self.push_error(span, err);
+                }
+                None
+            }
         }
     }
 
-    #[expect(dead_code)]
-    fn args_tuple2<T1, T2>(
-        &mut self,
-        args: &Spanned<Vec<Spanned<Expr>>>,
-        expect_str: &'static str,
-    ) -> Option<(T1, T2)>
-    where
-        T1: TryFrom<Link>,
-        <T1 as TryFrom<Link>>::Error: Into<CompileErrorType>,
-        T2: TryFrom<Link>,
-        <T2 as TryFrom<Link>>::Error: Into<CompileErrorType>,
-    {
-        if let Some((a, b)) = self.result_to_option(to_tuple2(args, expect_str), &args.span) {
-            let a = self.expr_to_typed_link(a)?;
-            let b = self.expr_to_typed_link(b)?;
-            Some((a, b))
-        } else {
-            None
+    fn prop(&mut self, value: PropValue) -> Option<f32> {
+        match value.evaluate(&self.context, self.filename) {
+            Ok(v) => Some(v),
+            Err(err) => {
+                self.errors.push(err);
+                None
+            }
         }
     }
 }
-
-fn to_tuple1<'a, T>(arr: &'a [T], expect_str: &'static str) -> Result<(&'a T,), CompileErrorType> {
-    match arr {
-        [a] => Ok((a,)),
-        _ => Err(CompileErrorType::LengthMismatch {
-            expect_str,
-            found_len: arr.len(),
-        }),
-    }
-}
-
-fn to_tuple2<'a, T>(
-    arr: &'a [T],
-    expect_str: &'static str,
-) -> Result<(&'a T, &'a T), CompileErrorType> {
-    match arr {
-        [a, b] => Ok((a, b)),
-        _ => Err(CompileErrorType::LengthMismatch {
-            expect_str,
-            found_len: arr.len(),
-        }),
-    }
-}
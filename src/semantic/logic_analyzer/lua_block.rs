@@ -0,0 +1,199 @@
+use super::{LogicAnalyzer, Span};
+use crate::{
+    compile_error::{CompileError, CompileErrorType},
+    microcontroller::{Link, NodeType},
+    semantic::{
+        FieldAnalyzer, ValueType, evaluate_expr,
+        lua_script::{CHANNEL_LIMIT, ScriptPorts, check_channels, check_length},
+    },
+    syntax::{Assignment, LuaElement, LuaPort, Spanned},
+};
+
+use std::{collections::HashSet, path::Path};
+
+impl<'f, 'e, 't> LogicAnalyzer<'f, 'e, 't> {
+    // lua { inputs {...} outputs {...} script = "..." }
+    // 出力の名前と Link を宣言順に返す
+    pub(super) fn lua_block(
+        &mut self,
+        args: &Option<Spanned<Vec<Spanned<Assignment>>>>,
+        elements: &[Spanned<LuaElement>],
+        span: &Span,
+    ) -> Option<Vec<(String, Link)>> {
+        let errors = self.errors.len();
+
+        let mut inputs = None;
+        let mut outputs = None;
+        let mut script = None;
+        let mut file = None;
+        let filename = self.filename;
+        let context = &self.context;
+
+        let mut fields = FieldAnalyzer::new(filename);
+        for assignment in args.iter().flat_map(|args| &args.inner) {
+            let r = fields.assignment(assignment, |ident, expr| {
+                match ident.as_str() {
+                    "file" => {
+                        let path: String = evaluate_expr(expr, context, filename)?.try_into()?;
+                        file = Some((path, expr.span.clone()));
+                    }
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            });
+            if let Err(err) = r {
+                self.errors.push(err);
+            }
+        }
+        for element in elements {
+            let r = match &element.inner {
+                LuaElement::Inputs(ports) => set_once(&mut inputs, ports, element, filename),
+                LuaElement::Outputs(ports) => set_once(&mut outputs, ports, element, filename),
+                LuaElement::Field(assignment) => fields.assignment(assignment, |ident, expr| {
+                    match ident.as_str() {
+                        "script" => {
+                            let text: String =
+                                evaluate_expr(expr, context, filename)?.try_into()?;
+                            script = Some((text, expr.span.clone()));
+                        }
+                        _ => return Ok(false),
+                    }
+                    Ok(true)
+                }),
+            };
+            if let Err(err) = r {
+                self.errors.push(err);
+            }
+        }
+
+        let (script, source_span) = match (script, file) {
+            (Some(script), None) => script,
+            (None, Some((path, path_span))) => {
+                // パスはソースファイルからの相対パス
+                let full_path = Path::new(filename)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .join(&path);
+                self.scripts.insert(full_path.clone());
+                match std::fs::read_to_string(full_path) {
+                    Ok(text) => (text, path_span),
+                    Err(err) => {
+                        self.push_error(
+                            path_span,
+                            CompileErrorType::LuaFileNotReadable {
+                                path,
+                                message: err.to_string(),
+                            },
+                        );
+                        return None;
+                    }
+                }
+            }
+            _ => {
+                self.push_error(span.clone(), CompileErrorType::InvalidLuaSource);
+                return None;
+            }
+        };
+
+        // 入力の値
+        let mut ports = ScriptPorts::default();
+        for port in inputs.unwrap_or_default() {
+            let Some(node_type) = self.port_type(port) else {
+                continue;
+            };
+            let value = port.value.as_ref().unwrap();
+            let Some(link) = self.expr_to_components(value) else {
+                continue;
+            };
+            let r = match node_type {
+                NodeType::Number => link.try_into().map(|l| ports.number_inputs.push(l)),
+                _ => link.try_into().map(|l| ports.bool_inputs.push(l)),
+            };
+            if let Err(err) = r {
+                self.push_error(value.span.clone(), err);
+            }
+        }
+
+        // 出力のチャンネル
+        let mut names = HashSet::new();
+        let mut output_names = Vec::new();
+        let (mut numbers, mut bools) = (0, 0);
+        for port in outputs.unwrap_or_default() {
+            let Some(node_type) = self.port_type(port) else {
+                continue;
+            };
+            if !names.insert(port.name.clone()) {
+                self.push_error(port.span.clone(), CompileErrorType::FieldAlreadyDeclared);
+                continue;
+            }
+            let counter = match node_type {
+                NodeType::Number => &mut numbers,
+                _ => &mut bools,
+            };
+            *counter += 1;
+            ports.outputs.push((node_type, *counter));
+            output_names.push(port.name.clone());
+        }
+
+        let counts = [
+            (NodeType::Number, ports.number_inputs.len()),
+            (NodeType::Bool, ports.bool_inputs.len()),
+            (NodeType::Number, numbers),
+            (NodeType::Bool, bools),
+        ];
+        for (node_type, count) in counts {
+            if count > CHANNEL_LIMIT {
+                self.push_error(
+                    span.clone(),
+                    CompileErrorType::TooManyChannels {
+                        node_type,
+                        limit: CHANNEL_LIMIT,
+                    },
+                );
+            }
+        }
+        if let Err(err) = check_length(&script) {
+            self.push_error(source_span.clone(), err);
+        }
+        let declared = [
+            ("input.getNumber", ports.number_inputs.len(), false),
+            ("input.getBool", ports.bool_inputs.len(), false),
+            ("output.setNumber", numbers, true),
+            ("output.setBool", bools, true),
+        ];
+        for err in check_channels(&script, declared) {
+            self.push_error(source_span.clone(), err);
+        }
+
+        if self.errors[errors..].iter().any(|e| !e.is_warning()) {
+            return None;
+        }
+
+        let (components, links) = ports.into_components(script);
+        self.components.extend(components);
+        Some(output_names.into_iter().zip(links).collect())
+    }
+
+    fn port_type(&mut self, port: &Spanned<LuaPort>) -> Option<NodeType> {
+        let r = ValueType::node_type(&port.type_name);
+        self.result_to_option(r, &port.span)
+    }
+}
+
+// inputs {...} や outputs {...} は 1 回だけ書ける
+fn set_once<'a, 'f>(
+    slot: &mut Option<&'a [Spanned<LuaPort>]>,
+    ports: &'a [Spanned<LuaPort>],
+    element: &Spanned<LuaElement>,
+    filename: &'f str,
+) -> Result<(), CompileError<'f>> {
+    if slot.is_some() {
+        return Err(CompileError::new(
+            filename,
+            element.span.clone(),
+            CompileErrorType::ElementAlreadyDeclared,
+        ));
+    }
+    *slot = Some(ports);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use super::{LogicAnalyzer, Span};
+use crate::{
+    compile_error::CompileErrorType,
+    microcontroller::{ArithmeticComponent, Component, Link, NumberLink},
+    semantic::{evaluate_expr::evaluate_expr, match_arms::check_arms},
+    syntax::{Expr, MatchArm, Spanned},
+};
+
+impl<'f, 'e, 't> LogicAnalyzer<'f, 'e, 't> {
+    pub(super) fn match_expr(
+        &mut self,
+        value: &Spanned<Expr>,
+        arms: &[Spanned<MatchArm>],
+        span: &Span,
+    ) -> Option<Result<Link, CompileErrorType>> {
+        let arms = check_arms(arms, span, &self.context, self.filename, self.errors);
+        let input: NumberLink = self.expr_to_typed_link(value)?;
+        let arms = arms?;
+
+        // 値がすべて定数なら 1 つの関数にまとめる
+        let constants = arms
+            .cases
+            .iter()
+            .map(|(key, value)| Some((*key, self.constant(value)?)))
+            .collect::<Option<Vec<_>>>();
+        if let (Some(cases), Some(default)) = (constants, self.constant(arms.default)) {
+            let n = NumberLink::default;
+            return Some(self.add_component(
+                Component::Arithmetic(ArithmeticComponent::Function8 {
+                    input_x: input,
+                    input_y: n(),
+                    input_z: n(),
+                    input_w: n(),
+                    input_a: n(),
+                    input_b: n(),
+                    input_c: n(),
+                    input_d: n(),
+                    function: lookup_function(&cases, default),
+                }),
+                0,
+            ));
+        }
+
+        let values = arms
+            .cases
+            .iter()
+            .map(|(key, value)| Some((*key, self.expr_to_typed_link(value)?)))
+            .collect::<Vec<_>>();
+        let default = self.expr_to_typed_link(arms.default);
+        let cases = values.into_iter().collect::<Option<Vec<_>>>()?;
+        let default: NumberLink = default?;
+        Some(self.switch_tree(&input, &cases, &default).map(Link::Number))
+    }
+
+    // 昇順の分岐を閾値で半分ずつに分け、最後は Equal で選ぶ
+    fn switch_tree(
+        &mut self,
+        input: &NumberLink,
+        cases: &[(f32, NumberLink)],
+        default: &NumberLink,
+    ) -> Result<NumberLink, CompileErrorType> {
+        let (switch, on_value, off_value) = match cases {
+            [] => return Ok(default.clone()),
+            [(key, value)] => {
+                let key = self.constant_link(*key)?;
+                let switch = self.add_component(
+                    Component::Arithmetic(ArithmeticComponent::Equal {
+                        input_a: input.clone(),
+                        input_b: key,
+                        epsilon: 0.0,
+                    }),
+                    0,
+                )?;
+                (switch, value.clone(), default.clone())
+            }
+            _ => {
+                let mid = cases.len() / 2;
+                let threshold = self.constant_link((cases[mid - 1].0 + cases[mid].0) / 2.0)?;
+                let switch = self.add_component(
+                    Component::Arithmetic(ArithmeticComponent::LessThan {
+                        input_a: input.clone(),
+                        input_b: threshold,
+                    }),
+                    0,
+                )?;
+                let lower = self.switch_tree(input, &cases[..mid], default)?;
+                let upper = self.switch_tree(input, &cases[mid..], default)?;
+                (switch, lower, upper)
+            }
+        };
+        self.add_component(
+            Component::Arithmetic(ArithmeticComponent::NumericalSwitchbox {
+                on_value,
+                off_value,
+                switch: switch.try_into()?,
+            }),
+            0,
+        )?
+        .try_into()
+    }
+
+    fn constant_link(&mut self, value: f32) -> Result<NumberLink, CompileErrorType> {
+        self.add_component(
+            Component::Arithmetic(ArithmeticComponent::ConstantNumber { value }),
+            0,
+        )?
+        .try_into()
+    }
+
+    // コンパイル時に求まる数値 (求まらなければ None でエラーにはしない)
+    fn constant(&self, expr: &Spanned<Expr>) -> Option<f32> {
+        evaluate_expr(expr, &self.context, self.filename)
+            .and_then(f32::try_from)
+            .ok()
+    }
+}
+
+// x がパターンの値と等しいときだけ 1 になる 1-sgn(abs(x-k)) を重みにして足し合わせる
+fn lookup_function(cases: &[(f32, f32)], default: f32) -> String {
+    let number = |v: f32| {
+        if v < 0.0 {
+            format!("({:?})", v)
+        } else {
+            format!("{:?}", v)
+        }
+    };
+    let differs = |key: f32| format!("sgn(abs(x-{}))", number(key));
+
+    let mut terms = cases
+        .iter()
+        .filter(|(_, value)| *value != 0.0)
+        .map(|(key, value)| format!("{}*(1-{})", number(*value), differs(*key)))
+        .collect::<Vec<_>>();
+    if default != 0.0 {
+        let factors = std::iter::once(number(default))
+            .chain(cases.iter().map(|(key, _)| differs(*key)))
+            .collect::<Vec<_>>();
+        terms.push(factors.join("*"));
+    }
+    if terms.is_empty() {
+        "0".to_owned()
+    } else {
+        terms.join("+")
+    }
+}
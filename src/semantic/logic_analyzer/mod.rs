@@ -1,128 +1,87 @@
+mod attributes;
 mod functions;
+mod lua_block;
+mod match_expr;
 mod operators;
+mod value_info;
 use operators::{binary_operation, unary_operation};
+use value_info::ValueInfoMap;
 
-use super::evaluate_expr::{EvaluatedValue, evaluate_expr};
+use super::{
+    context::Context,
+    evaluate_expr::{ConstantValue, Constants, EvaluatedValue, evaluate_expr},
+    for_loop::{iterations, note_iteration},
+    function_table::{FunctionTable, assigns_outputs, reads_inputs},
+    has_errors,
+    interface::Annotations,
+};
 use crate::{
     compile_error::{CompileError, CompileErrorType},
-    microcontroller::{ArithmeticComponent, Component, InputNode, Link, OutputNode},
-    syntax::{AssignmentTarget, Expr, Spanned, Statement},
+    microcontroller::{ArithmeticComponent, Component, LayoutHint, Link},
+    syntax::{AssignmentTarget, Expr, ForLoop, Spanned, Statement},
 };
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::PathBuf,
+    rc::Rc,
+};
 
 type Span = std::ops::Range<usize>;
-type Inputs = HashMap<String, Rc<InputNode>>;
-type Outputs = HashMap<String, Rc<RefCell<OutputNode>>>;
-
-#[derive(Debug)]
-struct ContextRoot {
-    inputs: Inputs,
-    outputs: Outputs,
-}
 
-#[derive(Default, Debug)]
-struct ContextScope {
-    variables: HashMap<String, Link>,
+// 変数の値
+#[derive(Clone, Debug)]
+pub(super) enum Variable {
+    Link(Link),
+    // 出力が複数ある lua ブロック (name.output で使う)
+    Record(Rc<HashMap<String, Link>>),
 }
 
 #[derive(Debug)]
-pub(super) struct Context {
-    root: Option<ContextRoot>,
-    stack: Vec<ContextScope>,
-}
-
-impl Context {
-    pub(super) fn new(inputs: Inputs, outputs: Outputs) -> Self {
-        Self {
-            root: Some(ContextRoot { inputs, outputs }),
-            stack: Vec::new(),
-        }
-    }
-
-    fn get_variable(&self, ident: &str) -> Option<Link> {
-        for scope in self.stack.iter().rev() {
-            if let Some(v) = scope.variables.get(ident) {
-                return Some(v.clone());
-            }
-        }
-        None
-    }
-
-    fn define_variable(&mut self, ident: String, link: Link) {
-        if let Some(scope) = self.stack.last_mut() {
-            scope.variables.insert(ident, link);
-        } else {
-            self.stack.push(ContextScope {
-                variables: HashMap::from([(ident, link)]),
-            });
-        }
-    }
-
-    fn get_variable_err(&self, ident: &str) -> Result<Link, CompileErrorType> {
-        self.get_variable(ident)
-            .ok_or(CompileErrorType::UnknownName {
-                name: ident.to_owned(),
-            })
-    }
-
-    fn get_input(&self, name: &str) -> Result<&Rc<InputNode>, CompileErrorType> {
-        if let Some(root) = &self.root {
-            root.inputs.get(name).ok_or(CompileErrorType::UnknownField {
-                ident: name.to_owned(),
-            })
-        } else {
-            todo!()
-            //Err(CompileErrorType::InputsAreNotAvailable)
-        }
-    }
-
-    fn get_output(&self, name: &str) -> Result<&Rc<RefCell<OutputNode>>, CompileErrorType> {
-        if let Some(root) = &self.root {
-            root.outputs
-                .get(name)
-                .ok_or(CompileErrorType::UnknownField {
-                    ident: name.to_owned(),
-                })
-        } else {
-            todo!()
-            //Err(CompileErrorType::OutputsAreNotAvailable)
-        }
-    }
-
-    fn push_scope(&mut self) {
-        self.stack.push(ContextScope::default());
-    }
-
-    fn pop_scope(&mut self) -> Option<ContextScope> {
-        self.stack.pop()
-    }
-}
-
-#[derive(Debug)]
-pub(super) struct LogicAnalyzer<'f, 'e> {
-    context: Context,
+pub(super) struct LogicAnalyzer<'f, 'e, 't> {
+    context: Context<Variable>,
     components: Vec<Rc<Component>>,
+    // components の添字 -> レイアウトの指示
+    hints: BTreeMap<usize, LayoutHint>,
+    value_info: ValueInfoMap,
+    functions: FunctionTable<'t>,
+    // 展開中の関数 (再帰の検出に使う)
+    calls: Vec<&'t str>,
+    // lua(file = ...) で読み込んだファイル (読めなかったものも含む)
+    scripts: BTreeSet<PathBuf>,
     filename: &'f str,
     errors: &'e mut Vec<CompileError<'f>>,
 }
 
-impl<'f, 'e> LogicAnalyzer<'f, 'e> {
+impl<'f, 'e, 't> LogicAnalyzer<'f, 'e, 't> {
     pub(super) fn new(
-        context: Context,
+        context: Context<Variable>,
+        annotations: Annotations,
+        functions: FunctionTable<'t>,
         filename: &'f str,
         errors: &'e mut Vec<CompileError<'f>>,
     ) -> Self {
         Self {
+            value_info: ValueInfoMap::new(annotations, &context),
             context,
             components: Vec::new(),
+            hints: BTreeMap::new(),
+            functions,
+            calls: Vec::new(),
+            scripts: BTreeSet::new(),
             filename,
             errors,
         }
     }
 
-    pub(super) fn into_components(self) -> Vec<Rc<Component>> {
-        self.components
+    pub(super) fn into_components(
+        self,
+    ) -> (
+        Vec<Rc<Component>>,
+        BTreeMap<usize, LayoutHint>,
+        BTreeSet<PathBuf>,
+    ) {
+        (self.components, self.hints, self.scripts)
     }
 
     pub(super) fn statement(&mut self, statement: &Spanned<Statement>) {
@@ -131,15 +90,58 @@ impl<'f, 'e> LogicAnalyzer<'f, 'e> {
                 let target = &assignment.inner.target;
                 let value = &assignment.inner.value;
 
+                if !self.calls.is_empty() && assigns_outputs(&target.inner) {
+                    self.push_error(
+                        assignment.span.clone(),
+                        CompileErrorType::InterfaceInFunction {
+                            interface: "outputs",
+                        },
+                    );
+                    return;
+                }
                 if let Some(link) = self.expr_to_components(value) {
                     self.assign_link(target, link, &value.span);
                 }
             }
             Statement::Let(ident, value) => {
-                if let Some(link) = self.expr_to_components(value) {
-                    self.context.define_variable(ident.clone(), link);
+                let variable = if let Expr::Lua { args, elements } = &value.inner {
+                    self.lua_block(args, elements, &value.span)
+                        .map(|outputs| Variable::Record(Rc::new(outputs.into_iter().collect())))
+                } else {
+                    self.expr_to_components(value).map(Variable::Link)
+                };
+                if let Some(variable) = variable {
+                    self.context.define_variable(ident.clone(), variable);
                 }
             }
+            Statement::For(for_loop) => self.for_loop(for_loop),
+        }
+    }
+
+    // 本体を回数分展開する (回ごとのスコープでループ変数は定数)
+    // エラーが出たら残りは展開しない (同じエラーが回数分並ぶので)
+    fn for_loop(&mut self, for_loop: &ForLoop<Statement>) {
+        let range = match iterations(for_loop, &self.context, self.filename) {
+            Ok(range) => range,
+            Err(err) => {
+                self.errors.push(err);
+                return;
+            }
+        };
+        let variable = &for_loop.variable.inner;
+        for i in range {
+            let errors = self.errors.len();
+            self.context.push_scope();
+            self.context
+                .define_constant(variable.clone(), ConstantValue::Int(i));
+            for statement in &for_loop.body {
+                self.statement(statement);
+            }
+            self.context.pop_scope().unwrap();
+            note_iteration(&mut self.errors[errors..], variable, i);
+            if has_errors(&self.errors[errors..]) {
+                break;
+            }
         }
     }
 
@@ -156,14 +158,66 @@ impl<'f, 'e> LogicAnalyzer<'f, 'e> {
                 0,
             ),
             Expr::StringLiteral(_) => Err(CompileErrorType::StringInLogic),
-            Expr::Ident(ident) => self.context.get_variable_err(ident),
+            // 定数は Constant Number にする
+            Expr::Ident(ident) if self.context.get_constant(ident).is_some() => {
+                let value = self.evaluate_expr(expr)?;
+                self.add_component(
+                    Component::Arithmetic(ArithmeticComponent::ConstantNumber { value }),
+                    0,
+                )
+            }
+            Expr::Ident(ident) => match self.context.get_variable_err(ident) {
+                Ok(Variable::Link(link)) => Ok(link),
+                Ok(Variable::Record(_)) => Err(CompileErrorType::FieldAccessOnly),
+                Err(err) => Err(err),
+            },
+            Expr::Inputs | Expr::MemberAccess(..) | Expr::Index(..)
+                if !self.calls.is_empty() && reads_inputs(&expr.inner) =>
+            {
+                Err(CompileErrorType::InterfaceInFunction {
+                    interface: "inputs",
+                })
+            }
             Expr::Inputs => Err(CompileErrorType::FieldAccessOnly),
             Expr::Outputs => Err(CompileErrorType::OutputsInExpression),
             Expr::Tuple(_) => todo!(),
             Expr::MemberAccess(object, field) => match &object.inner {
                 Expr::Inputs => self.context.get_input(field).map(Link::node),
+                Expr::Ident(ident) => match self.context.get_variable_err(ident) {
+                    Ok(Variable::Record(outputs)) => {
+                        outputs
+                            .get(field)
+                            .cloned()
+                            .ok_or(CompileErrorType::UnknownField {
+                                ident: field.clone(),
+                            })
+                    }
+                    Ok(Variable::Link(_)) => Err(CompileErrorType::UnknownField {
+                        ident: field.clone(),
+                    }),
+                    Err(err) => Err(err),
+                },
+                Expr::Lua { args, elements } => {
+                    let outputs = self.lua_block(args, elements, &object.span)?;
+                    outputs
+                        .into_iter()
+                        .find(|(name, _)| name == field)
+                        .map(|(_, link)| link)
+                        .ok_or(CompileErrorType::UnknownField {
+                            ident: field.clone(),
+                        })
+                }
                 _ => todo!(),
             },
+            Expr::Index(object, index) => {
+                match self.context.input_element(object, index, self.filename) {
+                    Ok(name) => self.context.get_input(&name).map(Link::node),
+                    Err(err) => {
+                        self.errors.push(err);
+                        return None;
+                    }
+                }
+            }
             Expr::BinaryOp(op) => binary_operation(self, op)?,
             Expr::UnaryOp(op) => unary_operation(self, op)?,
             Expr::Block {
@@ -180,9 +234,44 @@ impl<'f, 'e> LogicAnalyzer<'f, 'e> {
                 self.context.pop_scope().unwrap();
                 return ret;
             }
+            // 条件で数値を切り替える
+            Expr::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let switch = self.expr_to_typed_link(condition);
+                let on_value = self.expr_to_typed_link(then);
+                let off_value = self.expr_to_typed_link(otherwise);
+                let (switch, on_value, off_value) = (switch?, on_value?, off_value?);
+                if let Err(err) = self.value_info.check_sum(&on_value, &off_value) {
+                    self.push_error(then.span.start..otherwise.span.end, err);
+                }
+                self.add_component(
+                    Component::Arithmetic(ArithmeticComponent::NumericalSwitchbox {
+                        on_value,
+                        off_value,
+                        switch,
+                    }),
+                    0,
+                )
+            }
+            Expr::Match { value, arms } => self.match_expr(value, arms, &expr.span)?,
             Expr::FunctionCall { ident, props, args } => {
                 self.function_call(ident, props, args, &expr.span)?
             }
+            Expr::Annotated { attrs, expr } => return self.annotated(attrs, expr),
+            // 出力が 1 つなら、そのまま値として使える
+            Expr::Lua { args, elements } => {
+                let mut outputs = self.lua_block(args, elements, &expr.span)?;
+                if outputs.len() == 1 {
+                    Ok(outputs.pop().unwrap().1)
+                } else {
+                    Err(CompileErrorType::LuaBlockOutputs {
+                        count: outputs.len(),
+                    })
+                }
+            }
         };
 
         match r {
@@ -217,7 +306,7 @@ impl<'f, 'e> LogicAnalyzer<'f, 'e> {
         T: TryFrom<EvaluatedValue<'f>>,
         <T as TryFrom<EvaluatedValue<'f>>>::Error: Into<CompileError<'f>>,
     {
-        match evaluate_expr(expr, self.filename)
+        match evaluate_expr(expr, &self.context, self.filename)
             .and_then(|v| T::try_from(v).map_err(|err| err.into()))
         {
             Ok(v) => Some(v),
@@ -235,6 +324,7 @@ impl<'f, 'e> LogicAnalyzer<'f, 'e> {
     ) -> Result<Link, CompileErrorType> {
         let rc = Rc::new(component);
         if let Some(link) = Link::component(&rc, index) {
+            self.value_info.add_component(&rc);
             self.components.push(rc);
             Ok(link)
         } else {
@@ -246,36 +336,34 @@ impl<'f, 'e> LogicAnalyzer<'f, 'e> {
     }
 
     fn assign_link(&mut self, target: &Spanned<AssignmentTarget>, link: Link, value_span: &Span) {
-        let r = match &target.inner {
-            AssignmentTarget::Ident(_) => todo!(),
-            AssignmentTarget::Inputs => todo!(),
-            AssignmentTarget::Outputs => todo!(),
-            AssignmentTarget::FieldAccess(object, field) => match &object.inner {
-                AssignmentTarget::Ident(_) => todo!(),
-                AssignmentTarget::Inputs => todo!(),
-                AssignmentTarget::Outputs => self
-                    .context
-                    .get_output(field)
-                    .map_err(|err| (&target.span, err))
-                    .and_then(|o| {
-                        let expected_type = o.borrow().node_type();
-                        let found_type = link.node_type();
-                        let success = o.borrow_mut().set_input_link(link);
-                        if success {
-                            Ok(())
-                        } else {
-                            Err((
-                                value_span,
-                                CompileErrorType::IncompatibleNodeType {
-                                    expected_type,
-                                    found_type,
-                                },
-                            ))
-                        }
-                    }),
-                AssignmentTarget::FieldAccess(_, _) => todo!(),
-            },
+        let name = match self.context.output_name(target, self.filename) {
+            Ok(name) => name,
+            Err(err) => {
+                self.errors.push(err);
+                return;
+            }
         };
+        let link = self.check_output(&name, link, value_span);
+        let r = self
+            .context
+            .get_output(&name)
+            .map_err(|err| (&target.span, err))
+            .and_then(|o| {
+                let expected_type = o.borrow().node_type();
+                let found_type = link.node_type();
+                let success = o.borrow_mut().set_input_link(link);
+                if success {
+                    Ok(())
+                } else {
+                    Err((
+                        value_span,
+                        CompileErrorType::IncompatibleNodeType {
+                            expected_type,
+                            found_type,
+                        },
+                    ))
+                }
+            });
 
         if let Err((span, err)) = r {
             self.push_error(span.clone(), err);
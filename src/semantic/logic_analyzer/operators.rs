@@ -1,8 +1,8 @@
 use super::LogicAnalyzer;
 use crate::{
     compile_error::CompileErrorType,
-    microcontroller::{ArithmeticComponent, Component, Link},
-    syntax::{BinaryOp, UnaryOp},
+    microcontroller::{ArithmeticComponent, Component, Link, NumberLink},
+    syntax::{BinaryOp, Expr, Spanned, UnaryOp},
 };
 
 pub(super) fn binary_operation(
@@ -10,20 +10,18 @@ pub(super) fn binary_operation(
     op: &BinaryOp,
 ) -> Option<Result<Link, CompileErrorType>> {
     let (component, index) = match op {
-        BinaryOp::Add(lhs, rhs) => (
-            Component::Arithmetic(ArithmeticComponent::Add {
-                input_a: logic_analyzer.expr_to_typed_link(lhs)?,
-                input_b: logic_analyzer.expr_to_typed_link(rhs)?,
-            }),
-            0,
-        ),
-        BinaryOp::Sub(lhs, rhs) => (
-            Component::Arithmetic(ArithmeticComponent::Subtract {
-                input_a: logic_analyzer.expr_to_typed_link(lhs)?,
-                input_b: logic_analyzer.expr_to_typed_link(rhs)?,
-            }),
-            0,
-        ),
+        BinaryOp::Add(lhs, rhs) | BinaryOp::Sub(lhs, rhs) => {
+            let input_a = logic_analyzer.expr_to_typed_link(lhs)?;
+            let input_b = logic_analyzer.expr_to_typed_link(rhs)?;
+            if let Err(err) = logic_analyzer.value_info.check_sum(&input_a, &input_b) {
+                logic_analyzer.push_error(lhs.span.start..rhs.span.end, err);
+            }
+            let component = match op {
+                BinaryOp::Add(_, _) => ArithmeticComponent::Add { input_a, input_b },
+                _ => ArithmeticComponent::Subtract { input_a, input_b },
+            };
+            (Component::Arithmetic(component), 0)
+        }
         BinaryOp::Mul(lhs, rhs) => (
             Component::Arithmetic(ArithmeticComponent::Multiply {
                 input_a: logic_analyzer.expr_to_typed_link(lhs)?,
@@ -31,17 +29,75 @@ pub(super) fn binary_operation(
             }),
             0,
         ),
-        BinaryOp::Div(lhs, rhs) => (
-            Component::Arithmetic(ArithmeticComponent::Divide {
-                input_a: logic_analyzer.expr_to_typed_link(lhs)?,
-                input_b: logic_analyzer.expr_to_typed_link(rhs)?,
-            }),
-            0,
-        ),
+        BinaryOp::Div(lhs, rhs) => {
+            let input_a = logic_analyzer.expr_to_typed_link(lhs)?;
+            let input_b = logic_analyzer.expr_to_typed_link(rhs)?;
+            if let Err(err) = logic_analyzer.value_info.check_divisor(&input_b) {
+                logic_analyzer.push_error(rhs.span.clone(), err);
+            }
+            (
+                Component::Arithmetic(ArithmeticComponent::Divide { input_a, input_b }),
+                0,
+            )
+        }
+        BinaryOp::Mod(lhs, rhs) => {
+            let input_a = logic_analyzer.expr_to_typed_link(lhs)?;
+            let input_b = logic_analyzer.expr_to_typed_link(rhs)?;
+            if let Err(err) = logic_analyzer.value_info.check_modulus(&input_b) {
+                logic_analyzer.push_error(rhs.span.clone(), err);
+            }
+            (
+                Component::Arithmetic(ArithmeticComponent::Modulo { input_a, input_b }),
+                0,
+            )
+        }
+        BinaryOp::Lt(lhs, rhs) | BinaryOp::Gt(lhs, rhs) => {
+            let input_a = logic_analyzer.expr_to_typed_link(lhs)?;
+            let input_b = logic_analyzer.expr_to_typed_link(rhs)?;
+            if let Err(err) = logic_analyzer.value_info.check_sum(&input_a, &input_b) {
+                logic_analyzer.push_error(lhs.span.start..rhs.span.end, err);
+            }
+            let component = match op {
+                BinaryOp::Lt(_, _) => ArithmeticComponent::LessThan { input_a, input_b },
+                _ => ArithmeticComponent::GreaterThan { input_a, input_b },
+            };
+            (Component::Arithmetic(component), 0)
+        }
+        // 指数が定数なら式に埋め込む
+        BinaryOp::Pow(lhs, rhs) => match constant(rhs) {
+            Some(exponent) => (
+                Component::Arithmetic(ArithmeticComponent::Function1 {
+                    input_x: logic_analyzer.expr_to_typed_link(lhs)?,
+                    function: format!("pow(x, {})", exponent),
+                }),
+                0,
+            ),
+            None => (
+                Component::Arithmetic(ArithmeticComponent::Function3 {
+                    input_x: logic_analyzer.expr_to_typed_link(lhs)?,
+                    input_y: logic_analyzer.expr_to_typed_link(rhs)?,
+                    input_z: NumberLink::default(),
+                    function: "pow(x, y)".to_owned(),
+                }),
+                0,
+            ),
+        },
     };
     Some(logic_analyzer.add_component(component, index))
 }
 
+// 数値リテラル (符号付き) なら関数の式に書ける形で返す
+fn constant(expr: &Spanned<Expr>) -> Option<String> {
+    match &expr.inner {
+        Expr::IntLiteral(v) => Some(v.to_string()),
+        Expr::FloatLiteral(v) => Some(format!("{:?}", v)),
+        Expr::UnaryOp(UnaryOp::Neg(x)) if !matches!(x.inner, Expr::UnaryOp(_)) => {
+            constant(x).map(|v| format!("-{}", v))
+        }
+        _ => None,
+    }
+}
+
 pub(super) fn unary_operation(
     logic_analyzer: &mut LogicAnalyzer,
     op: &UnaryOp,
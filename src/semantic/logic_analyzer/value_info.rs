@@ -0,0 +1,255 @@
+use super::{LogicAnalyzer, Span};
+use crate::{
+    compile_error::CompileErrorType,
+    microcontroller::{
+        ArithmeticComponent, Component, ComponentData as _, Link, LinkNode, NumberLink,
+    },
+    semantic::{
+        context::Context,
+        interface::{Annotations, NodeAnnotation},
+        interval::Interval,
+        unit::Unit,
+    },
+};
+
+use std::{collections::HashMap, rc::Rc};
+
+// 数値の範囲と単位
+// None は不明 (何とでも組み合わせられる)
+#[derive(Clone, Default, Debug)]
+pub(super) struct ValueInfo {
+    interval: Option<Interval>,
+    unit: Option<Unit>,
+}
+
+impl ValueInfo {
+    fn constant(value: f32) -> Self {
+        Self {
+            interval: Some(Interval::point(value as f64)),
+            unit: Some(Unit::default()),
+        }
+    }
+
+    fn map(&self, f: impl FnOnce(Interval) -> Interval) -> Self {
+        Self {
+            interval: self.interval.map(f),
+            unit: self.unit.clone(),
+        }
+    }
+}
+
+impl From<&NodeAnnotation> for ValueInfo {
+    fn from(value: &NodeAnnotation) -> Self {
+        Self {
+            interval: value.range,
+            unit: value.unit.clone(),
+        }
+    }
+}
+
+// 無次元の値 (定数など) はどの単位とも足し引きできる
+fn sum_unit(a: &Option<Unit>, b: &Option<Unit>) -> Result<Option<Unit>, CompileErrorType> {
+    match (a, b) {
+        (Some(a), Some(b)) if !a.is_dimensionless() && !b.is_dimensionless() && a != b => {
+            Err(CompileErrorType::UnitMismatch {
+                lhs: a.to_string(),
+                rhs: b.to_string(),
+            })
+        }
+        (Some(a), Some(b)) if a.is_dimensionless() => Ok(Some(b.clone())),
+        (Some(a), _) => Ok(Some(a.clone())),
+        (None, _) => Ok(None),
+    }
+}
+
+// リンク元のコンポーネントまたはノードと出力の添字
+type LinkKey = (*const (), usize);
+
+fn link_key(link: &Option<LinkNode>) -> Option<LinkKey> {
+    match link.as_ref()? {
+        LinkNode::Node(node) => Some((node.as_ptr() as *const (), 0)),
+        LinkNode::Component(component, index) => Some((component.as_ptr() as *const (), *index)),
+    }
+}
+
+// リンクごとの範囲と単位
+#[derive(Default, Debug)]
+pub(super) struct ValueInfoMap {
+    values: HashMap<LinkKey, ValueInfo>,
+    // 出力ノード名 -> 宣言された範囲と単位
+    outputs: HashMap<String, NodeAnnotation>,
+}
+
+impl ValueInfoMap {
+    pub(super) fn new<V: Clone>(annotations: Annotations, context: &Context<V>) -> Self {
+        let mut values = HashMap::new();
+        for (name, annotation) in &annotations.inputs {
+            if let Ok(node) = context.get_input(name)
+                && let Some(key) = link_key(Link::node(node).link_node())
+            {
+                values.insert(key, ValueInfo::from(annotation));
+            }
+        }
+        Self {
+            values,
+            outputs: annotations.outputs,
+        }
+    }
+
+    fn get(&self, link: &Option<LinkNode>) -> ValueInfo {
+        link_key(link)
+            .and_then(|key| self.values.get(&key))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // コンポーネントの出力の範囲と単位を入力から求める
+    pub(super) fn add_component(&mut self, component: &Rc<Component>) {
+        let inputs = component.input_links_node();
+        let input = |i: usize| inputs.get(i).map(|l| self.get(l)).unwrap_or_default();
+
+        let Component::Arithmetic(c) = component.as_ref() else {
+            return;
+        };
+        let info = match c {
+            ArithmeticComponent::Add { .. } | ArithmeticComponent::Subtract { .. } => {
+                let (a, b) = (input(0), input(1));
+                let interval = a.interval.zip(b.interval).map(|(a, b)| match c {
+                    ArithmeticComponent::Add { .. } => a.add(b),
+                    _ => a.sub(b),
+                });
+                ValueInfo {
+                    interval,
+                    unit: sum_unit(&a.unit, &b.unit).unwrap_or_default(),
+                }
+            }
+            ArithmeticComponent::Multiply { .. } => {
+                let (a, b) = (input(0), input(1));
+                ValueInfo {
+                    interval: a.interval.zip(b.interval).map(|(a, b)| a.mul(b)),
+                    unit: a.unit.zip(b.unit).map(|(a, b)| a.mul(&b)),
+                }
+            }
+            ArithmeticComponent::Divide { .. } => {
+                let (a, b) = (input(0), input(1));
+                ValueInfo {
+                    interval: a.interval.zip(b.interval).and_then(|(a, b)| a.div(b)),
+                    unit: a.unit.zip(b.unit).map(|(a, b)| a.div(&b)),
+                }
+            }
+            ArithmeticComponent::Modulo { .. } => {
+                let (a, b) = (input(0), input(1));
+                ValueInfo {
+                    interval: a.interval.zip(b.interval).map(|(a, b)| a.modulo(b)),
+                    unit: a.unit,
+                }
+            }
+            ArithmeticComponent::Clamp { min, max, .. } => {
+                let info = input(0);
+                let (min, max) = (*min as f64, *max as f64);
+                ValueInfo {
+                    interval: Some(
+                        info.interval
+                            .map_or(Interval::new(min, max), |i| i.clamp(min, max)),
+                    ),
+                    unit: info.unit,
+                }
+            }
+            // どちらの値にもなりうる
+            ArithmeticComponent::NumericalSwitchbox { .. } => {
+                let (a, b) = (input(0), input(1));
+                ValueInfo {
+                    interval: a.interval.zip(b.interval).map(|(a, b)| a.union(b)),
+                    unit: sum_unit(&a.unit, &b.unit).unwrap_or_default(),
+                }
+            }
+            ArithmeticComponent::Abs { .. } => input(0).map(Interval::abs),
+            ArithmeticComponent::Function1 { function, .. } if function == "-x" => {
+                input(0).map(Interval::neg)
+            }
+            ArithmeticComponent::ConstantNumber { value } => ValueInfo::constant(*value),
+            _ => return,
+        };
+        if let Some(key) = link_key(&Some(LinkNode::Component(Rc::downgrade(component), 0))) {
+            self.values.insert(key, info);
+        }
+    }
+
+    // 除数が定数 0 なら結果は常に NaN になる
+    pub(super) fn check_modulus(&self, divisor: &NumberLink) -> Result<(), CompileErrorType> {
+        match self.get(divisor).interval {
+            Some(interval) if interval == Interval::point(0.0) => {
+                Err(CompileErrorType::ModuloByZero)
+            }
+            _ => self.check_divisor(divisor),
+        }
+    }
+
+    pub(super) fn check_sum(&self, a: &NumberLink, b: &NumberLink) -> Result<(), CompileErrorType> {
+        sum_unit(&self.get(a).unit, &self.get(b).unit).map(|_| ())
+    }
+
+    // 除数が 0 を含む範囲にあるなら警告
+    pub(super) fn check_divisor(&self, divisor: &NumberLink) -> Result<(), CompileErrorType> {
+        match self.get(divisor).interval {
+            Some(interval) if interval.contains_zero() => {
+                Err(CompileErrorType::PossibleDivisionByZero {
+                    divisor: interval.to_string(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'f, 'e, 't> LogicAnalyzer<'f, 'e, 't> {
+    // 出力ノードの範囲と単位を確かめ、必要なら Clamp を挟む
+    pub(super) fn check_output(&mut self, name: &str, link: Link, span: &Span) -> Link {
+        let Link::Number(number) = &link else {
+            return link;
+        };
+        let Some(annotation) = self.value_info.outputs.get(name).cloned() else {
+            return link;
+        };
+        let info = self.value_info.get(number);
+
+        if let (Some(expected), Some(found)) = (&annotation.unit, &info.unit)
+            && !found.is_dimensionless()
+            && expected != found
+        {
+            self.push_error(
+                span.clone(),
+                CompileErrorType::IncompatibleUnit {
+                    expected: expected.to_string(),
+                    found: found.to_string(),
+                },
+            );
+        }
+
+        let Some(range) = annotation.range else {
+            return link;
+        };
+        if info.interval.is_some_and(|i| i.is_within(&range)) {
+            return link;
+        }
+        if annotation.clamp {
+            let clamp = Component::Arithmetic(ArithmeticComponent::Clamp {
+                input: number.clone(),
+                min: range.min as f32,
+                max: range.max as f32,
+            });
+            return self.add_component(clamp, 0).unwrap_or(link);
+        }
+        if let Some(interval) = info.interval {
+            self.push_error(
+                span.clone(),
+                CompileErrorType::OutputOutOfRange {
+                    name: name.to_owned(),
+                    range: range.to_string(),
+                    found: interval.to_string(),
+                },
+            );
+        }
+        link
+    }
+}
@@ -0,0 +1,54 @@
+use super::LuaGenerator;
+use crate::{
+    microcontroller::{Component, Link, NodeType},
+    semantic::lua_script::ScriptPorts,
+};
+
+use std::rc::Rc;
+
+impl<'f, 'e, 't> LuaGenerator<'f, 'e, 't> {
+    // 代入された出力ノードだけをスクリプトから読み出してつなぐ
+    pub(in crate::semantic) fn into_components(mut self) -> Vec<Rc<Component>> {
+        let script = self.script();
+        let channels = self.channels;
+
+        let number_outputs = channels
+            .number_outputs
+            .iter()
+            .zip(&self.assigned_numbers)
+            .enumerate()
+            .map(|(i, (o, a))| (NodeType::Number, i + 1, o, *a));
+        let bool_outputs = channels
+            .bool_outputs
+            .iter()
+            .zip(&self.assigned_bools)
+            .enumerate()
+            .map(|(i, (o, a))| (NodeType::Bool, i + 1, o, *a));
+        let outputs: Vec<_> = number_outputs
+            .chain(bool_outputs)
+            .filter(|(_, _, _, assigned)| *assigned)
+            .collect();
+
+        let ports = ScriptPorts {
+            number_inputs: channels
+                .number_inputs
+                .iter()
+                .map(|n| Link::node(n).try_into().unwrap())
+                .collect(),
+            bool_inputs: channels
+                .bool_inputs
+                .iter()
+                .map(|n| Link::node(n).try_into().unwrap())
+                .collect(),
+            outputs: outputs
+                .iter()
+                .map(|(node_type, channel, _, _)| (*node_type, *channel))
+                .collect(),
+        };
+        let (components, links) = ports.into_components(script);
+        for ((_, _, output, _), link) in outputs.into_iter().zip(links) {
+            output.borrow_mut().set_input_link(link);
+        }
+        components
+    }
+}
@@ -0,0 +1,407 @@
+use super::{LuaGenerator, LuaValue, Precedence, Span};
+use crate::{
+    compile_error::CompileErrorType,
+    microcontroller::NodeType,
+    semantic::{
+        evaluate_expr::{ConstantValue, Constants, evaluate_expr},
+        function_table::reads_inputs,
+        match_arms::check_arms,
+        signature::{ABS, CLAMP, FunctionSignature},
+    },
+    syntax::{Argument, BinaryOp, Expr, Function, MatchArm, Spanned, Statement, UnaryOp},
+};
+
+impl<'f, 'e, 't> LuaGenerator<'f, 'e, 't> {
+    pub(super) fn expr_to_lua(&mut self, expr: &Spanned<Expr>) -> Option<LuaValue> {
+        let r = match &expr.inner {
+            Expr::Null => Err(CompileErrorType::UnsupportedInLua {
+                what: "`null`".to_owned(),
+            }),
+            Expr::BoolLiteral(v) => Ok(LuaValue::new(
+                v.to_string(),
+                NodeType::Bool,
+                Precedence::Atom,
+            )),
+            Expr::IntLiteral(v) => Ok(number(v.to_string())),
+            Expr::FloatLiteral(v) => Ok(number(format!("{:?}", v))),
+            Expr::StringLiteral(_) => Err(CompileErrorType::StringInLogic),
+            // 定数は値を埋め込む
+            Expr::Ident(ident) if self.context.get_constant(ident).is_some() => {
+                if let Some(ConstantValue::Int(v)) = self.context.get_constant(ident) {
+                    return Some(number(v.to_string()));
+                }
+                match evaluate_expr(expr, &self.context, self.filename).and_then(f32::try_from) {
+                    Ok(v) => Ok(number(format!("{:?}", v))),
+                    Err(err) => {
+                        self.errors.push(err);
+                        return None;
+                    }
+                }
+            }
+            Expr::Ident(ident) => self.context.get_variable_err(ident),
+            Expr::Inputs | Expr::MemberAccess(..) | Expr::Index(..)
+                if !self.calls.is_empty() && reads_inputs(&expr.inner) =>
+            {
+                Err(CompileErrorType::InterfaceInFunction {
+                    interface: "inputs",
+                })
+            }
+            Expr::Inputs => Err(CompileErrorType::FieldAccessOnly),
+            Expr::Outputs => Err(CompileErrorType::OutputsInExpression),
+            Expr::Tuple(_) => Err(CompileErrorType::UnsupportedInLua {
+                what: "Tuple".to_owned(),
+            }),
+            Expr::MemberAccess(object, field) => match &object.inner {
+                Expr::Inputs => self.input(field),
+                _ => Err(CompileErrorType::FieldAccessOnly),
+            },
+            Expr::Index(object, index) => {
+                match self.context.input_element(object, index, self.filename) {
+                    Ok(name) => self.input(&name),
+                    Err(err) => {
+                        self.errors.push(err);
+                        return None;
+                    }
+                }
+            }
+            Expr::BinaryOp(op) => self.binary_operation(op)?,
+            Expr::UnaryOp(op) => self.unary_operation(op)?,
+            Expr::Block {
+                statements,
+                return_value,
+            } => return self.block(statements, return_value.as_deref()),
+            // 値は数値なので false や nil にならない
+            Expr::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let condition = self.expr_of_type(condition, NodeType::Bool);
+                let then = self.expr_to_number(then);
+                let otherwise = self.expr_to_number(otherwise);
+                let (condition, then, otherwise) = (condition?, then?, otherwise?);
+                Ok(LuaValue::new(
+                    format!(
+                        "{} and {} or {}",
+                        condition.operand(Precedence::Comparison),
+                        then.operand(Precedence::Comparison),
+                        otherwise.operand(Precedence::Comparison)
+                    ),
+                    NodeType::Number,
+                    Precedence::Or,
+                ))
+            }
+            Expr::Match { value, arms } => self.match_expr(value, arms, &expr.span)?,
+            Expr::FunctionCall { ident, props, args } => {
+                self.function_call(ident, props, args, &expr.span)?
+            }
+            Expr::Lua { .. } => Err(CompileErrorType::UnsupportedInLua {
+                what: "Nested `lua` block".to_owned(),
+            }),
+            // レイアウトの指示は Lua には関係ない
+            Expr::Annotated { expr, .. } => return self.expr_to_lua(expr),
+        };
+
+        match r {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.push_error(expr.span.clone(), err);
+                None
+            }
+        }
+    }
+
+    fn expr_to_number(&mut self, expr: &Spanned<Expr>) -> Option<LuaValue> {
+        self.expr_of_type(expr, NodeType::Number)
+    }
+
+    fn expr_of_type(&mut self, expr: &Spanned<Expr>, node_type: NodeType) -> Option<LuaValue> {
+        let value = self.expr_to_lua(expr)?;
+        if value.node_type == node_type {
+            Some(value)
+        } else {
+            self.push_error(
+                expr.span.clone(),
+                CompileErrorType::IncompatibleNodeType {
+                    expected_type: node_type,
+                    found_type: value.node_type,
+                },
+            );
+            None
+        }
+    }
+
+    fn input(&self, field: &str) -> Result<LuaValue, CompileErrorType> {
+        let node = self.context.get_input(field)?;
+        let function = match node.node_type() {
+            NodeType::Number => "getNumber",
+            NodeType::Bool => "getBool",
+            node_type => {
+                return Err(CompileErrorType::UnsupportedInLua {
+                    what: format!("{} input", node_type),
+                });
+            }
+        };
+        let channel = self.channels.input(node).unwrap();
+        Ok(LuaValue::new(
+            format!("input.{}({})", function, channel),
+            node.node_type(),
+            Precedence::Atom,
+        ))
+    }
+
+    // ブロックの値は一時変数に入れる
+    // 文がなければ値の式をそのまま使う (if の分岐など)
+    fn block(
+        &mut self,
+        statements: &[Spanned<Statement>],
+        return_value: Option<&Spanned<Expr>>,
+    ) -> Option<LuaValue> {
+        if statements.is_empty() {
+            return return_value.and_then(|r| self.expr_to_lua(r));
+        }
+        let temp = return_value.map(|_| self.new_temp());
+        if let Some(temp) = &temp {
+            self.push_line(format!("local {}", temp));
+        }
+        self.push_line("do".to_owned());
+        self.indent += 1;
+        self.context.push_scope();
+        for statement in statements {
+            self.statement(statement);
+        }
+        let ret = return_value.and_then(|r| self.expr_to_lua(r));
+        if let (Some(temp), Some(ret)) = (&temp, &ret) {
+            self.push_line(format!("{} = {}", temp, ret.expr));
+        }
+        self.context.pop_scope().unwrap();
+        self.indent -= 1;
+        self.push_line("end".to_owned());
+
+        ret.map(|ret| LuaValue::new(temp.unwrap(), ret.node_type, Precedence::Atom))
+    }
+
+    fn binary_operation(&mut self, op: &BinaryOp) -> Option<Result<LuaValue, CompileErrorType>> {
+        let (lhs, rhs, operator, precedence) = match op {
+            BinaryOp::Add(lhs, rhs) => (lhs, rhs, "+", Precedence::Sum),
+            BinaryOp::Sub(lhs, rhs) => (lhs, rhs, "-", Precedence::Sum),
+            BinaryOp::Mul(lhs, rhs) => (lhs, rhs, "*", Precedence::Product),
+            BinaryOp::Div(lhs, rhs) => (lhs, rhs, "/", Precedence::Product),
+            // Lua の % は床除算の剰余なので、ゲームの Modulo に合わせて fmod を使う
+            BinaryOp::Mod(lhs, rhs) => {
+                if matches!(rhs.inner, Expr::IntLiteral(0))
+                    || matches!(rhs.inner, Expr::FloatLiteral(v) if v == 0.0)
+                {
+                    self.push_error(rhs.span.clone(), CompileErrorType::ModuloByZero);
+                }
+                let lhs = self.expr_to_number(lhs)?;
+                let rhs = self.expr_to_number(rhs)?;
+                return Some(Ok(number(format!("math.fmod({}, {})", lhs.expr, rhs.expr))));
+            }
+            BinaryOp::Lt(lhs, rhs) | BinaryOp::Gt(lhs, rhs) => {
+                let operator = if matches!(op, BinaryOp::Lt(_, _)) {
+                    "<"
+                } else {
+                    ">"
+                };
+                let lhs = self.expr_to_number(lhs)?;
+                let rhs = self.expr_to_number(rhs)?;
+                return Some(Ok(LuaValue::new(
+                    format!(
+                        "{} {} {}",
+                        lhs.operand(Precedence::Sum),
+                        operator,
+                        rhs.operand(Precedence::Sum)
+                    ),
+                    NodeType::Bool,
+                    Precedence::Comparison,
+                )));
+            }
+            // ^ は右結合
+            BinaryOp::Pow(lhs, rhs) => {
+                let lhs = self.expr_to_number(lhs)?;
+                let rhs = self.expr_to_number(rhs)?;
+                return Some(Ok(LuaValue::new(
+                    format!(
+                        "{} ^ {}",
+                        lhs.operand(Precedence::Atom),
+                        rhs.operand(Precedence::Power)
+                    ),
+                    NodeType::Number,
+                    Precedence::Power,
+                )));
+            }
+        };
+        let lhs = self.expr_to_number(lhs)?;
+        let rhs = self.expr_to_number(rhs)?;
+        // 左結合なので、右辺は同じ優先順位でも括弧が要る
+        let rhs = if rhs.precedence <= precedence {
+            format!("({})", rhs.expr)
+        } else {
+            rhs.expr
+        };
+        Some(Ok(LuaValue::new(
+            format!("{} {} {}", lhs.operand(precedence), operator, rhs),
+            NodeType::Number,
+            precedence,
+        )))
+    }
+
+    fn unary_operation(&mut self, op: &UnaryOp) -> Option<Result<LuaValue, CompileErrorType>> {
+        match op {
+            UnaryOp::Neg(x) => {
+                let x = self.expr_to_number(x)?;
+                // `--` はコメントになるので、単項演算子は常に括弧で囲む
+                Some(Ok(LuaValue::new(
+                    format!("-{}", x.operand(Precedence::Atom)),
+                    NodeType::Number,
+                    Precedence::Unary,
+                )))
+            }
+        }
+    }
+
+    fn function_call(
+        &mut self,
+        func_type: &str,
+        props: &Option<Spanned<Vec<Spanned<Argument>>>>,
+        args: &Spanned<Vec<Spanned<Argument>>>,
+        span: &Span,
+    ) -> Option<Result<LuaValue, CompileErrorType>> {
+        // ユーザー定義関数は組み込み関数より優先する
+        if let Some(function) = self.functions.get(func_type) {
+            return self.user_function(function, props, args, span);
+        }
+
+        let expr = match func_type {
+            "clamp" => {
+                let ([min, max], [input]) = self.bind(&CLAMP, props, args, span)?;
+                let input = self.expr_to_number(input)?;
+                let min = self.prop(min)?;
+                let max = self.prop(max)?;
+                format!("math.min(math.max({}, {:?}), {:?})", input.expr, min, max)
+            }
+            "abs" => {
+                let ([], [input]) = self.bind(&ABS, props, args, span)?;
+                let input = self.expr_to_number(input)?;
+                format!("math.abs({})", input.expr)
+            }
+            "pid" => {
+                return Some(Err(CompileErrorType::UnsupportedInLua {
+                    what: "`pid`".to_owned(),
+                }));
+            }
+            _ => return Some(Err(self.functions.unknown(func_type))),
+        };
+        Some(Ok(number(expr)))
+    }
+
+    // 引数を変数として、本体を呼び出した位置に展開する
+    fn user_function(
+        &mut self,
+        function: &'t Function,
+        props: &Option<Spanned<Vec<Spanned<Argument>>>>,
+        args: &Spanned<Vec<Spanned<Argument>>>,
+        span: &Span,
+    ) -> Option<Result<LuaValue, CompileErrorType>> {
+        if self.calls.contains(&function.name.as_str()) {
+            return Some(Err(CompileErrorType::RecursiveFunction {
+                name: function.name.clone(),
+            }));
+        }
+        let (prop_values, arg_values) = match FunctionSignature(function).bind(props, args, span) {
+            Ok(bound) => bound,
+            Err(errors) => {
+                for (span, err) in errors {
+                    self.push_error(span, err);
+                }
+                return None;
+            }
+        };
+
+        // 値は呼び出し側のスコープで求める
+        let prop_values = prop_values
+            .into_iter()
+            .map(|v| match evaluate_expr(v, &self.context, self.filename) {
+                Ok(value) => Some(ConstantValue::from(value)),
+                Err(err) => {
+                    self.errors.push(err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let arg_values = arg_values
+            .into_iter()
+            .map(|v| {
+                let value = self.expr_to_lua(v)?;
+                Some(self.evaluate_once(value))
+            })
+            .collect::<Vec<_>>();
+        let prop_values = prop_values.into_iter().collect::<Option<Vec<_>>>()?;
+        let arg_values = arg_values.into_iter().collect::<Option<Vec<_>>>()?;
+
+        self.context.push_isolated_scope();
+        for (name, value) in function.props.iter().zip(prop_values) {
+            self.context.define_constant(name.inner.clone(), value);
+        }
+        for (name, value) in function.params.iter().zip(arg_values) {
+            self.context.define_variable(name.inner.clone(), value);
+        }
+        self.calls.push(&function.name);
+        let ret = self.expr_to_lua(&function.body);
+        self.calls.pop();
+        self.context.pop_scope().unwrap();
+        ret.map(Ok)
+    }
+
+    // 各分岐で x == k と比べる (値は数値なので false や nil にならない)
+    fn match_expr(
+        &mut self,
+        value: &Spanned<Expr>,
+        arms: &[Spanned<MatchArm>],
+        span: &Span,
+    ) -> Option<Result<LuaValue, CompileErrorType>> {
+        let arms = check_arms(arms, span, &self.context, self.filename, self.errors);
+        let value = self.expr_to_number(value)?;
+        let arms = arms?;
+        let value = self.evaluate_once(value);
+
+        let cases = arms
+            .cases
+            .iter()
+            .map(|(key, v)| Some((*key, self.expr_to_number(v)?)))
+            .collect::<Vec<_>>();
+        let default = self.expr_to_number(arms.default);
+        let cases = cases.into_iter().collect::<Option<Vec<_>>>()?;
+        let default = default?;
+        if cases.is_empty() {
+            return Some(Ok(default));
+        }
+
+        let mut expr = String::new();
+        for (key, v) in cases {
+            expr += &format!(
+                "{} == {:?} and {} or ",
+                value.expr,
+                key,
+                v.operand(Precedence::Comparison)
+            );
+        }
+        expr += &default.operand(Precedence::Comparison);
+        Some(Ok(LuaValue::new(expr, NodeType::Number, Precedence::Or)))
+    }
+
+    // 式は一時変数に入れて一度だけ評価する
+    fn evaluate_once(&mut self, value: LuaValue) -> LuaValue {
+        if value.precedence == Precedence::Atom {
+            return value;
+        }
+        let temp = self.new_temp();
+        self.push_line(format!("local {} = {}", temp, value.expr));
+        LuaValue::new(temp, value.node_type, Precedence::Atom)
+    }
+}
+
+fn number(expr: String) -> LuaValue {
+    LuaValue::new(expr, NodeType::Number, Precedence::Atom)
+}
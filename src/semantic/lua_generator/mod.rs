@@ -0,0 +1,399 @@
+mod components;
+mod expressions;
+
+use super::{
+    context::Context,
+    evaluate_expr::ConstantValue,
+    for_loop::{iterations, note_iteration},
+    function_table::{FunctionTable, assigns_outputs},
+    has_errors,
+    interface::{Annotations, NodeAnnotation},
+    lua_script::{CHANNEL_LIMIT, check_length},
+    signature::{Bound, PropValue, Signature},
+};
+use crate::{
+    compile_error::{CompileError, CompileErrorType},
+    microcontroller::{InputNode, Node, NodeType, OutputNode},
+    syntax::{Argument, AssignmentTarget, ForLoop, Spanned, Statement},
+};
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+type Span = std::ops::Range<usize>;
+
+// Lua の式と、その値の型
+#[derive(Clone, Debug)]
+pub(super) struct LuaValue {
+    expr: String,
+    node_type: NodeType,
+    precedence: Precedence,
+}
+
+impl LuaValue {
+    fn new(expr: String, node_type: NodeType, precedence: Precedence) -> Self {
+        Self {
+            expr,
+            node_type,
+            precedence,
+        }
+    }
+
+    // 演算子の被演算子にするとき、必要なら括弧で囲む
+    fn operand(&self, precedence: Precedence) -> String {
+        if self.precedence < precedence {
+            format!("({})", self.expr)
+        } else {
+            self.expr.clone()
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+enum Precedence {
+    // c and a or b
+    Or,
+    Comparison,
+    Sum,
+    Product,
+    Unary,
+    Power,
+    Atom,
+}
+
+// インターフェースのノードと、コンポジットのチャンネルの対応
+// 数値とオンオフでそれぞれ 1 から割り当てる
+#[derive(Default, Debug)]
+struct Channels {
+    number_inputs: Vec<Rc<InputNode>>,
+    bool_inputs: Vec<Rc<InputNode>>,
+    number_outputs: Vec<Rc<RefCell<OutputNode>>>,
+    bool_outputs: Vec<Rc<RefCell<OutputNode>>>,
+}
+
+impl Channels {
+    fn new(nodes: &[Node]) -> Self {
+        let mut channels = Self::default();
+        for node in nodes {
+            match node {
+                Node::Input(n) => match n.node_type() {
+                    NodeType::Number => channels.number_inputs.push(n.clone()),
+                    NodeType::Bool => channels.bool_inputs.push(n.clone()),
+                    _ => {}
+                },
+                Node::Output(n) => match n.borrow().node_type() {
+                    NodeType::Number => channels.number_outputs.push(n.clone()),
+                    NodeType::Bool => channels.bool_outputs.push(n.clone()),
+                    _ => {}
+                },
+            }
+        }
+        channels
+    }
+
+    fn input(&self, node: &Rc<InputNode>) -> Option<usize> {
+        let list = match node.node_type() {
+            NodeType::Number => &self.number_inputs,
+            NodeType::Bool => &self.bool_inputs,
+            _ => return None,
+        };
+        list.iter().position(|n| Rc::ptr_eq(n, node)).map(|i| i + 1)
+    }
+
+    fn output(&self, node: &Rc<RefCell<OutputNode>>) -> Option<usize> {
+        let list = match node.borrow().node_type() {
+            NodeType::Number => &self.number_outputs,
+            NodeType::Bool => &self.bool_outputs,
+            _ => return None,
+        };
+        list.iter().position(|n| Rc::ptr_eq(n, node)).map(|i| i + 1)
+    }
+
+    fn check_limit(&self) -> Result<(), CompileErrorType> {
+        let lists = [
+            (NodeType::Number, self.number_inputs.len()),
+            (NodeType::Bool, self.bool_inputs.len()),
+            (NodeType::Number, self.number_outputs.len()),
+            (NodeType::Bool, self.bool_outputs.len()),
+        ];
+        for (node_type, len) in lists {
+            if len > CHANNEL_LIMIT {
+                return Err(CompileErrorType::TooManyChannels {
+                    node_type,
+                    limit: CHANNEL_LIMIT,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+// ロジックを Lua スクリプト 1 つに変換する
+#[derive(Debug)]
+pub(super) struct LuaGenerator<'f, 'e, 't> {
+    context: Context<LuaValue>,
+    channels: Channels,
+    // 代入された出力ノードのチャンネル
+    assigned_numbers: Vec<bool>,
+    assigned_bools: Vec<bool>,
+    // 出力ノード名 -> 宣言された範囲と単位
+    output_annotations: HashMap<String, NodeAnnotation>,
+    functions: FunctionTable<'t>,
+    // 展開中の関数 (再帰の検出に使う)
+    calls: Vec<&'t str>,
+    lines: Vec<String>,
+    indent: usize,
+    temp_count: usize,
+    span: Span,
+    filename: &'f str,
+    errors: &'e mut Vec<CompileError<'f>>,
+}
+
+impl<'f, 'e, 't> LuaGenerator<'f, 'e, 't> {
+    // span はチャンネル数やスクリプトの長さの超過を指す位置 (backend の指定など)
+    pub(super) fn new(
+        context: Context<LuaValue>,
+        nodes: &[Node],
+        annotations: Annotations,
+        functions: FunctionTable<'t>,
+        span: Span,
+        filename: &'f str,
+        errors: &'e mut Vec<CompileError<'f>>,
+    ) -> Self {
+        let channels = Channels::new(nodes);
+        let mut generator = Self {
+            context,
+            assigned_numbers: vec![false; channels.number_outputs.len()],
+            assigned_bools: vec![false; channels.bool_outputs.len()],
+            channels,
+            output_annotations: annotations.outputs,
+            functions,
+            calls: Vec::new(),
+            lines: Vec::new(),
+            indent: 1,
+            temp_count: 0,
+            span,
+            filename,
+            errors,
+        };
+        if let Err(err) = generator.channels.check_limit() {
+            generator.push_error(generator.span.clone(), err);
+        }
+        generator
+    }
+
+    pub(super) fn statement(&mut self, statement: &Spanned<Statement>) {
+        match &statement.inner {
+            Statement::Assignment(assignment) => {
+                let target = &assignment.inner.target;
+                let value = &assignment.inner.value;
+
+                if !self.calls.is_empty() && assigns_outputs(&target.inner) {
+                    self.push_error(
+                        assignment.span.clone(),
+                        CompileErrorType::InterfaceInFunction {
+                            interface: "outputs",
+                        },
+                    );
+                    return;
+                }
+                if let Some(value) = self.expr_to_lua(value) {
+                    self.assign_output(target, value, &assignment.inner.value.span);
+                }
+            }
+            Statement::Let(ident, value) => {
+                if let Some(value) = self.expr_to_lua(value) {
+                    let name = self.local_name(ident);
+                    self.push_line(format!("local {} = {}", name, value.expr));
+                    self.context.define_variable(
+                        ident.clone(),
+                        LuaValue::new(name, value.node_type, Precedence::Atom),
+                    );
+                }
+            }
+            Statement::For(for_loop) => self.for_loop(for_loop),
+        }
+    }
+
+    // 本体を回数分展開する (回ごとのスコープでループ変数は定数)
+    // 本体で変数を宣言するなら、local が重ならないよう do ... end で囲む
+    fn for_loop(&mut self, for_loop: &ForLoop<Statement>) {
+        let range = match iterations(for_loop, &self.context, self.filename) {
+            Ok(range) => range,
+            Err(err) => {
+                self.errors.push(err);
+                return;
+            }
+        };
+        let scoped = for_loop
+            .body
+            .iter()
+            .any(|s| matches!(s.inner, Statement::Let(..)));
+        let variable = &for_loop.variable.inner;
+        for i in range {
+            let errors = self.errors.len();
+            if scoped {
+                self.push_line("do".to_owned());
+                self.indent += 1;
+            }
+            self.context.push_scope();
+            self.context
+                .define_constant(variable.clone(), ConstantValue::Int(i));
+            for statement in &for_loop.body {
+                self.statement(statement);
+            }
+            self.context.pop_scope().unwrap();
+            if scoped {
+                self.indent -= 1;
+                self.push_line("end".to_owned());
+            }
+            note_iteration(&mut self.errors[errors..], variable, i);
+            if has_errors(&self.errors[errors..]) {
+                break;
+            }
+        }
+    }
+
+    fn assign_output(&mut self, target: &Spanned<AssignmentTarget>, value: LuaValue, span: &Span) {
+        let field = match self.context.output_name(target, self.filename) {
+            Ok(name) => name,
+            Err(err) => {
+                self.errors.push(err);
+                return;
+            }
+        };
+
+        let output = match self.context.get_output(&field) {
+            Ok(o) => o.clone(),
+            Err(err) => {
+                self.push_error(target.span.clone(), err);
+                return;
+            }
+        };
+        let expected_type = output.borrow().node_type();
+        if expected_type != value.node_type {
+            self.push_error(
+                span.clone(),
+                CompileErrorType::IncompatibleNodeType {
+                    expected_type,
+                    found_type: value.node_type,
+                },
+            );
+            return;
+        }
+        let Some(channel) = self.channels.output(&output) else {
+            self.push_error(
+                target.span.clone(),
+                CompileErrorType::UnsupportedInLua {
+                    what: format!("{} output", expected_type),
+                },
+            );
+            return;
+        };
+
+        let (function, assigned) = match expected_type {
+            NodeType::Number => ("setNumber", &mut self.assigned_numbers),
+            _ => ("setBool", &mut self.assigned_bools),
+        };
+        assigned[channel - 1] = true;
+
+        // clamp = true の出力は範囲に収める
+        let mut expr = value.expr;
+        if let Some(annotation) = self.output_annotations.get(&field)
+            && annotation.clamp
+            && let Some(range) = annotation.range
+        {
+            expr = format!(
+                "math.min(math.max({}, {:?}), {:?})",
+                expr, range.min as f32, range.max as f32
+            );
+        }
+        self.push_line(format!("output.{}({}, {})", function, channel, expr));
+    }
+
+    fn prop(&mut self, value: PropValue) -> Option<f32> {
+        match value.evaluate(&self.context, self.filename) {
+            Ok(v) => Some(v),
+            Err(err) => {
+                self.errors.push(err);
+                None
+            }
+        }
+    }
+
+    fn push_line(&mut self, line: String) {
+        self.lines
+            .push(format!("{}{}", "\t".repeat(self.indent), line));
+    }
+
+    fn new_temp(&mut self) -> String {
+        self.temp_count += 1;
+        format!("_t{}", self.temp_count)
+    }
+
+    // Lua の名前に使えない識別子 (ASCII 以外を含むもの) は一時変数の名前にする
+    fn local_name(&mut self, ident: &str) -> String {
+        if !is_lua_name(ident) {
+            self.new_temp()
+        } else if is_reserved(ident) {
+            format!("{}_", ident)
+        } else {
+            ident.to_owned()
+        }
+    }
+
+    fn bind<'a, const P: usize, const A: usize>(
+        &mut self,
+        signature: &Signature<P, A>,
+        props: &'a Option<Spanned<Vec<Spanned<Argument>>>>,
+        args: &'a Spanned<Vec<Spanned<Argument>>>,
+        span: &Span,
+    ) -> Option<Bound<'a, P, A>> {
+        match signature.bind(props, args, span) {
+            Ok(bound) => Some(bound),
+            Err(errors) => {
+                for (span, err) in errors {
+                    self.push_error(span, err);
+                }
+                None
+            }
+        }
+    }
+
+    fn push_error(&mut self, span: Span, error_type: CompileErrorType) {
+        self.errors
+            .push(CompileError::new(self.filename, span, error_type));
+    }
+
+    // 出力するスクリプト (長すぎればエラーも出す)
+    pub(super) fn script(&mut self) -> String {
+        let mut script = "function onTick()\n".to_owned();
+        for line in &self.lines {
+            script += line;
+            script += "\n";
+        }
+        script += "end\n";
+        if let Err(err) = check_length(&script) {
+            self.push_error(self.span.clone(), err);
+        }
+        script
+    }
+}
+
+// Lua の予約語やスクリプトで使うグローバル変数、一時変数と重なる名前は変える
+fn is_reserved(ident: &str) -> bool {
+    const RESERVED: &[&str] = &[
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if",
+        "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+        "input", "output", "math", "property", "screen", "onTick", "onDraw",
+    ];
+    RESERVED.contains(&ident)
+        || ident
+            .strip_prefix("_t")
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+// Lua 5.3 の名前は [A-Za-z_][A-Za-z0-9_]*
+fn is_lua_name(ident: &str) -> bool {
+    ident.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && ident.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,159 @@
+use crate::{
+    compile_error::CompileErrorType,
+    microcontroller::{
+        BoolLink, Component, CompositeComponent, CompositeLink, Link, LuaComponent, NodeType,
+        NumberLink,
+    },
+    xml_schema,
+};
+
+use std::rc::Rc;
+
+// コンポジット 1 本で送れるチャンネル数
+pub(super) const CHANNEL_LIMIT: usize = 32;
+
+// ゲーム内のエディタに入る文字数
+pub(super) const SCRIPT_LIMIT: usize = 4096;
+
+// スクリプトとやりとりする値
+// チャンネルは数値とオンオフでそれぞれ並び順に 1 から割り当てる
+#[derive(Default, Debug)]
+pub(super) struct ScriptPorts {
+    pub(super) number_inputs: Vec<NumberLink>,
+    pub(super) bool_inputs: Vec<BoolLink>,
+    // 読み出す出力の型とチャンネル
+    pub(super) outputs: Vec<(NodeType, usize)>,
+}
+
+impl ScriptPorts {
+    // 入力ノード -> コンポジット書き込み -> Lua -> コンポジット読み込み
+    // 戻り値の Link は outputs と同じ順
+    pub(super) fn into_components(self, script: String) -> (Vec<Rc<Component>>, Vec<Link>) {
+        let mut components = Vec::new();
+
+        let mut composite = CompositeLink::default();
+        if !self.number_inputs.is_empty() {
+            let write = CompositeComponent::WriteNumber {
+                composite,
+                inputs: self.number_inputs,
+                start_channel: 1,
+            };
+            composite = push(&mut components, Component::Composite(write))
+                .try_into()
+                .unwrap();
+        }
+        if !self.bool_inputs.is_empty() {
+            let write = CompositeComponent::WriteBool {
+                composite,
+                inputs: self.bool_inputs,
+                start_channel: 1,
+            };
+            composite = push(&mut components, Component::Composite(write))
+                .try_into()
+                .unwrap();
+        }
+
+        let lua: CompositeLink = push(
+            &mut components,
+            Component::Lua(LuaComponent {
+                composite,
+                video: Default::default(),
+                script,
+            }),
+        )
+        .try_into()
+        .unwrap();
+
+        let links = self
+            .outputs
+            .into_iter()
+            .map(|(node_type, channel)| {
+                let composite = lua.clone();
+                let channel = channel as u8;
+                let read = match node_type {
+                    NodeType::Number => CompositeComponent::ReadNumber { composite, channel },
+                    _ => CompositeComponent::ReadBool { composite, channel },
+                };
+                push(&mut components, Component::Composite(read))
+            })
+            .collect();
+
+        (components, links)
+    }
+}
+
+fn push(components: &mut Vec<Rc<Component>>, component: Component) -> Link {
+    let rc = Rc::new(component);
+    let link = Link::component(&rc, 0).unwrap();
+    components.push(rc);
+    link
+}
+
+// ゲームは改行などを文字参照にした XML の属性値の長さで数える
+pub(super) fn check_length(script: &str) -> Result<(), CompileErrorType> {
+    let length = xml_schema::escaped_len(script);
+    if length > SCRIPT_LIMIT {
+        Err(CompileErrorType::LuaScriptTooLong {
+            length,
+            limit: SCRIPT_LIMIT,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+// スクリプト中の input.getNumber(1) などのチャンネル
+#[derive(Default, Debug)]
+struct ChannelUsage {
+    channels: Vec<usize>,
+    // チャンネルが定数でない呼び出しがあるか
+    dynamic: bool,
+}
+
+fn channel_usage(script: &str, function: &str) -> ChannelUsage {
+    let mut usage = ChannelUsage::default();
+    let pattern = format!("{}(", function);
+    for line in script.lines() {
+        // コメントは無視する
+        let code = line.split("--").next().unwrap_or_default();
+        let mut rest = code;
+        while let Some(i) = rest.find(&pattern) {
+            rest = &rest[i + pattern.len()..];
+            let arg = rest.split([',', ')']).next().unwrap_or_default().trim();
+            match arg.parse() {
+                Ok(channel) => usage.channels.push(channel),
+                Err(_) => usage.dynamic = true,
+            }
+        }
+    }
+    usage
+}
+
+// 宣言した入出力の数と、スクリプトが読み書きするチャンネルが合っているか
+// declared は (関数, 宣言した数, 全て書き込まれる必要があるか)
+pub(super) fn check_channels(
+    script: &str,
+    declared: [(&'static str, usize, bool); 4],
+) -> Vec<CompileErrorType> {
+    let mut errors = Vec::new();
+    for (function, count, required) in declared {
+        let usage = channel_usage(script, function);
+        for &channel in &usage.channels {
+            if channel == 0 || channel > count {
+                errors.push(CompileErrorType::UndeclaredLuaChannel {
+                    function,
+                    channel,
+                    count,
+                });
+            }
+        }
+        if required && !usage.dynamic {
+            for channel in 1..=count {
+                if !usage.channels.contains(&channel) {
+                    errors.push(CompileErrorType::UnusedLuaChannel { function, channel });
+                }
+            }
+        }
+    }
+    errors
+}
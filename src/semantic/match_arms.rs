@@ -0,0 +1,89 @@
+use super::evaluate_expr::{ConstantValue, Constants};
+use crate::{
+    compile_error::{CompileError, CompileErrorType},
+    syntax::{Expr, MatchArm, Pattern, Spanned},
+};
+
+// 到達できる分岐 (パターンの値の昇順) と、どれにも当てはまらないときの値
+#[derive(Debug)]
+pub(super) struct Arms<'a> {
+    pub(super) cases: Vec<(f32, &'a Spanned<Expr>)>,
+    pub(super) default: &'a Spanned<Expr>,
+}
+
+// 数値のパターンの値 (定数の名前なら定数の値)
+fn pattern_value(
+    pattern: &Pattern,
+    constants: &dyn Constants,
+) -> Result<Option<f32>, CompileErrorType> {
+    match pattern {
+        Pattern::Number(v) => Ok(Some(*v as f32)),
+        Pattern::Name(path) => match (
+            &path[..],
+            path.first().and_then(|name| constants.get_constant(name)),
+        ) {
+            ([_], Some(ConstantValue::Int(v))) => Ok(Some(*v as f32)),
+            ([_], Some(ConstantValue::Float(v))) => Ok(Some(*v as f32)),
+            _ => Err(CompileErrorType::UnsupportedPattern {
+                pattern: path.join("."),
+            }),
+        },
+        Pattern::Wildcard => Ok(None),
+    }
+}
+
+// _ より後の分岐と、同じ値の 2 つ目以降の分岐は到達できない (警告)
+// _ がなければ網羅していないのでエラー
+pub(super) fn check_arms<'a, 'f>(
+    arms: &'a [Spanned<MatchArm>],
+    span: &std::ops::Range<usize>,
+    constants: &dyn Constants,
+    filename: &'f str,
+    errors: &mut Vec<CompileError<'f>>,
+) -> Option<Arms<'a>> {
+    let mut cases: Vec<(f32, &Spanned<Expr>)> = Vec::new();
+    let mut default = None;
+    let mut failed = false;
+    for arm in arms {
+        let value = match pattern_value(&arm.pattern.inner, constants) {
+            Ok(value) => value,
+            Err(err) => {
+                errors.push(CompileError::new(filename, arm.pattern.span.clone(), err));
+                failed = true;
+                continue;
+            }
+        };
+        let unreachable = default.is_some()
+            || match value {
+                // ゲーム内の値は f32 なので、f32 で同じなら同じパターン
+                Some(v) => cases.iter().any(|(key, _)| *key == v),
+                None => false,
+            };
+        if unreachable {
+            errors.push(CompileError::new(
+                filename,
+                arm.pattern.span.clone(),
+                CompileErrorType::UnreachableArm,
+            ));
+            continue;
+        }
+        match value {
+            Some(v) => cases.push((v, &arm.value)),
+            None => default = Some(&arm.value),
+        }
+    }
+    if failed {
+        return None;
+    }
+
+    let Some(default) = default else {
+        errors.push(CompileError::new(
+            filename,
+            span.clone(),
+            CompileErrorType::NonExhaustiveMatch,
+        ));
+        return None;
+    };
+    cases.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Some(Arms { cases, default })
+}
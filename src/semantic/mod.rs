@@ -1,72 +1,140 @@
+mod constant_table;
+mod context;
 mod evaluate_expr;
 mod field_analyzer;
+mod for_loop;
+mod function_table;
 mod interface;
+mod interval;
 //mod logic;
 mod logic_analyzer;
+mod lua_generator;
+mod lua_script;
+mod match_arms;
+mod signature;
+mod unit;
 mod value_type;
+use constant_table::ConstantTable;
+use context::Context;
 use evaluate_expr::evaluate_expr;
 use field_analyzer::FieldAnalyzer;
+use function_table::{FunctionTable, functions};
 use interface::InterfaceAnalyzer;
-use logic_analyzer::{Context, LogicAnalyzer};
+use logic_analyzer::LogicAnalyzer;
+use lua_generator::LuaGenerator;
 pub use value_type::ValueType;
 
 use crate::{
-    compile_error::CompileError,
-    microcontroller::{Component, Node, UnpositionedMicrocontroller},
+    compile_error::{CompileError, CompileErrorType, Diagnostic, WithWarnings},
+    microcontroller::{Component, LayoutHint, Node, UnpositionedMicrocontroller},
     syntax::{self, MicrocontrollerElement, Spanned},
 };
 
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    rc::Rc,
+};
 
 #[derive(Debug)]
 pub struct FileAnalyzeResult<'a> {
-    microcontrollers: HashMap<String, UnpositionedMicrocontroller>,
+    microcontrollers: BTreeMap<String, UnpositionedMicrocontroller>,
     errors: Vec<CompileError<'a>>,
+    // lua(file = ...) で参照しているファイル
+    pub scripts: BTreeSet<PathBuf>,
 }
 
 impl<'a> FileAnalyzeResult<'a> {
-    pub fn into_output(self) -> Option<HashMap<String, UnpositionedMicrocontroller>> {
-        if self.has_errors() {
-            None
+    // 警告だけなら成功とし、警告も一緒に返す
+    pub fn into_result(
+        self,
+    ) -> Result<WithWarnings<BTreeMap<String, UnpositionedMicrocontroller>>, Vec<Diagnostic>> {
+        let failed = has_errors(&self.errors);
+        let diagnostics = self.errors.into_iter().map(Diagnostic::from).collect();
+        if failed {
+            Err(diagnostics)
         } else {
-            Some(self.microcontrollers)
+            Ok(WithWarnings::new(self.microcontrollers, diagnostics))
         }
     }
+}
 
-    pub fn has_errors(&self) -> bool {
-        !self.errors.is_empty()
-    }
-
-    pub fn errors(&self) -> &Vec<CompileError<'a>> {
-        &self.errors
-    }
+fn has_errors(errors: &[CompileError]) -> bool {
+    errors.iter().any(|e| !e.is_warning())
 }
 
 pub fn analyze_file<'a>(tree: &Spanned<syntax::File>, filename: &'a str) -> FileAnalyzeResult<'a> {
-    let mut microcontrollers = HashMap::new();
+    let mut microcontrollers = BTreeMap::new();
     let mut errors = Vec::new();
+    let mut scripts = BTreeSet::new();
+
+    // 定数は宣言の順に求める
+    let file_constants = ConstantTable::default().with(
+        tree.elements.iter().filter_map(|e| match &e.inner {
+            syntax::Element::Constant(constant) => Some(constant),
+            _ => None,
+        }),
+        filename,
+        &mut errors,
+    );
+
+    // 関数は定義より前でも呼び出せる
+    let file_functions = FunctionTable::default().with(
+        functions(&tree.elements, |e| match e {
+            syntax::Element::Function(function) => Some(function),
+            _ => None,
+        }),
+        filename,
+        &mut errors,
+    );
 
     for element in &tree.elements {
         match &element.inner {
-            syntax::Element::Microcontroller { name, elements } => {
-                if let Some(mc) = analyze_microcontroller(elements, filename, &mut errors) {
+            syntax::Element::Microcontroller {
+                name,
+                doc,
+                elements,
+            } => {
+                if let Some(mc) = analyze_microcontroller(
+                    elements,
+                    doc,
+                    &file_constants,
+                    &file_functions,
+                    filename,
+                    &mut errors,
+                    &mut scripts,
+                ) {
                     microcontrollers.insert(name.clone(), mc);
                 }
             }
+            syntax::Element::Function(_) | syntax::Element::Constant(_) => {}
         }
     }
 
     FileAnalyzeResult {
         microcontrollers,
         errors,
+        scripts,
     }
 }
 
+// ロジックをどう実装するか
+#[derive(strum::EnumString, Default, Clone, Copy, Debug)]
+#[strum(serialize_all = "lowercase")]
+enum Backend {
+    // 演算コンポーネントのグラフ
+    #[default]
+    Components,
+    // Lua スクリプト 1 つ
+    Lua,
+}
+
 #[derive(Default, Debug)]
 struct MicrocontrollerField {
     name: Option<String>,
     description: Option<String>,
     size: Option<(u8, u8)>,
+    backend: Option<(Backend, std::ops::Range<usize>)>,
 }
 
 impl MicrocontrollerField {
@@ -74,6 +142,7 @@ impl MicrocontrollerField {
         self,
         nodes: Vec<Node>,
         components: Vec<Rc<Component>>,
+        hints: BTreeMap<usize, LayoutHint>,
     ) -> UnpositionedMicrocontroller {
         let (width, length) = self.size.unwrap_or((1, 1));
         UnpositionedMicrocontroller {
@@ -83,33 +152,65 @@ impl MicrocontrollerField {
             length,
             nodes,
             components,
+            hints,
         }
     }
 }
 
-fn analyze_microcontroller<'a>(
-    elements: &[Spanned<syntax::MicrocontrollerElement>],
+fn analyze_microcontroller<'a, 't>(
+    elements: &'t [Spanned<syntax::MicrocontrollerElement>],
+    doc: &Option<String>,
+    file_constants: &ConstantTable,
+    file_functions: &FunctionTable<'t>,
     filename: &'a str,
     errors: &mut Vec<CompileError<'a>>,
+    scripts: &mut BTreeSet<PathBuf>,
 ) -> Option<UnpositionedMicrocontroller> {
-    let mut mc = MicrocontrollerField::default();
+    let constants = file_constants.with(
+        elements.iter().filter_map(|e| match &e.inner {
+            MicrocontrollerElement::Constant(constant) => Some(constant),
+            _ => None,
+        }),
+        filename,
+        errors,
+    );
+
+    // ドキュメントコメントは description の既定値
+    let mut mc = MicrocontrollerField {
+        description: doc.clone(),
+        ..Default::default()
+    };
 
     let mut fields = FieldAnalyzer::new(filename);
     for element in elements {
         if let MicrocontrollerElement::Field(assignment) = &element.inner {
             let r = fields.assignment(assignment, |ident, expr| {
                 match ident.as_str() {
-                    "name" => mc.name = Some(evaluate_expr(expr, filename)?.try_into()?),
+                    "name" => {
+                        mc.name = Some(evaluate_expr(expr, &constants, filename)?.try_into()?)
+                    }
                     "description" => {
-                        mc.description = Some(evaluate_expr(expr, filename)?.try_into()?)
+                        mc.description =
+                            Some(evaluate_expr(expr, &constants, filename)?.try_into()?)
                     }
                     "size" => {
                         mc.size = Some(
-                            evaluate_expr(expr, filename)?
+                            evaluate_expr(expr, &constants, filename)?
                                 .tuple_int_ranged(vec![1..=6, 1..=6])?
                                 .try_into()?,
                         )
                     }
+                    "backend" => {
+                        let name: String = evaluate_expr(expr, &constants, filename)?.try_into()?;
+                        let backend = name.parse().map_err(|_| {
+                            CompileError::new(
+                                filename,
+                                expr.span.clone(),
+                                CompileErrorType::UnknownBackend { name },
+                            )
+                        })?;
+                        mc.backend = Some((backend, expr.span.clone()));
+                    }
                     _ => {
                         return Ok(false);
                     }
@@ -121,7 +222,7 @@ fn analyze_microcontroller<'a>(
             }
         }
     }
-    if !errors.is_empty() {
+    if has_errors(errors) {
         return None;
     }
 
@@ -129,32 +230,97 @@ fn analyze_microcontroller<'a>(
     for element in elements {
         if let MicrocontrollerElement::Interface(items) = &element.inner {
             for item in items {
-                interface.element(item, errors);
+                interface.element(item, &constants, errors);
             }
         }
     }
-    if !errors.is_empty() {
+    if has_errors(errors) {
         return None;
     }
     let interface = interface.layout();
     mc.size = Some(interface.size);
 
-    let mut logic_analyzer = LogicAnalyzer::new(
-        Context::new(interface.inputs, interface.outputs),
+    let functions = file_functions.with(
+        functions(elements, |e| match e {
+            MicrocontrollerElement::Function(function) => Some(function),
+            _ => None,
+        }),
         filename,
         errors,
     );
-    for element in elements {
-        if let MicrocontrollerElement::Logic(statements) = &element.inner {
-            for statement in statements {
+
+    let logic = elements.iter().filter_map(|element| match &element.inner {
+        MicrocontrollerElement::Logic(statements) => Some(statements),
+        _ => None,
+    });
+    let (components, hints) = match mc.backend.clone().unwrap_or_default() {
+        (Backend::Components, _) => {
+            let mut logic_analyzer = LogicAnalyzer::new(
+                Context::new(
+                    interface.inputs,
+                    interface.outputs,
+                    interface.arrays,
+                    constants,
+                ),
+                interface.annotations,
+                functions,
+                filename,
+                errors,
+            );
+            for statement in logic.flatten() {
                 logic_analyzer.statement(statement);
             }
+            let (components, hints, mut files) = logic_analyzer.into_components();
+            scripts.append(&mut files);
+            (components, hints)
         }
-    }
-    let components = logic_analyzer.into_components();
-    if !errors.is_empty() {
+        (Backend::Lua, span) => {
+            let mut lua_generator = LuaGenerator::new(
+                Context::new(
+                    interface.inputs,
+                    interface.outputs,
+                    interface.arrays,
+                    constants,
+                ),
+                &interface.nodes,
+                interface.annotations,
+                functions,
+                span,
+                filename,
+                errors,
+            );
+            for statement in logic.flatten() {
+                lua_generator.statement(statement);
+            }
+            (lua_generator.into_components(), BTreeMap::new())
+        }
+    };
+    if has_errors(errors) {
         return None;
     }
 
-    Some(mc.into_microcontroller(interface.nodes, components))
+    Some(mc.into_microcontroller(interface.nodes, components, hints))
+}
+
+fn to_tuple1<'a, T>(arr: &'a [T], expect_str: &'static str) -> Result<(&'a T,), CompileErrorType> {
+    match arr {
+        [a] => Ok((a,)),
+        _ => Err(CompileErrorType::LengthMismatch {
+            expect_str,
+            found_len: arr.len(),
+        }),
+    }
+}
+
+fn to_tuple2<'a, T>(
+    arr: &'a [T],
+    expect_str: &'static str,
+) -> Result<(&'a T, &'a T), CompileErrorType> {
+    match arr {
+        [a, b] => Ok((a, b)),
+        _ => Err(CompileErrorType::LengthMismatch {
+            expect_str,
+            found_len: arr.len(),
+        }),
+    }
 }
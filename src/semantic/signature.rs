@@ -0,0 +1,305 @@
+use crate::{
+    compile_error::{CompileError, CompileErrorType},
+    semantic::{evaluate_expr, evaluate_expr::Constants},
+    syntax::{Argument, Expr, Function, Spanned},
+};
+
+use std::fmt::Display;
+
+type Span = std::ops::Range<usize>;
+
+// 組み込み関数のプロパティ (定数) と引数
+#[derive(Debug)]
+pub(super) struct Signature<const P: usize, const A: usize> {
+    name: &'static str,
+    props: [Prop; P],
+    args: [&'static str; A],
+}
+
+// 既定値があれば省略できる
+#[derive(Debug)]
+struct Prop {
+    name: &'static str,
+    default: Option<f32>,
+}
+
+const fn prop(name: &'static str) -> Prop {
+    Prop {
+        name,
+        default: None,
+    }
+}
+
+const fn optional(name: &'static str, default: f32) -> Prop {
+    Prop {
+        name,
+        default: Some(default),
+    }
+}
+
+// ロジックで呼び出せる組み込み関数
+pub(super) const BUILTIN_FUNCTIONS: [&str; 3] = ["clamp", "abs", "pid"];
+
+pub(super) const CLAMP: Signature<2, 1> = Signature {
+    name: "clamp",
+    props: [prop("min"), prop("max")],
+    args: ["input"],
+};
+
+pub(super) const ABS: Signature<0, 1> = Signature {
+    name: "abs",
+    props: [],
+    args: ["input"],
+};
+
+// コンパイル時に値を文字列にする
+pub(super) const STR: Signature<0, 1> = Signature {
+    name: "str",
+    props: [],
+    args: ["value"],
+};
+
+// 係数を入力で受け取る PID Controller
+pub(super) const PID: Signature<0, 6> = Signature {
+    name: "pid",
+    props: [],
+    args: [
+        "setpoint",
+        "process_variable",
+        "proportional",
+        "integral",
+        "derivative",
+        "active",
+    ],
+};
+
+// 係数がプロパティなら PID Controller (Advanced)
+pub(super) const PID_ADVANCED: Signature<3, 3> = Signature {
+    name: "pid",
+    props: [prop("kp"), optional("ki", 0.0), optional("kd", 0.0)],
+    args: ["setpoint", "process_variable", "active"],
+};
+
+// プロパティの値 (省略されたら既定値)
+#[derive(Clone, Copy, Debug)]
+pub(super) enum PropValue<'a> {
+    Expr(&'a Spanned<Expr>),
+    Default(f32),
+}
+
+impl<'a> PropValue<'a> {
+    pub(super) fn evaluate<'f>(
+        self,
+        constants: &dyn Constants,
+        filename: &'f str,
+    ) -> Result<f32, CompileError<'f>> {
+        match self {
+            Self::Expr(expr) => evaluate_expr(expr, constants, filename)?.try_into(),
+            Self::Default(v) => Ok(v),
+        }
+    }
+}
+
+// 割り当てたプロパティと引数
+pub(super) type Bound<'a, const P: usize, const A: usize> =
+    ([PropValue<'a>; P], [&'a Spanned<Expr>; A]);
+
+impl<const P: usize, const A: usize> Signature<P, A> {
+    // 位置または名前で渡された値をパラメーターに割り当てる
+    pub(super) fn bind<'a>(
+        &self,
+        props: &'a Option<Spanned<Vec<Spanned<Argument>>>>,
+        args: &'a Spanned<Vec<Spanned<Argument>>>,
+        span: &Span,
+    ) -> Result<Bound<'a, P, A>, Vec<(Span, CompileErrorType)>> {
+        let signature = self.to_string();
+        let mut errors = Vec::new();
+        let prop_names = self.props.each_ref().map(|p| p.name);
+        let prop_values = props.as_ref().map_or(&[][..], |p| &p.inner);
+        let props_span = props.as_ref().map_or(span, |p| &p.span);
+        let props = match_values(prop_values, &prop_names, &signature, &mut errors);
+        let arg_values = match_values(&args.inner, &self.args, &signature, &mut errors);
+
+        let props: [_; P] = std::array::from_fn(|i| match (props[i], self.props[i].default) {
+            (Some(expr), _) => Some(PropValue::Expr(expr)),
+            (None, Some(default)) => Some(PropValue::Default(default)),
+            (None, None) => None,
+        });
+        let arg_values: [_; A] = arg_values.try_into().unwrap();
+
+        // 足りないパラメーター
+        missing(&props, &prop_names, props_span, &signature, &mut errors);
+        missing(&arg_values, &self.args, &args.span, &signature, &mut errors);
+
+        if errors.is_empty() {
+            Ok((props.map(Option::unwrap), arg_values.map(Option::unwrap)))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// ユーザー定義関数のプロパティと引数 (すべて必須)
+#[derive(Clone, Copy, Debug)]
+pub(super) struct FunctionSignature<'t>(pub(super) &'t Function);
+
+// 割り当てたプロパティと引数 (宣言の順)
+pub(super) type FunctionBound<'a> = (Vec<&'a Spanned<Expr>>, Vec<&'a Spanned<Expr>>);
+
+impl<'t> FunctionSignature<'t> {
+    pub(super) fn bind<'a>(
+        &self,
+        props: &'a Option<Spanned<Vec<Spanned<Argument>>>>,
+        args: &'a Spanned<Vec<Spanned<Argument>>>,
+        span: &Span,
+    ) -> Result<FunctionBound<'a>, Vec<(Span, CompileErrorType)>> {
+        let signature = self.to_string();
+        let mut errors = Vec::new();
+        let prop_names = names(&self.0.props);
+        let param_names = names(&self.0.params);
+        let prop_values = props.as_ref().map_or(&[][..], |p| &p.inner);
+        let props_span = props.as_ref().map_or(span, |p| &p.span);
+        let props = match_values(prop_values, &prop_names, &signature, &mut errors);
+        let arg_values = match_values(&args.inner, &param_names, &signature, &mut errors);
+
+        missing(&props, &prop_names, props_span, &signature, &mut errors);
+        missing(
+            &arg_values,
+            &param_names,
+            &args.span,
+            &signature,
+            &mut errors,
+        );
+
+        if errors.is_empty() {
+            Ok((
+                props.into_iter().map(Option::unwrap).collect(),
+                arg_values.into_iter().map(Option::unwrap).collect(),
+            ))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn names(names: &[Spanned<String>]) -> Vec<&str> {
+    names.iter().map(|n| n.inner.as_str()).collect()
+}
+
+// 名前のない値は前から順に、名前付きの値は同じ名前のパラメーターに
+fn match_values<'a>(
+    values: &'a [Spanned<Argument>],
+    names: &[&str],
+    signature: &str,
+    errors: &mut Vec<(Span, CompileErrorType)>,
+) -> Vec<Option<&'a Spanned<Expr>>> {
+    let mut matched = vec![None; names.len()];
+    let mut named = false;
+    for (i, value) in values.iter().enumerate() {
+        let index = match &value.name {
+            Some(name) => {
+                named = true;
+                match names.iter().position(|n| *n == name.inner) {
+                    Some(index) => index,
+                    None => {
+                        errors.push((
+                            name.span.clone(),
+                            CompileErrorType::UnknownParameter {
+                                name: name.inner.clone(),
+                                signature: signature.to_owned(),
+                            },
+                        ));
+                        continue;
+                    }
+                }
+            }
+            None if named => {
+                errors.push((value.span.clone(), CompileErrorType::PositionalAfterNamed));
+                continue;
+            }
+            None if i < names.len() => i,
+            None => {
+                errors.push((
+                    value.span.clone(),
+                    CompileErrorType::TooManyArguments {
+                        limit: names.len(),
+                        count: values.len(),
+                        signature: signature.to_owned(),
+                    },
+                ));
+                break;
+            }
+        };
+        if matched[index].is_some() {
+            let span = value.name.as_ref().map_or(&value.span, |n| &n.span);
+            errors.push((
+                span.clone(),
+                CompileErrorType::DuplicateParameter {
+                    name: names[index].to_owned(),
+                    signature: signature.to_owned(),
+                },
+            ));
+            continue;
+        }
+        matched[index] = Some(&value.value);
+    }
+    matched
+}
+
+fn missing<T>(
+    values: &[Option<T>],
+    names: &[&str],
+    span: &Span,
+    signature: &str,
+    errors: &mut Vec<(Span, CompileErrorType)>,
+) {
+    for (_, name) in values.iter().zip(names).filter(|(v, _)| v.is_none()) {
+        errors.push((
+            span.clone(),
+            CompileErrorType::MissingParameter {
+                name: (*name).to_owned(),
+                signature: signature.to_owned(),
+            },
+        ));
+    }
+}
+
+// clamp{min, max}(input) のように表示する
+fn write_signature(
+    f: &mut std::fmt::Formatter<'_>,
+    name: &str,
+    props: &[String],
+    args: &[&str],
+) -> std::fmt::Result {
+    write!(f, "{}", name)?;
+    if !props.is_empty() {
+        write!(f, "{{{}}}", props.join(", "))?;
+    }
+    write!(f, "({})", args.join(", "))
+}
+
+impl<const P: usize, const A: usize> Display for Signature<P, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let props = self
+            .props
+            .iter()
+            .map(|p| match p.default {
+                Some(default) => format!("{} = {:?}", p.name, default),
+                None => p.name.to_owned(),
+            })
+            .collect::<Vec<_>>();
+        write_signature(f, self.name, &props, &self.args)
+    }
+}
+
+impl<'t> Display for FunctionSignature<'t> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let props = self
+            .0
+            .props
+            .iter()
+            .map(|p| p.inner.clone())
+            .collect::<Vec<_>>();
+        write_signature(f, &self.0.name, &props, &names(&self.0.params))
+    }
+}
@@ -0,0 +1,101 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+// 物理量の単位 (記号ごとの指数)
+// 指数が空なら無次元
+#[derive(PartialEq, Eq, Clone, Default, Debug)]
+pub(super) struct Unit(BTreeMap<String, i32>);
+
+impl Unit {
+    pub(super) fn is_dimensionless(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn combine(&self, rhs: &Self, sign: i32) -> Self {
+        let mut exponents = self.0.clone();
+        for (symbol, exponent) in &rhs.0 {
+            let e = exponents.entry(symbol.clone()).or_default();
+            *e += sign * exponent;
+            if *e == 0 {
+                exponents.remove(symbol);
+            }
+        }
+        Self(exponents)
+    }
+
+    pub(super) fn mul(&self, rhs: &Self) -> Self {
+        self.combine(rhs, 1)
+    }
+
+    pub(super) fn div(&self, rhs: &Self) -> Self {
+        self.combine(rhs, -1)
+    }
+}
+
+// "kg*m/s^2" のように記号を * と / でつなぐ
+// 無次元は "1"
+impl FromStr for Unit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut unit = Self::default();
+        let mut sign = 1;
+        let mut rest = s.trim();
+        loop {
+            let end = rest.find(['*', '/']).unwrap_or(rest.len());
+            let term = rest[..end].trim();
+            let (symbol, exponent) = match term.split_once('^') {
+                Some((symbol, exponent)) => {
+                    (symbol.trim(), exponent.trim().parse().map_err(|_| ())?)
+                }
+                None => (term, 1),
+            };
+            if symbol != "1" {
+                if symbol.is_empty() || !symbol.chars().all(char::is_alphabetic) {
+                    return Err(());
+                }
+                unit = unit.combine(&Self(BTreeMap::from([(symbol.to_owned(), exponent)])), sign);
+            }
+
+            let Some(op) = rest[end..].chars().next() else {
+                break;
+            };
+            sign = if op == '*' { 1 } else { -1 };
+            rest = &rest[end + 1..];
+        }
+        Ok(unit)
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let term = |symbol: &str, exponent: i32| {
+            if exponent == 1 {
+                symbol.to_owned()
+            } else {
+                format!("{}^{}", symbol, exponent)
+            }
+        };
+        let numerator = self
+            .0
+            .iter()
+            .filter(|(_, e)| **e > 0)
+            .map(|(s, e)| term(s, *e))
+            .collect::<Vec<_>>();
+        let denominator = self
+            .0
+            .iter()
+            .filter(|(_, e)| **e < 0)
+            .map(|(s, e)| term(s, -e))
+            .collect::<Vec<_>>();
+
+        if numerator.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", numerator.join("*"))?;
+        }
+        for d in denominator {
+            write!(f, "/{}", d)?;
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use crate::{compile_error::CompileErrorType, microcontroller::NodeType};
+
+#[derive(Clone, Debug)]
 pub enum ValueType {
     Bool,
     Int,
@@ -17,6 +19,21 @@ impl ValueType {
             _ => Err(type_name),
         }
     }
+
+    // ノードに使える型 (bool, float) の名前からノードの型へ
+    pub(super) fn node_type(type_name: &str) -> Result<NodeType, CompileErrorType> {
+        match Self::from_str(type_name) {
+            Ok(Self::Bool) => Ok(NodeType::Bool),
+            Ok(Self::Float) => Ok(NodeType::Number),
+            Ok(found_type) => Err(CompileErrorType::IncompatibleType {
+                expected_types: vec![Self::Bool, Self::Float],
+                found_type,
+            }),
+            Err(err) => Err(CompileErrorType::UnknownType {
+                type_name: err.to_owned(),
+            }),
+        }
+    }
 }
 
 impl std::fmt::Display for ValueType {
@@ -0,0 +1,57 @@
+use crate::lexical::Token;
+
+use std::ops::Range;
+
+// 行頭の - + ( が前の行の式の続きとして解析されるもの
+// 継続していない直近の行より深くインデントされていれば意図的な継続とみなす
+pub fn ambiguous_continuations(code: &str, tokens: &[(Token, Range<usize>)]) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut base_indent = 0;
+    let mut prev: Option<&(Token, Range<usize>)> = None;
+    for pair @ (token, span) in tokens {
+        let line_start = prev.is_none_or(|(_, p)| code[p.end..span.start].contains('\n'));
+        if line_start {
+            let continues = prev.is_some_and(|(p, _)| ends_expr(p));
+            match token {
+                Token::Minus | Token::Plus | Token::LParen if continues => {
+                    if indent(code, span.start) <= base_indent {
+                        spans.push(span.clone());
+                    }
+                }
+                Token::Asterisk
+                | Token::Slash
+                | Token::Percent
+                | Token::DoubleAsterisk
+                | Token::Less
+                | Token::Greater => {}
+                _ => base_indent = indent(code, span.start),
+            }
+        }
+        prev = Some(pair);
+    }
+    spans
+}
+
+// 式の最後になりうるトークン
+pub(crate) fn ends_expr(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Ident(_)
+            | Token::Int(_)
+            | Token::Float(_)
+            | Token::Bool(_)
+            | Token::String(_)
+            | Token::Null
+            | Token::RParen
+            | Token::RBracket
+    )
+}
+
+// pos を含む行の先頭の空白の幅
+fn indent(code: &str, pos: usize) -> usize {
+    let line_start = code[..pos].rfind('\n').map_or(0, |i| i + 1);
+    code[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .count()
+}
@@ -0,0 +1,72 @@
+use crate::lexical::Token;
+
+use std::ops::Range;
+
+// 括弧の種類 (ドキュメントコメントを付けられる場所を決める)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Scope {
+    // microcontroller Name {...}
+    Microcontroller,
+    // interface {...}
+    Interface,
+    // inputs {...} と outputs {...} (中の for も含む)
+    Ports,
+    // ノードのプロパティ name: float {...}
+    Properties,
+    Other,
+}
+
+// 何にも付かないドキュメントコメントの位置
+// 付けられるのはマイコン、マイコンの項目、インターフェースのノードとそのプロパティ
+pub fn misplaced_doc_comments(tokens: &[(Token, Range<usize>)]) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut stack = Vec::new();
+    // Ports の中で for から始まる項目を読んでいるか
+    let mut in_for = false;
+    for (i, (token, span)) in tokens.iter().enumerate() {
+        let top = stack.last().copied();
+        match token {
+            Token::DocComment(_) => {
+                let mut rest = tokens[i..]
+                    .iter()
+                    .map(|(t, _)| t)
+                    .filter(|t| !matches!(t, Token::DocComment(_)));
+                let (next, after) = (rest.next(), rest.next());
+                let field = matches!(next, Some(Token::Ident(_))) && after == Some(&Token::Equal);
+                let attached = match top {
+                    None => next == Some(&Token::Microcontroller),
+                    Some(Scope::Microcontroller | Scope::Properties) => field,
+                    Some(Scope::Ports) => matches!(next, Some(Token::Ident(_))),
+                    Some(Scope::Interface | Scope::Other) => false,
+                };
+                if !attached {
+                    spans.push(span.clone());
+                }
+            }
+            Token::LBrace => {
+                let prev = |n: usize| i.checked_sub(n).map(|j| &tokens[j].0);
+                let scope = match (top, prev(1), prev(2)) {
+                    (None, Some(Token::Ident(_)), Some(Token::Microcontroller)) => {
+                        Scope::Microcontroller
+                    }
+                    (Some(Scope::Microcontroller), Some(Token::Interface), _) => Scope::Interface,
+                    (Some(Scope::Interface), Some(Token::Inputs | Token::Outputs), _) => {
+                        Scope::Ports
+                    }
+                    (Some(Scope::Ports), ..) if in_for => Scope::Ports,
+                    (Some(Scope::Ports), ..) => Scope::Properties,
+                    _ => Scope::Other,
+                };
+                in_for = false;
+                stack.push(scope);
+            }
+            Token::LParen | Token::LBracket => stack.push(Scope::Other),
+            Token::RBrace | Token::RParen | Token::RBracket => {
+                stack.pop();
+            }
+            Token::For if top == Some(Scope::Ports) => in_for = true,
+            _ => {}
+        }
+    }
+    spans
+}
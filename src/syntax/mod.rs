@@ -1,6 +1,11 @@
+mod continuation;
+mod doc_comment;
 mod syntax_tree;
 
 use crate::lexical::Token;
+pub use continuation::ambiguous_continuations;
+pub(crate) use continuation::ends_expr;
+pub use doc_comment::misplaced_doc_comments;
 pub use syntax_tree::*;
 
 use chumsky::{
@@ -15,7 +20,7 @@ type Span = std::ops::Range<usize>;
 
 macro_rules! parser_trait {
     ($l: lifetime, $i: ty, $t: ty) => {
-        impl Parser<$l, $i, $t, chumsky::extra::Err<Rich<$l, <$i>::Token, <$i>::Span>>> + Clone
+        impl Parser<$l, $i, $t, chumsky::extra::Err<Rich<$l, <$i>::Token, <$i>::Span>>> + Clone + $l
     };
 }
 
@@ -26,6 +31,38 @@ where
     select! { Token::Ident(v) => v }.labelled("identifier")
 }
 
+fn doc_parser<'src, I>() -> parser_trait!('src, I, Option<String>)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    // 続けて書かれたドキュメントコメントは改行でつなぐ
+    select! { Token::DocComment(v) => v }
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(|lines| (!lines.is_empty()).then(|| lines.join("\n")))
+}
+
+// 後置のアクセス .name と [index]
+enum Postfix {
+    Member(String),
+    Index(Spanned<Expr>),
+}
+
+fn postfix_parser<'src, I>(
+    expr: parser_trait!('src, I, Spanned<Expr>),
+) -> parser_trait!('src, I, Postfix)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    choice((
+        just(Token::Dot)
+            .ignore_then(ident_parser())
+            .map(Postfix::Member),
+        expr.delimited_by(just(Token::LBracket), just(Token::RBracket))
+            .map(Postfix::Index),
+    ))
+}
+
 fn assignment_parser<'src, I>(
     expr: parser_trait!('src, I, Spanned<Expr>),
 ) -> parser_trait!('src, I, Spanned<Assignment>)
@@ -48,20 +85,85 @@ where
         }),
     ))
     .clone()
-    .foldl_with(
-        just(Token::Dot).ignore_then(ident_parser()).repeated(),
-        |lhs, rhs, e| Spanned {
-            inner: AssignmentTarget::FieldAccess(Box::new(lhs), rhs),
+    .foldl_with(postfix_parser(expr.clone()).repeated(), |lhs, rhs, e| {
+        Spanned {
+            inner: match rhs {
+                Postfix::Member(field) => AssignmentTarget::FieldAccess(Box::new(lhs), field),
+                Postfix::Index(index) => AssignmentTarget::Index(Box::new(lhs), Box::new(index)),
+            },
             span: e.span(),
-        },
-    );
+        }
+    });
     assignment_target
         .then_ignore(just(Token::Equal))
         .then(expr.clone())
         .map_with(|(target, value), e| Spanned {
-            inner: Assignment { target, value },
+            inner: Assignment {
+                target,
+                value,
+                doc: None,
+            },
+            span: e.span(),
+        })
+}
+
+// ドキュメントコメントを付けられる代入 (マイコンの項目とノードのプロパティ)
+// ドキュメントコメントは範囲に含めない
+fn documented_assignment_parser<'src, I>(
+    expr: parser_trait!('src, I, Spanned<Expr>),
+) -> parser_trait!('src, I, Spanned<Assignment>)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    doc_parser()
+        .then(assignment_parser(expr))
+        .map(|(doc, mut assignment)| {
+            assignment.inner.doc = doc;
+            assignment
+        })
+}
+
+fn attributes_parser<'src, I>(
+    expr: parser_trait!('src, I, Spanned<Expr>),
+) -> parser_trait!('src, I, Vec<Spanned<Attribute>>)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    // @name(args...)
+    just(Token::At)
+        .ignore_then(ident_parser())
+        .then(
+            expr.separated_by(just(Token::Comma))
+                .allow_trailing()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::LParen), just(Token::RParen))
+                .map_with(|args, e| Spanned {
+                    inner: args,
+                    span: e.span(),
+                }),
+        )
+        .map_with(|(name, args), e| Spanned {
+            inner: Attribute { name, args },
             span: e.span(),
         })
+        .labelled("attribute")
+        .repeated()
+        .collect::<Vec<_>>()
+}
+
+// 属性があれば式を包む
+fn annotate(attrs: Vec<Spanned<Attribute>>, expr: Spanned<Expr>, span: Span) -> Spanned<Expr> {
+    if attrs.is_empty() {
+        expr
+    } else {
+        Spanned {
+            inner: Expr::Annotated {
+                attrs,
+                expr: Box::new(expr),
+            },
+            span,
+        }
+    }
 }
 
 fn statement_parser<'src, I>(
@@ -70,27 +172,71 @@ fn statement_parser<'src, I>(
 where
     I: Input<'src, Token = Token, Span = Span>,
 {
-    // 変数宣言
-    let let_definition = just(Token::Let)
-        .ignore_then(ident_parser())
-        .then_ignore(just(Token::Equal))
-        .then(expr.clone())
-        .map_with(|(name, value), e| Spanned {
-            inner: Statement::Let(name, value),
+    recursive(|statement| {
+        // 変数宣言 (属性は値の式に付ける)
+        let let_definition = attributes_parser(expr.clone())
+            .then_ignore(just(Token::Let))
+            .then(ident_parser())
+            .then_ignore(just(Token::Equal))
+            .then(expr.clone())
+            .map_with(|((attrs, name), value), e| {
+                let span = value.span.clone();
+                Spanned {
+                    inner: Statement::Let(name, annotate(attrs, value, span)),
+                    span: e.span(),
+                }
+            });
+
+        let for_loop = for_parser(expr.clone(), statement).map_with(|for_loop, e| Spanned {
+            inner: Statement::For(for_loop),
             span: e.span(),
         });
 
-    // 文
-    choice((
-        let_definition,
-        assignment_parser(expr).map_with(|assignment, e| Spanned {
-            inner: Statement::Assignment(assignment),
-            span: e.span(),
-        }),
-    ))
+        // 文 (; で終えてもよい)
+        choice((
+            let_definition,
+            for_loop,
+            assignment_parser(expr.clone()).map_with(|assignment, e| Spanned {
+                inner: Statement::Assignment(assignment),
+                span: e.span(),
+            }),
+        ))
+        .then_ignore(just(Token::Semicolon).or_not())
+    })
     .labelled("statement")
 }
 
+fn for_parser<'src, I, T: 'src>(
+    expr: parser_trait!('src, I, Spanned<Expr>),
+    item: parser_trait!('src, I, Spanned<T>),
+) -> parser_trait!('src, I, ForLoop<T>)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    // for name in start..end { body }
+    just(Token::For)
+        .ignore_then(ident_parser().map_with(|name, e| Spanned {
+            inner: name,
+            span: e.span(),
+        }))
+        .then_ignore(just(Token::In))
+        .then(expr.clone())
+        .then_ignore(just(Token::DotDot))
+        .then(expr)
+        .then(
+            item.repeated()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::LBrace), just(Token::RBrace)),
+        )
+        .map(|(((variable, start), end), body)| ForLoop {
+            variable,
+            start,
+            end,
+            body,
+        })
+        .labelled("for")
+}
+
 fn block_parser<'src, I>(
     expr: parser_trait!('src, I, Spanned<Expr>),
 ) -> parser_trait!('src, I, (Vec<Spanned<Statement>>, Option<Spanned<Expr>>))
@@ -104,6 +250,82 @@ where
         .delimited_by(just(Token::LBrace), just(Token::RBrace))
 }
 
+fn lua_parser<'src, I>(
+    expr: parser_trait!('src, I, Spanned<Expr>),
+) -> parser_trait!('src, I, Spanned<Expr>)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    // name: type = expr (入力) / name: type (出力)
+    let port = |value: bool| {
+        ident_parser()
+            .then_ignore(just(Token::Colon))
+            .then(ident_parser())
+            .then(
+                just(Token::Equal)
+                    .ignore_then(expr.clone())
+                    .or_not()
+                    .try_map(move |v, span| match (value, v) {
+                        (true, None) => Err(Rich::custom(span, "Lua input needs `= value`")),
+                        (false, Some(_)) => {
+                            Err(Rich::custom(span, "Lua output cannot have a value"))
+                        }
+                        (_, v) => Ok(v),
+                    }),
+            )
+            .map_with(|((name, type_name), value), e| Spanned {
+                inner: LuaPort {
+                    name,
+                    type_name,
+                    value,
+                },
+                span: e.span(),
+            })
+            .repeated()
+            .collect::<Vec<_>>()
+            .delimited_by(just(Token::LBrace), just(Token::RBrace))
+    };
+
+    let element = choice((
+        just(Token::Inputs)
+            .ignore_then(port(true))
+            .map(LuaElement::Inputs),
+        just(Token::Outputs)
+            .ignore_then(port(false))
+            .map(LuaElement::Outputs),
+        assignment_parser(expr.clone()).map(LuaElement::Field),
+    ))
+    .map_with(|element, e| Spanned {
+        inner: element,
+        span: e.span(),
+    });
+
+    just(Token::Lua)
+        .ignore_then(
+            assignment_parser(expr)
+                .separated_by(just(Token::Comma))
+                .allow_trailing()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::LParen), just(Token::RParen))
+                .map_with(|args, e| Spanned {
+                    inner: args,
+                    span: e.span(),
+                })
+                .or_not(),
+        )
+        .then(
+            element
+                .repeated()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::LBrace), just(Token::RBrace)),
+        )
+        .map_with(|(args, elements), e| Spanned {
+            inner: Expr::Lua { args, elements },
+            span: e.span(),
+        })
+        .labelled("lua block")
+}
+
 fn expr_parser<'src, I>() -> parser_trait!('src, I, Spanned<Expr>)
 where
     I: Input<'src, Token = Token, Span = Span>,
@@ -126,10 +348,22 @@ where
             .clone()
             .delimited_by(just(Token::LParen), just(Token::RParen));
 
-        // 関数呼び出し
+        // 関数呼び出し name{props}(args)
+        let argument = ident_parser()
+            .map_with(|name, e| Spanned {
+                inner: name,
+                span: e.span(),
+            })
+            .then_ignore(just(Token::Equal))
+            .or_not()
+            .then(r_expr.clone())
+            .map_with(|(name, value), e| Spanned {
+                inner: Argument { name, value },
+                span: e.span(),
+            });
         let func_call = ident_parser()
             .then(
-                r_expr
+                argument
                     .clone()
                     .separated_by(just(Token::Comma))
                     .allow_trailing()
@@ -142,8 +376,7 @@ where
                     .or_not(),
             )
             .then(
-                r_expr
-                    .clone()
+                argument
                     .separated_by(just(Token::Comma))
                     .allow_trailing()
                     .collect::<Vec<_>>()
@@ -162,6 +395,79 @@ where
                 span: e.span(),
             });
 
+        // ブロック { statements; value }
+        let block =
+            block_parser(r_expr.clone()).map_with(|(statements, return_value), e| Spanned {
+                inner: Expr::Block {
+                    statements,
+                    return_value: return_value.map(Box::new),
+                },
+                span: e.span(),
+            });
+
+        // if condition { ... } else { ... } (else if も書ける)
+        let if_expr = recursive(|if_expr| {
+            just(Token::If)
+                .ignore_then(r_expr.clone())
+                .then(block.clone())
+                .then_ignore(just(Token::Else))
+                .then(block.clone().or(if_expr))
+                .map_with(|((condition, then), otherwise), e| Spanned {
+                    inner: Expr::If {
+                        condition: Box::new(condition),
+                        then: Box::new(then),
+                        otherwise: Box::new(otherwise),
+                    },
+                    span: e.span(),
+                })
+        });
+
+        // match value { pattern => expr, ... }
+        // -1 と - 1 のどちらも負の数
+        let number = select! {
+            Token::Int(v) => v as f64,
+            Token::Float(v) => v,
+        };
+        let pattern = choice((
+            just(Token::Minus)
+                .ignore_then(number)
+                .map(|v| Pattern::Number(-v)),
+            number.map(Pattern::Number),
+            select! { Token::Ident(v) if v == "_" => Pattern::Wildcard },
+            ident_parser()
+                .separated_by(just(Token::Dot))
+                .at_least(1)
+                .collect::<Vec<_>>()
+                .map(Pattern::Name),
+        ))
+        .map_with(|pattern, e| Spanned {
+            inner: pattern,
+            span: e.span(),
+        })
+        .labelled("pattern");
+        let arm = pattern
+            .then_ignore(just(Token::FatArrow))
+            .then(r_expr.clone())
+            .map_with(|(pattern, value), e| Spanned {
+                inner: MatchArm { pattern, value },
+                span: e.span(),
+            });
+        let match_expr = just(Token::Match)
+            .ignore_then(r_expr.clone())
+            .then(
+                arm.separated_by(just(Token::Comma))
+                    .allow_trailing()
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::LBrace), just(Token::RBrace)),
+            )
+            .map_with(|(value, arms), e| Spanned {
+                inner: Expr::Match {
+                    value: Box::new(value),
+                    arms,
+                },
+                span: e.span(),
+            });
+
         let atom = choice((
             literal,
             just(Token::Inputs).map_with(|_, e| Spanned {
@@ -173,46 +479,78 @@ where
                 span: e.span(),
             }),
             func_call,
+            lua_parser(r_expr.clone()),
             ident_parser().map_with(|name, e| Spanned {
                 inner: Expr::Ident(name),
                 span: e.span(),
             }),
+            if_expr,
+            match_expr,
             parenthesized,
-            block_parser(r_expr).map_with(|(statements, return_value), e| Spanned {
-                inner: Expr::Block {
-                    statements,
-                    return_value: return_value.map(Box::new),
-                },
-                span: e.span(),
-            }),
+            block,
         ));
 
-        // メンバーアクセス
-        let member_access = atom.clone().foldl_with(
-            just(Token::Dot).ignore_then(ident_parser()).repeated(),
-            |lhs, rhs, e| Spanned {
-                inner: Expr::MemberAccess(Box::new(lhs), rhs),
-                span: e.span(),
-            },
-        );
+        // メンバーアクセスと添字
+        let member_access =
+            atom.clone()
+                .foldl_with(postfix_parser(r_expr.clone()).repeated(), |lhs, rhs, e| {
+                    Spanned {
+                        inner: match rhs {
+                            Postfix::Member(field) => Expr::MemberAccess(Box::new(lhs), field),
+                            Postfix::Index(index) => Expr::Index(Box::new(lhs), Box::new(index)),
+                        },
+                        span: e.span(),
+                    }
+                });
 
         // 単項演算 (-)
-        let unary = just(Token::Minus)
-            .repeated()
-            .foldr_with(member_access, |_op, rhs, e| Spanned {
-                inner: Expr::UnaryOp(UnaryOp::Neg(Box::new(rhs))),
-                span: e.span(),
-            });
+        let negation = |operand| {
+            just(Token::Minus)
+                .repeated()
+                .foldr_with(operand, |_op, rhs, e| Spanned {
+                    inner: Expr::UnaryOp(UnaryOp::Neg(Box::new(rhs))),
+                    span: e.span(),
+                })
+        };
 
-        // 二項演算 (* /)
+        // 累乗 (右結合で、左辺の単項マイナスより強く結びつく)
+        let power = recursive(|power| {
+            member_access
+                .clone()
+                .then(
+                    just(Token::DoubleAsterisk)
+                        .ignore_then(negation(power))
+                        .or_not(),
+                )
+                .map_with(|(lhs, rhs), e| match rhs {
+                    Some(rhs) => Spanned {
+                        inner: Expr::BinaryOp(BinaryOp::Pow(Box::new(lhs), Box::new(rhs))),
+                        span: e.span(),
+                    },
+                    None => lhs,
+                })
+        });
+        let negation = negation(power);
+
+        // 属性 @pos(...) など
+        let unary = attributes_parser(r_expr.clone())
+            .then(negation)
+            .map_with(|(attrs, expr), e| annotate(attrs, expr, e.span()));
+
+        // 二項演算 (* / %)
         let binary_1 = unary.clone().foldl_with(
-            choice((just(Token::Asterisk), just(Token::Slash)))
-                .then(unary)
-                .repeated(),
+            choice((
+                just(Token::Asterisk),
+                just(Token::Slash),
+                just(Token::Percent),
+            ))
+            .then(unary)
+            .repeated(),
             |lhs, (op, rhs), e| Spanned {
                 inner: match op {
                     Token::Asterisk => Expr::BinaryOp(BinaryOp::Mul(Box::new(lhs), Box::new(rhs))),
                     Token::Slash => Expr::BinaryOp(BinaryOp::Div(Box::new(lhs), Box::new(rhs))),
+                    Token::Percent => Expr::BinaryOp(BinaryOp::Mod(Box::new(lhs), Box::new(rhs))),
                     _ => unreachable!(),
                 },
                 span: e.span(),
@@ -234,8 +572,30 @@ where
             },
         );
 
+        // 比較 (< >) は連ねられない
+        let comparison = binary_2
+            .clone()
+            .then(
+                choice((just(Token::Less), just(Token::Greater)))
+                    .then(binary_2)
+                    .or_not(),
+            )
+            .map_with(|(lhs, rhs), e| match rhs {
+                Some((op, rhs)) => Spanned {
+                    inner: match op {
+                        Token::Less => Expr::BinaryOp(BinaryOp::Lt(Box::new(lhs), Box::new(rhs))),
+                        Token::Greater => {
+                            Expr::BinaryOp(BinaryOp::Gt(Box::new(lhs), Box::new(rhs)))
+                        }
+                        _ => unreachable!(),
+                    },
+                    span: e.span(),
+                },
+                None => lhs,
+            });
+
         // タプル
-        let tuple = binary_2
+        let tuple = comparison
             .clone()
             .separated_by(just(Token::Comma))
             .collect::<Vec<_>>()
@@ -246,7 +606,7 @@ where
             })
             .labelled("tuple");
 
-        tuple.or(binary_2)
+        tuple.or(comparison)
     })
     .labelled("expression")
 }
@@ -257,31 +617,63 @@ fn interface_parser<'src, I>(
 where
     I: Input<'src, Token = Token, Span = Span>,
 {
-    // name: type { field = expr }
-    let interface_node = ident_parser()
-        .then_ignore(just(Token::Colon))
-        .then(ident_parser())
-        .then(
-            assignment_parser(expr)
-                .repeated()
-                .collect::<Vec<_>>()
-                .delimited_by(just(Token::LBrace), just(Token::RBrace))
-                .or_not(),
-        )
-        .map_with(|((name, type_name), fields), e| Spanned {
-            inner: MicrocontrollerInterfaceNode {
+    // name: type { field = expr } / name: type[length] { field = expr }
+    // name[index]: type { field = expr } は配列の要素を 1 つ宣言する
+    // ドキュメントコメントはノードの範囲に含めない
+    let index = || {
+        expr.clone()
+            .delimited_by(just(Token::LBracket), just(Token::RBracket))
+    };
+    let interface_node = doc_parser().then(
+        ident_parser()
+            .then(choice((
+                index()
+                    .then_ignore(just(Token::Colon))
+                    .then(ident_parser())
+                    .map(|(index, type_name)| (Some(index), type_name, None)),
+                just(Token::Colon)
+                    .ignore_then(ident_parser())
+                    .then(index().or_not())
+                    .map(|(type_name, length)| (None, type_name, length)),
+            )))
+            .then(
+                documented_assignment_parser(expr.clone())
+                    .repeated()
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::LBrace), just(Token::RBrace))
+                    .or_not(),
+            )
+            .map_with(|((name, (index, type_name, length)), fields), e| {
+                (name, index, type_name, length, fields, e.span())
+            }),
+    );
+    let interface_node = interface_node.map(
+        |(doc, (name, index, type_name, length, fields, span))| Spanned {
+            inner: InterfaceItem::Node(MicrocontrollerInterfaceNode {
                 name,
+                index,
                 type_name,
+                length,
                 fields,
-            },
+                doc,
+            }),
+            span,
+        },
+    );
+
+    // ノードと for name in start..end {...}
+    let item = recursive(|item| {
+        let for_loop = for_parser(expr, item).map_with(|for_loop, e| Spanned {
+            inner: InterfaceItem::For(for_loop),
             span: e.span(),
         });
+        choice((for_loop, interface_node))
+    });
 
     // inputs {...}
     let inputs = just(Token::Inputs)
         .ignore_then(
-            interface_node
-                .clone()
+            item.clone()
                 .repeated()
                 .collect::<Vec<_>>()
                 .delimited_by(just(Token::LBrace), just(Token::RBrace)),
@@ -295,9 +687,7 @@ where
     // outputs {...}
     let outputs = just(Token::Outputs)
         .ignore_then(
-            interface_node
-                .clone()
-                .repeated()
+            item.repeated()
                 .collect::<Vec<_>>()
                 .delimited_by(just(Token::LBrace), just(Token::RBrace)),
         )
@@ -322,6 +712,63 @@ where
         .labelled("interface")
 }
 
+fn function_parser<'src, I>(
+    expr: parser_trait!('src, I, Spanned<Expr>),
+) -> parser_trait!('src, I, Function)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    let names = || {
+        ident_parser()
+            .map_with(|name, e| Spanned {
+                inner: name,
+                span: e.span(),
+            })
+            .separated_by(just(Token::Comma))
+            .allow_trailing()
+            .collect::<Vec<_>>()
+    };
+
+    // fn name{props}(params) = body (; で終えてもよい)
+    just(Token::Fn)
+        .ignore_then(ident_parser())
+        .then(
+            names()
+                .delimited_by(just(Token::LBrace), just(Token::RBrace))
+                .or_not(),
+        )
+        .then(names().delimited_by(just(Token::LParen), just(Token::RParen)))
+        .then_ignore(just(Token::Equal))
+        .then(expr)
+        .then_ignore(just(Token::Semicolon).or_not())
+        .map(|(((name, props), params), body)| Function {
+            name,
+            props: props.unwrap_or_default(),
+            params,
+            body,
+        })
+        .labelled("function")
+}
+
+fn constant_parser<'src, I>(
+    expr: parser_trait!('src, I, Spanned<Expr>),
+) -> parser_trait!('src, I, Constant)
+where
+    I: Input<'src, Token = Token, Span = Span>,
+{
+    // const NAME = value (; で終えてもよい)
+    just(Token::Const)
+        .ignore_then(ident_parser().map_with(|name, e| Spanned {
+            inner: name,
+            span: e.span(),
+        }))
+        .then_ignore(just(Token::Equal))
+        .then(expr)
+        .then_ignore(just(Token::Semicolon).or_not())
+        .map(|(name, value)| Constant { name, value })
+        .labelled("constant")
+}
+
 pub fn parser<'src, I>() -> parser_trait!('src, I, Spanned<File>)
 where
     I: Input<'src, Token = Token, Span = Span>,
@@ -347,28 +794,56 @@ where
         .labelled("logic");
 
     // microcontroller Name {...}
-    let microcontroller = just(Token::Microcontroller)
-        .ignore_then(ident_parser())
+    let microcontroller = doc_parser()
         .then(
-            choice((
-                assignment_parser(expr).map_with(|assignment, e| Spanned {
-                    inner: MicrocontrollerElement::Field(assignment),
-                    span: e.span(),
-                }),
-                interface,
-                logic,
-            ))
-            .repeated()
-            .collect::<Vec<_>>()
-            .delimited_by(just(Token::LBrace), just(Token::RBrace)),
+            just(Token::Microcontroller)
+                .ignore_then(ident_parser())
+                .then(
+                    choice((
+                        documented_assignment_parser(expr.clone()).map(|assignment| Spanned {
+                            span: assignment.span.clone(),
+                            inner: MicrocontrollerElement::Field(assignment),
+                        }),
+                        interface,
+                        logic,
+                        function_parser(expr.clone()).map_with(|function, e| Spanned {
+                            inner: MicrocontrollerElement::Function(function),
+                            span: e.span(),
+                        }),
+                        constant_parser(expr.clone()).map_with(|constant, e| Spanned {
+                            inner: MicrocontrollerElement::Constant(constant),
+                            span: e.span(),
+                        }),
+                    ))
+                    .repeated()
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::LBrace), just(Token::RBrace)),
+                )
+                .map_with(|(name, elements), e| (name, elements, e.span())),
         )
-        .map_with(|(name, elements), e| Spanned {
-            inner: Element::Microcontroller { name, elements },
-            span: e.span(),
+        .map(|(doc, (name, elements, span))| Spanned {
+            inner: Element::Microcontroller {
+                name,
+                doc,
+                elements,
+            },
+            span,
         })
         .labelled("microcontroller");
 
-    let element = microcontroller.labelled("element");
+    // fn name(params) = body
+    let function = function_parser(expr.clone()).map_with(|function, e| Spanned {
+        inner: Element::Function(function),
+        span: e.span(),
+    });
+
+    // const NAME = value
+    let constant = constant_parser(expr).map_with(|constant, e| Spanned {
+        inner: Element::Constant(constant),
+        span: e.span(),
+    });
+
+    let element = choice((microcontroller, function, constant)).labelled("element");
 
     element.repeated().collect().map_with(|el, e| Spanned {
         inner: File { elements: el },
@@ -13,6 +13,10 @@ pub enum BinaryOp {
     Sub(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     Mul(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     Div(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Mod(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Pow(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Lt(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Gt(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
 }
 
 #[derive(Debug)]
@@ -20,6 +24,36 @@ pub enum UnaryOp {
     Neg(Box<Spanned<Expr>>),
 }
 
+// @pos(10, -4) などのレイアウト指定
+#[derive(Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub args: Spanned<Vec<Spanned<Expr>>>,
+}
+
+// 関数呼び出しのプロパティと引数 (名前付きなら name = value)
+#[derive(Debug)]
+pub struct Argument {
+    pub name: Option<Spanned<String>>,
+    pub value: Spanned<Expr>,
+}
+
+// match の分岐のパターン (数値、定数の名前か _)
+// 列挙型はないので Mode.A のような名前は解析時にエラーにする
+#[derive(Debug)]
+pub enum Pattern {
+    Number(f64),
+    // . で区切った名前
+    Name(Vec<String>),
+    Wildcard,
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Spanned<Pattern>,
+    pub value: Spanned<Expr>,
+}
+
 #[derive(Debug)]
 pub enum Expr {
     Null,
@@ -32,17 +66,39 @@ pub enum Expr {
     Outputs,
     Tuple(Vec<Spanned<Expr>>),
     MemberAccess(Box<Spanned<Expr>>, String),
+    // inputs.name[i]
+    Index(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     BinaryOp(BinaryOp),
     UnaryOp(UnaryOp),
     FunctionCall {
         ident: String,
-        props: Option<Spanned<Vec<Spanned<Expr>>>>,
-        args: Spanned<Vec<Spanned<Expr>>>,
+        props: Option<Spanned<Vec<Spanned<Argument>>>>,
+        args: Spanned<Vec<Spanned<Argument>>>,
     },
     Block {
         statements: Vec<Spanned<Statement>>,
         return_value: Option<Box<Spanned<Expr>>>,
     },
+    // match value { 0 => a, 1 => b, _ => c }
+    Match {
+        value: Box<Spanned<Expr>>,
+        arms: Vec<Spanned<MatchArm>>,
+    },
+    // if condition { then } else { otherwise } (else if は otherwise に入れ子にする)
+    If {
+        condition: Box<Spanned<Expr>>,
+        then: Box<Spanned<Expr>>,
+        otherwise: Box<Spanned<Expr>>,
+    },
+    Annotated {
+        attrs: Vec<Spanned<Attribute>>,
+        expr: Box<Spanned<Expr>>,
+    },
+    // lua(file = "x.lua") { inputs {...} outputs {...} }
+    Lua {
+        args: Option<Spanned<Vec<Spanned<Assignment>>>>,
+        elements: Vec<Spanned<LuaElement>>,
+    },
 }
 
 #[derive(Debug)]
@@ -51,31 +107,88 @@ pub enum AssignmentTarget {
     Inputs,
     Outputs,
     FieldAccess(Box<Spanned<AssignmentTarget>>, String),
+    Index(Box<Spanned<AssignmentTarget>>, Box<Spanned<Expr>>),
 }
 
 #[derive(Debug)]
 pub struct Assignment {
     pub target: Spanned<AssignmentTarget>,
     pub value: Spanned<Expr>,
+    // 直前のドキュメントコメント (マイコンの項目とノードのプロパティだけ)
+    pub doc: Option<String>,
+}
+
+// for name in start..end { body } (解析時に展開する)
+#[derive(Debug)]
+pub struct ForLoop<T> {
+    pub variable: Spanned<String>,
+    pub start: Spanned<Expr>,
+    pub end: Spanned<Expr>,
+    pub body: Vec<Spanned<T>>,
 }
 
 #[derive(Debug)]
 pub enum Statement {
     Let(String, Spanned<Expr>),
     Assignment(Spanned<Assignment>),
+    For(ForLoop<Statement>),
+}
+
+// Lua ブロックの入出力 (入力は値を持つ)
+#[derive(Debug)]
+pub struct LuaPort {
+    pub name: String,
+    pub type_name: String,
+    pub value: Option<Spanned<Expr>>,
+}
+
+#[derive(Debug)]
+pub enum LuaElement {
+    Inputs(Vec<Spanned<LuaPort>>),
+    Outputs(Vec<Spanned<LuaPort>>),
+    Field(Spanned<Assignment>),
 }
 
 #[derive(Debug)]
 pub struct MicrocontrollerInterfaceNode {
     pub name: String,
+    // 配列の要素を 1 つずつ宣言するなら添字 (name[i]: float)
+    pub index: Option<Spanned<Expr>>,
     pub type_name: String,
+    // 配列なら要素数 (float[8])
+    pub length: Option<Spanned<Expr>>,
     pub fields: Option<Vec<Spanned<Assignment>>>,
+    // 直前のドキュメントコメント
+    pub doc: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum InterfaceItem {
+    Node(MicrocontrollerInterfaceNode),
+    For(ForLoop<InterfaceItem>),
 }
 
 #[derive(Debug)]
 pub enum MicrocontrollerInterface {
-    Inputs(Vec<Spanned<MicrocontrollerInterfaceNode>>),
-    Outputs(Vec<Spanned<MicrocontrollerInterfaceNode>>),
+    Inputs(Vec<Spanned<InterfaceItem>>),
+    Outputs(Vec<Spanned<InterfaceItem>>),
+}
+
+// fn name{props}(params) = body
+// props はコンパイル時の定数
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub props: Vec<Spanned<String>>,
+    pub params: Vec<Spanned<String>>,
+    pub body: Spanned<Expr>,
+}
+
+// const NAME = value (コンパイル時に求める)
+#[derive(Debug)]
+pub struct Constant {
+    pub name: Spanned<String>,
+    pub value: Spanned<Expr>,
 }
 
 #[derive(Debug)]
@@ -83,12 +196,17 @@ pub enum MicrocontrollerElement {
     Field(Spanned<Assignment>),
     Interface(Vec<Spanned<MicrocontrollerInterface>>),
     Logic(Vec<Spanned<Statement>>),
+    Function(Function),
+    Constant(Constant),
 }
 
 #[derive(Debug)]
 pub enum Element {
+    Function(Function),
+    Constant(Constant),
     Microcontroller {
         name: String,
+        doc: Option<String>,
         elements: Vec<Spanned<MicrocontrollerElement>>,
     },
 }
@@ -1,12 +1,12 @@
 use super::ComponentPos;
 
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 
 #[derive(Default, Clone, Debug)]
 pub struct ComponentObject {
     pub id: u32,
-    pub attrs: Option<HashMap<String, String>>,
+    pub attrs: Option<BTreeMap<String, String>>,
 
     pub pos: Option<ComponentPos>,
     pub inc: Option<ObjectInput>,
@@ -36,7 +36,7 @@ impl<'de> Deserialize<'de> for ComponentObject {
                 A: MapAccess<'de>,
             {
                 let mut id = None;
-                let mut attrs = HashMap::new();
+                let mut attrs = BTreeMap::new();
 
                 let mut pos = None;
                 let mut inc = None;
@@ -125,7 +125,11 @@ impl Serialize for ComponentObject {
         map.serialize_entry("@id", &self.id)?;
         if let Some(attrs) = &self.attrs {
             for (k, v) in attrs {
-                map.serialize_entry(k, v)?;
+                if k.starts_with('@') {
+                    map.serialize_entry(k, v)?;
+                } else {
+                    map.serialize_entry(&format!("@{}", k), v)?;
+                }
             }
         }
 
@@ -169,6 +173,9 @@ pub enum ObjectValueTag {
     Min,
     Max,
     Int,
+    Kp,
+    Ki,
+    Kd,
 }
 
 impl TryFrom<&str> for ObjectValueTag {
@@ -184,6 +191,9 @@ impl TryFrom<&str> for ObjectValueTag {
             "min" => Ok(Self::Min),
             "max" => Ok(Self::Max),
             "int" => Ok(Self::Int),
+            "kp" => Ok(Self::Kp),
+            "ki" => Ok(Self::Ki),
+            "kd" => Ok(Self::Kd),
             _ => Err("unknown object value tag"),
         }
     }
@@ -200,6 +210,9 @@ impl ObjectValueTag {
             Self::Min => "min",
             Self::Max => "max",
             Self::Int => "int",
+            Self::Kp => "kp",
+            Self::Ki => "ki",
+            Self::Kd => "kd",
         }
     }
 }
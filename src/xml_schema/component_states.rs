@@ -1,13 +1,23 @@
-use super::ComponentObject;
+use super::{ComponentObject, GroupItem};
 
 use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
 
-#[derive(Default, Clone, Debug)]
-pub struct ComponentStates {
-    pub c: Vec<(String, ComponentObject)>,
+// <c0>, <c1>, ... のように番号付きのタグが並ぶ要素
+#[derive(Clone, Debug)]
+pub struct ComponentStates<T = ComponentObject> {
+    pub c: Vec<(String, T)>,
 }
 
-impl<'de> Deserialize<'de> for ComponentStates {
+pub type GroupStates = ComponentStates<GroupItem>;
+
+impl<T> Default for ComponentStates<T> {
+    fn default() -> Self {
+        Self { c: Vec::new() }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ComponentStates<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
@@ -15,13 +25,13 @@ impl<'de> Deserialize<'de> for ComponentStates {
         use serde::de::{MapAccess, Visitor};
         use std::fmt;
 
-        struct ParentVisitor;
+        struct ParentVisitor<T>(PhantomData<T>);
 
-        impl<'de> Visitor<'de> for ParentVisitor {
-            type Value = ComponentStates;
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for ParentVisitor<T> {
+            type Value = ComponentStates<T>;
 
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                f.write_str("a map of XML elements under <ComponentStates>")
+                f.write_str("a map of numbered XML elements")
             }
 
             fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
@@ -30,7 +40,7 @@ impl<'de> Deserialize<'de> for ComponentStates {
             {
                 let mut c = Vec::new();
 
-                while let Some((key, value)) = map.next_entry::<String, ComponentObject>()? {
+                while let Some((key, value)) = map.next_entry::<String, T>()? {
                     c.push((key, value));
                 }
 
@@ -38,11 +48,11 @@ impl<'de> Deserialize<'de> for ComponentStates {
             }
         }
 
-        deserializer.deserialize_map(ParentVisitor)
+        deserializer.deserialize_map(ParentVisitor(PhantomData))
     }
 }
 
-impl Serialize for ComponentStates {
+impl<T: Serialize> Serialize for ComponentStates<T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
@@ -1,12 +1,16 @@
 use super::{
-    Attrs, ComponentStates, Components, Group, Microprocessor, Node, NodeItem, NodePos, Nodes,
+    Attrs, ComponentStates, Components, Group, GroupItem, GroupMember, GroupStates, Groups,
+    Microprocessor, Node, NodeItem, NodePos, Nodes,
 };
 use crate::{
-    microcontroller::{self, ComponentData as _, LinkNode, PositionedMicrocontroller},
+    microcontroller::{
+        self, ComponentData as _, ComponentPosition, LinkIndex, LinkNode,
+        PositionedMicrocontroller, StableId, option_component_pos,
+    },
     xml_schema::component_object::ObjectInput,
 };
 
-use std::{collections::HashMap, rc::Rc};
+use std::collections::BTreeMap;
 
 // デフォルトなら None 、それ以外なら Some でラップ
 fn to_option<T: PartialEq>(value: T, default: T) -> Option<T> {
@@ -46,55 +50,68 @@ pub enum MicroprocessorConversionError {
     UnknownInputComponent,
 }
 
-// コンポーネントのIDをカウント、割り当て、リンクからID取得
-#[derive(Default, Debug)]
+impl std::fmt::Display for MicroprocessorConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownInputNode => write!(f, "A link refers to a node that does not exist"),
+            Self::UnknownInputComponent => {
+                write!(f, "A link refers to a component that does not exist")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MicroprocessorConversionError {}
+
+// ノード、コンポーネントの順に並び順で 1 から ID を割り当てる
+#[derive(Debug)]
 struct ComponentIdManager {
-    id_counter: u32,
-    input_node_id_map: HashMap<usize, u32>,
-    id_map: HashMap<usize, u32>,
+    num_nodes: usize,
+    num_components: usize,
+    index: LinkIndex,
 }
 
 impl ComponentIdManager {
-    fn generate_id(&mut self) -> u32 {
-        self.id_counter += 1;
-        self.id_counter
+    fn new(mc: &PositionedMicrocontroller) -> Self {
+        Self {
+            num_nodes: mc.nodes.len(),
+            num_components: mc.components.len(),
+            index: LinkIndex::new(
+                mc.nodes.iter().map(|n| &n.inner),
+                mc.components.iter().map(|c| &c.inner),
+            ),
+        }
     }
 
-    fn add_node(&mut self, node: &microcontroller::Node) -> u32 {
-        let id = self.generate_id();
-        if let microcontroller::Node::Input(n) = node {
-            self.input_node_id_map.insert(Rc::as_ptr(n) as usize, id);
-        }
-        id
+    fn node_id(&self, i: usize) -> u32 {
+        i as u32 + 1
     }
 
-    fn add(&mut self, component: &Rc<microcontroller::Component>) -> u32 {
-        let id = self.generate_id();
-        self.id_map.insert(Rc::as_ptr(component) as usize, id);
-        id
+    fn component_id(&self, i: usize) -> u32 {
+        (self.num_nodes + i) as u32 + 1
+    }
+
+    fn id_counter(&self) -> u32 {
+        (self.num_nodes + self.num_components) as u32
     }
 
     fn get_object_input(
         &self,
         link: &LinkNode,
     ) -> Result<ObjectInput, MicroprocessorConversionError> {
-        match link {
-            LinkNode::Node(n) => Ok(ObjectInput {
-                component_id: self
-                    .input_node_id_map
-                    .get(&(n.as_ptr() as usize))
-                    .map(|value: &u32| option_u32(*value))
-                    .ok_or(MicroprocessorConversionError::UnknownInputNode)?,
+        match (link, self.index.get(link)) {
+            (LinkNode::Node(_), Some(StableId::Node(i))) => Ok(ObjectInput {
+                component_id: option_u32(self.node_id(i)),
                 node_index: None,
             }),
-            LinkNode::Component(c, i) => Ok(ObjectInput {
-                component_id: self
-                    .id_map
-                    .get(&(c.as_ptr() as usize))
-                    .map(|value: &u32| option_u32(*value))
-                    .ok_or(MicroprocessorConversionError::UnknownInputComponent)?,
-                node_index: option_usize(*i),
+            (LinkNode::Component(_, index), Some(StableId::Component(i))) => Ok(ObjectInput {
+                component_id: option_u32(self.component_id(i)),
+                node_index: option_usize(*index),
             }),
+            (LinkNode::Node(_), _) => Err(MicroprocessorConversionError::UnknownInputNode),
+            (LinkNode::Component(..), _) => {
+                Err(MicroprocessorConversionError::UnknownInputComponent)
+            }
         }
     }
 }
@@ -108,21 +125,15 @@ impl TryFrom<&PositionedMicrocontroller> for Microprocessor {
 
         let mut node_id_counter = 0;
 
-        // コンポーネントへの Weak 参照からIDを取得
-        let mut id_manager = ComponentIdManager {
-            id_counter: 0,
-            input_node_id_map: HashMap::new(),
-            id_map: HashMap::with_capacity(num_components),
-        };
+        // リンク先からIDを取得
+        let id_manager = ComponentIdManager::new(value);
 
         // マイコン入出力ノード
         let mut nodes = Vec::with_capacity(num_nodes);
         let mut node_components = Vec::with_capacity(num_nodes);
-        for node in &value.nodes {
+        for (i, node) in value.nodes.iter().enumerate() {
             node_id_counter += 1;
-
-            // InputNode へのポインタ -> ID を記録
-            let id = id_manager.add_node(&node.inner);
+            let id = id_manager.node_id(i);
 
             // <nodes> に追加
             nodes.push(NodeItem {
@@ -143,9 +154,8 @@ impl TryFrom<&PositionedMicrocontroller> for Microprocessor {
 
         // コンポーネント
         let mut components = Vec::with_capacity(num_components);
-        for component in &value.components {
-            // Component へのポインタ -> ID を記録
-            let id = id_manager.add(&component.inner);
+        for (i, component) in value.components.iter().enumerate() {
+            let id = id_manager.component_id(i);
 
             // <components> に追加
             components.push(component.to_xml_item(id));
@@ -191,12 +201,20 @@ impl TryFrom<&PositionedMicrocontroller> for Microprocessor {
             component_bridge_states.push((format!("c{}", i), item.object.clone()));
         }
 
+        // レイアウトの指示
+        let groups = groups(value, &id_manager);
+        let group_states = groups
+            .iter()
+            .enumerate()
+            .map(|(i, g)| (format!("g{}", i), g.clone()))
+            .collect();
+
         Ok(Self {
             name: option_string(value.name.clone()),
             description: option_string(value.description.clone()),
             width: value.width,
             length: value.length,
-            id_counter: id_manager.id_counter,
+            id_counter: id_manager.id_counter(),
             id_counter_node: node_id_counter,
             attrs: Attrs::default(),
             nodes: Nodes { n: nodes },
@@ -209,8 +227,53 @@ impl TryFrom<&PositionedMicrocontroller> for Microprocessor {
                 component_bridge_states: ComponentStates {
                     c: component_bridge_states,
                 },
+                groups: Groups { g: groups },
+                group_states: GroupStates { c: group_states },
                 ..Default::default()
             },
         })
     }
 }
+
+// @group ごとに 1 つ、グループに属さない @note はコンポーネントごとに 1 つ
+fn groups(mc: &PositionedMicrocontroller, id_manager: &ComponentIdManager) -> Vec<GroupItem> {
+    let mut named = BTreeMap::<&str, Vec<usize>>::new();
+    let mut notes = Vec::new();
+    for (&i, hint) in mc.hints.range(..mc.components.len()) {
+        match &hint.group {
+            Some(name) => named.entry(name).or_default().push(i),
+            None if hint.note.is_some() => notes.push((None, vec![i])),
+            None => {}
+        }
+    }
+
+    named
+        .into_iter()
+        .map(|(name, members)| (Some(name), members))
+        .chain(notes)
+        .map(|(name, members)| {
+            let note = members
+                .iter()
+                .find_map(|i| mc.hints.get(i).and_then(|h| h.note.clone()));
+            // メンバーを囲む範囲の左上
+            let left = members.iter().map(|i| mc.components[*i].position.x).min();
+            let top = members
+                .iter()
+                .map(|i| mc.components[*i].position.y + mc.components[*i].height() as i32)
+                .max();
+            GroupItem {
+                name: name.map(str::to_owned),
+                note,
+                pos: left
+                    .zip(top)
+                    .and_then(|(x, y)| option_component_pos(ComponentPosition::new(x, y))),
+                c: members
+                    .iter()
+                    .map(|i| GroupMember {
+                        id: id_manager.component_id(*i),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
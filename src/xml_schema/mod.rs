@@ -3,11 +3,43 @@ mod component_object;
 mod component_states;
 pub mod conversion;
 pub use attrs::Attrs;
-pub use component_object::{ComponentObject, ObjectValue, ObjectValueTag};
-pub use component_states::ComponentStates;
+pub use component_object::{ComponentObject, ObjectInput, ObjectValue, ObjectValueTag};
+pub use component_states::{ComponentStates, GroupStates};
 
 use serde::{Deserialize, Serialize};
 
+// 属性値の改行やタブは読み込み時に空白へ正規化されるので文字参照にする
+// シリアライザは要素の間に空白を出力しないため、出力全体を置換してよい
+pub fn escape_whitespace(xml: &str) -> String {
+    let mut escaped = String::with_capacity(xml.len());
+    for c in xml.chars() {
+        match c {
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 属性値として書き出したときの文字数
+pub fn escaped_len(value: &str) -> usize {
+    value
+        .chars()
+        .map(|c| match c {
+            '&' => "&amp;".len(),
+            '<' => "&lt;".len(),
+            '>' => "&gt;".len(),
+            '"' => "&quot;".len(),
+            '\n' => "&#10;".len(),
+            '\r' => "&#13;".len(),
+            '\t' => "&#9;".len(),
+            _ => 1,
+        })
+        .sum()
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(rename = "microprocessor")]
 pub struct Microprocessor {
@@ -102,11 +134,32 @@ pub struct GroupDataOutputs;
 
 #[derive(Deserialize, Serialize, Default, Clone, Debug)]
 #[serde(deny_unknown_fields)]
-pub struct Groups;
+pub struct Groups {
+    #[serde(default)]
+    pub g: Vec<GroupItem>,
+}
 
+// @group, @note でまとめたコンポーネント
 #[derive(Deserialize, Serialize, Default, Clone, Debug)]
 #[serde(deny_unknown_fields)]
-pub struct GroupStates;
+pub struct GroupItem {
+    #[serde(rename = "@name", skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "@note", skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<ComponentPos>,
+    #[serde(default)]
+    pub c: Vec<GroupMember>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GroupMember {
+    #[serde(rename = "@id")]
+    pub id: u32,
+}
 
 #[derive(Deserialize, Serialize, Default, Clone, Debug)]
 #[serde(deny_unknown_fields)]
@@ -0,0 +1,175 @@
+use sw_miconlang::project::{BuildError, BuildOptions, build, manifest_paths};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// テストごとに空の作業ディレクトリを作る
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("miconlang-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn source(name: &str) -> String {
+    format!(
+        "microcontroller {} {{\n    interface {{\n        inputs {{ a: float }}\n        outputs {{ b: float }}\n    }}\n    logic {{ outputs.b = inputs.a * 2 }}\n}}\n",
+        name
+    )
+}
+
+fn write_manifest(root: &Path, sources: &[&str]) {
+    let sources = sources
+        .iter()
+        .map(|s| format!("\"{}\"", s))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let manifest = format!(
+        "[build]\nsources = [{}]\nout_dir = \"out\"\n\n[export]\nmicroprocessors_dir = \"game\"\n",
+        sources
+    );
+    fs::write(root.join("miconlang.toml"), manifest).unwrap();
+}
+
+fn xml_files(dir: &Path) -> Vec<String> {
+    let mut names = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".xml"))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn rewrites_only_changed_outputs() {
+    let root = temp_dir("rewrite");
+    write_manifest(&root, &["a.mcl", "b.mcl"]);
+    fs::write(root.join("a.mcl"), source("A")).unwrap();
+    fs::write(root.join("b.mcl"), source("B")).unwrap();
+    let manifest = root.join("miconlang.toml");
+
+    let summary = build(&manifest, &BuildOptions::default()).unwrap();
+    assert_eq!((summary.built, summary.up_to_date), (2, 0));
+    assert_eq!(summary.written.len(), 4);
+    for dir in ["out", "game"] {
+        assert_eq!(xml_files(&root.join(dir)), ["A.xml", "B.xml"]);
+    }
+
+    // 変更がなければ何も書き込まない
+    let summary = build(&manifest, &BuildOptions::default()).unwrap();
+    assert_eq!((summary.built, summary.up_to_date), (0, 2));
+    assert!(summary.written.is_empty());
+
+    // 出力が変わらない変更は再コンパイルしても書き込まない
+    fs::write(root.join("a.mcl"), format!("// comment\n{}", source("A"))).unwrap();
+    let summary = build(&manifest, &BuildOptions::default()).unwrap();
+    assert_eq!((summary.built, summary.up_to_date), (1, 1));
+    assert!(summary.written.is_empty());
+
+    // ゲーム側で消されたファイルは書き戻す
+    fs::remove_file(root.join("game/B.xml")).unwrap();
+    let summary = build(&manifest, &BuildOptions::default()).unwrap();
+    assert_eq!(summary.written, [root.join("game/B.xml")]);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn removes_stale_outputs_from_every_directory() {
+    let root = temp_dir("stale");
+    write_manifest(&root, &["a.mcl", "b.mcl"]);
+    fs::write(root.join("a.mcl"), source("A")).unwrap();
+    fs::write(root.join("b.mcl"), source("B")).unwrap();
+    let manifest = root.join("miconlang.toml");
+    build(&manifest, &BuildOptions::default()).unwrap();
+
+    // マイコンの名前を変えると古い出力は消える
+    fs::write(root.join("a.mcl"), source("Renamed")).unwrap();
+    build(&manifest, &BuildOptions::default()).unwrap();
+    for dir in ["out", "game"] {
+        assert_eq!(xml_files(&root.join(dir)), ["B.xml", "Renamed.xml"]);
+    }
+
+    // sources から外したファイルの出力も消える
+    write_manifest(&root, &["a.mcl"]);
+    build(&manifest, &BuildOptions::default()).unwrap();
+    for dir in ["out", "game"] {
+        assert_eq!(xml_files(&root.join(dir)), ["Renamed.xml"]);
+    }
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn diagnostics_are_returned_to_the_caller() {
+    let root = temp_dir("diagnostics");
+    write_manifest(&root, &["a.mcl", "bad.mcl"]);
+    fs::write(root.join("a.mcl"), source("A")).unwrap();
+    fs::write(
+        root.join("bad.mcl"),
+        source("Bad").replace("inputs.a", "inputs.z"),
+    )
+    .unwrap();
+    let manifest = root.join("miconlang.toml");
+
+    let summary = build(&manifest, &BuildOptions::default()).unwrap();
+    assert_eq!((summary.built, summary.failed), (1, 1));
+    assert!(matches!(
+        &summary.errors[..],
+        [BuildError::Compile { path }] if path == &root.join("bad.mcl")
+    ));
+    assert_eq!(summary.diagnostics.len(), 1);
+    let source = &summary.diagnostics[0];
+    assert_eq!(source.path, root.join("bad.mcl"));
+    assert_eq!(source.diagnostics.len(), 1);
+    assert_eq!(source.diagnostics[0].error_type.name(), "Unknown Field");
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn editing_a_script_file_rebuilds_its_source() {
+    let root = temp_dir("script");
+    write_manifest(&root, &["a.mcl"]);
+    fs::write(
+        root.join("a.mcl"),
+        source("A").replace(
+            "inputs.a * 2",
+            "lua(file = \"double.lua\") {\n        inputs { x: float = inputs.a }\n        outputs { y: float }\n    }",
+        ),
+    )
+    .unwrap();
+    let script = "function onTick()\n\toutput.setNumber(1, input.getNumber(1) * 2)\nend\n";
+    fs::write(root.join("double.lua"), script).unwrap();
+    let manifest = root.join("miconlang.toml");
+
+    // スクリプトも監視の対象になる
+    assert_eq!(
+        manifest_paths(&manifest),
+        [
+            manifest.clone(),
+            root.join("a.mcl"),
+            root.join("double.lua")
+        ]
+    );
+
+    build(&manifest, &BuildOptions::default()).unwrap();
+    let summary = build(&manifest, &BuildOptions::default()).unwrap();
+    assert_eq!((summary.built, summary.up_to_date), (0, 1));
+
+    // ソースが同じでもスクリプトが変われば作り直す
+    fs::write(root.join("double.lua"), script.replace("* 2", "* 3")).unwrap();
+    let summary = build(&manifest, &BuildOptions::default()).unwrap();
+    assert_eq!((summary.built, summary.up_to_date), (1, 0));
+    assert_eq!(
+        summary.written,
+        [root.join("out/A.xml"), root.join("game/A.xml")]
+    );
+    let xml = fs::read_to_string(root.join("out/A.xml")).unwrap();
+    assert!(xml.contains("input.getNumber(1) * 3"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
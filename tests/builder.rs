@@ -0,0 +1,106 @@
+use sw_miconlang::{
+    layout,
+    microcontroller::{
+        ArithmeticComponent, Component, Link, MicrocontrollerBuilder, NodePosition, NodeType,
+    },
+    to_xml, write_xml,
+    xml_schema::{Components, Microprocessor},
+};
+
+use std::rc::Rc;
+
+// 型、ID、位置、入力 (接続元の ID)
+type ComponentSummary = (u8, u32, (f32, f32), Vec<u32>);
+
+fn components(components: &Components) -> Vec<ComponentSummary> {
+    components
+        .c
+        .iter()
+        .map(|c| {
+            let object = &c.object;
+            let pos = object.pos.as_ref().map_or((0.0, 0.0), |p| {
+                (p.x.unwrap_or_default(), p.y.unwrap_or_default())
+            });
+            let inputs = object
+                .in_map
+                .values()
+                .map(|i| i.component_id.unwrap_or_default())
+                .collect();
+            (c.component_type.unwrap_or_default(), object.id, pos, inputs)
+        })
+        .collect()
+}
+
+#[test]
+fn builder_graph_is_written_as_xml() {
+    let mut builder = MicrocontrollerBuilder::new("Built");
+    builder.size(2, 1);
+    let x = builder.input("x", NodeType::Number, NodePosition { x: 0, z: 0 });
+    let two = builder.component(Component::Arithmetic(ArithmeticComponent::ConstantNumber {
+        value: 2.0,
+    }));
+    let product = builder.component(Component::Arithmetic(ArithmeticComponent::Multiply {
+        input_a: x.try_into().unwrap(),
+        input_b: Link::component(&two, 0).unwrap().try_into().unwrap(),
+    }));
+    let abs = builder.component(Component::Arithmetic(ArithmeticComponent::Abs {
+        input: Link::component(&product, 0).unwrap().try_into().unwrap(),
+    }));
+    builder
+        .output(
+            "y",
+            NodeType::Number,
+            NodePosition { x: 1, z: 0 },
+            Link::component(&abs, 0),
+        )
+        .unwrap();
+
+    let xml = write_xml(&to_xml(&layout(builder.build())).unwrap()).unwrap();
+    let mp: Microprocessor = quick_xml::de::from_str(&xml).unwrap();
+    assert_eq!(
+        (mp.name.as_deref(), mp.width, mp.length),
+        (Some("Built"), 2, 1)
+    );
+
+    // ノードが 1, 2、コンポーネントが 3 から順に ID を持つ
+    let nodes = mp
+        .nodes
+        .n
+        .iter()
+        .map(|n| (n.id, n.node.label.as_deref(), n.node.mode))
+        .collect::<Vec<_>>();
+    assert_eq!(nodes, [(1, Some("x"), Some(1)), (2, Some("y"), None)]);
+    assert_eq!(
+        components(&mp.group.components_bridge),
+        [(2, 1, (0.0, -0.5), vec![]), (3, 2, (3.75, -0.5), vec![5])]
+    );
+    // 定数は入力ノードと同じ列に置き、乗算 -> 絶対値の順に右へ並ぶ
+    assert_eq!(
+        components(&mp.group.components),
+        [
+            (15, 3, (0.0, -1.0), vec![]),
+            (8, 4, (1.25, -0.75), vec![1, 3]),
+            (14, 5, (2.5, -0.5), vec![4]),
+        ]
+    );
+}
+
+#[test]
+fn link_to_missing_component_is_an_error() {
+    let mut builder = MicrocontrollerBuilder::new("Broken");
+    let x = builder.input("x", NodeType::Number, NodePosition { x: 0, z: 0 });
+    // ビルダーに追加していないコンポーネント
+    let outside = Rc::new(Component::Arithmetic(ArithmeticComponent::Abs {
+        input: x.try_into().unwrap(),
+    }));
+    builder
+        .output(
+            "y",
+            NodeType::Number,
+            NodePosition { x: 1, z: 0 },
+            Link::component(&outside, 0),
+        )
+        .unwrap();
+
+    assert!(to_xml(&layout(builder.build())).is_err());
+}
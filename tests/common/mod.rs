@@ -0,0 +1,101 @@
+// 振る舞いのテストで共有する補助関数
+#![allow(dead_code)]
+
+use sw_miconlang::{
+    analyze,
+    microcontroller::{
+        ComponentData as _, LinkIndex, LinkNode, SignalValue, Simulator, StableId,
+        UnpositionedMicrocontroller,
+    },
+    parse,
+};
+
+// 振る舞いのテストで使うマイコン M のソース
+// inputs と outputs は interface のそれぞれの中身、body は logic の中身
+pub fn program(inputs: &str, outputs: &str, body: &str) -> String {
+    format!(
+        "microcontroller M {{\n    interface {{\n        inputs {{\n            {}\n        }}\n        outputs {{\n            {}\n        }}\n    }}\n\n    logic {{\n        {}\n    }}\n}}\n",
+        inputs, outputs, body
+    )
+}
+
+// 数値の入力 a, b と出力 x を持つマイコン
+pub fn logic(body: &str) -> String {
+    program("a: float\n            b: float", "x: float", body)
+}
+
+// マイコンを 1 つだけ定義したソースを解析する
+pub fn analyze_one(source: &str) -> UnpositionedMicrocontroller {
+    let tree = parse(source, "test.mcl")
+        .unwrap_or_else(|d| panic!("source should parse: {:?}", d))
+        .value;
+    let mcs = analyze(&tree, "test.mcl")
+        .unwrap_or_else(|d| panic!("source should analyze: {:?}", d))
+        .value;
+    assert_eq!(mcs.len(), 1);
+    mcs.into_values().next().unwrap()
+}
+
+// dump --stage graph と同じ形式のコンポーネントの一覧 (関数の式も付ける)
+// c3 f(x) pow(x, 2) <- (n0)
+pub fn graph(mc: &UnpositionedMicrocontroller) -> Vec<String> {
+    let index = LinkIndex::new(&mc.nodes, &mc.components);
+    let link_name = |link: &Option<LinkNode>| match link.as_ref().map(|l| (index.get(l), l)) {
+        None => "-".to_owned(),
+        Some((Some(StableId::Node(i)), _)) => format!("n{}", i),
+        Some((Some(StableId::Component(i)), LinkNode::Component(_, port))) => {
+            format!("c{}.{}", i, port)
+        }
+        Some(_) => "?".to_owned(),
+    };
+    mc.components
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let function = c
+                .attrs()
+                .and_then(|attrs| attrs.get("e").cloned())
+                .map_or(String::new(), |e| format!(" {}", e));
+            let inputs = c
+                .input_links_node()
+                .into_iter()
+                .map(link_name)
+                .collect::<Vec<_>>();
+            format!("c{} {}{} <- ({})", i, c, function, inputs.join(", "))
+        })
+        .collect()
+}
+
+// 数値の入力を与えて 1 tick 動かした出力
+pub fn run(mc: &UnpositionedMicrocontroller, inputs: &[(&str, f32)]) -> Vec<(String, f32)> {
+    let mut simulator = Simulator::new(mc).unwrap();
+    for (label, value) in inputs {
+        simulator
+            .set_input(label, SignalValue::Number(*value))
+            .unwrap();
+    }
+    simulator.tick();
+    simulator
+        .outputs()
+        .into_iter()
+        .map(|(label, value)| match value {
+            SignalValue::Number(v) => (label, v),
+            SignalValue::Bool(b) => (label, b as u8 as f32),
+        })
+        .collect()
+}
+
+// エラーと警告の名前、指す範囲のソース、注記
+pub fn diagnostics(source: &str) -> Vec<(&'static str, &str, Vec<String>)> {
+    let tree = parse(source, "test.mcl")
+        .unwrap_or_else(|d| panic!("source should parse: {:?}", d))
+        .value;
+    let diagnostics = match analyze(&tree, "test.mcl") {
+        Ok(mcs) => mcs.warnings,
+        Err(diagnostics) => diagnostics,
+    };
+    diagnostics
+        .into_iter()
+        .map(|d| (d.error_type.name(), &source[d.span], d.notes))
+        .collect()
+}
@@ -0,0 +1,76 @@
+use sw_miconlang::{compile_str, microcontroller::OptLevel};
+
+const SOURCE: &str = r#"
+microcontroller Second {
+    interface {
+        inputs {
+            x: float
+            y: float
+            z: float
+        }
+        outputs {
+            sum: float
+            product: float
+            unused: bool
+        }
+    }
+
+    logic {
+        let a = inputs.x + inputs.y
+        let b = inputs.y * inputs.z
+        outputs.sum = clamp{-1.0, 1.0}(a + b / 2)
+        outputs.product = abs(a * b - -inputs.x)
+        let dead = inputs.z / 3
+    }
+}
+
+microcontroller First {
+    name = "First"
+    size = (2, 2)
+
+    interface {
+        inputs {
+            a: float { name = "Input A" }
+            b: float { name = "Input B" }
+        }
+        outputs {
+            c: float { name = "Output C" }
+            d: float { name = "Output D" }
+        }
+    }
+
+    logic {
+        let tmp = {
+            let hoge = inputs.a + inputs.b / 2
+            hoge * 2
+        }
+        outputs.c = clamp{0.0, 1.0}(tmp * tmp)
+        outputs.d = inputs.b - 1
+    }
+}
+"#;
+
+fn compile(opt_level: OptLevel) -> Vec<(String, String)> {
+    compile_str(SOURCE, "deterministic.mcl", opt_level)
+        .expect("source should compile")
+        .value
+        .into_iter()
+        .collect()
+}
+
+#[test]
+fn output_is_identical_across_compiles() {
+    for opt_level in [OptLevel::None, OptLevel::DeadComponents] {
+        let first = compile(opt_level);
+        assert_eq!(
+            first
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["First", "Second"]
+        );
+        for _ in 0..20 {
+            assert_eq!(compile(opt_level), first);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use sw_miconlang::{
+    parse,
+    syntax::{Element, InterfaceItem, MicrocontrollerElement, MicrocontrollerInterface},
+};
+
+const DOCUMENTED: &str = r#"
+/// 速度の制御
+microcontroller M {
+    /// 表示名
+    name = "Speed"
+
+    interface {
+        inputs {
+            /// 目標の速度
+            target: float {
+                /// エディタに表示する名前
+                name = "Target"
+            }
+        }
+        outputs { throttle: float }
+    }
+
+    logic {
+        outputs.throttle = inputs.target
+    }
+}
+"#;
+
+#[test]
+fn doc_comments_attach_to_fields_and_properties() {
+    let parsed = parse(DOCUMENTED, "doc.mcl").expect("source should parse");
+    assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+
+    let Element::Microcontroller { doc, elements, .. } = &parsed.value.elements[0].inner else {
+        panic!("expected a microcontroller");
+    };
+    assert_eq!(doc.as_deref(), Some("速度の制御"));
+    let field_docs = elements
+        .iter()
+        .filter_map(|e| match &e.inner {
+            MicrocontrollerElement::Field(assignment) => Some(assignment.doc.as_deref()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(field_docs, [Some("表示名")]);
+
+    let node = elements
+        .iter()
+        .find_map(|e| match &e.inner {
+            MicrocontrollerElement::Interface(interface) => match &interface[0].inner {
+                MicrocontrollerInterface::Inputs(items) => match &items[0].inner {
+                    InterfaceItem::Node(node) => Some(node),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .expect("interface should have an input node");
+    assert_eq!(node.doc.as_deref(), Some("目標の速度"));
+    let property_docs = node
+        .fields
+        .iter()
+        .flatten()
+        .map(|f| f.doc.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(property_docs, [Some("エディタに表示する名前")]);
+}
+
+#[test]
+fn other_doc_comments_are_ignored_with_a_warning() {
+    let source = r#"
+/// 定数
+const K = 2
+
+microcontroller M {
+    /// インターフェース
+    interface {
+        inputs { a: float }
+        outputs { b: float }
+    }
+
+    logic {
+        /// 文
+        outputs.b = inputs.a * K /** 式の中 */
+    }
+}
+"#;
+    let parsed = parse(source, "doc.mcl").expect("source should parse");
+    let warnings = parsed
+        .warnings
+        .iter()
+        .map(|d| (d.error_type.name(), &source[d.span.clone()]))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        [
+            ("Misplaced Doc Comment", "/// 定数"),
+            ("Misplaced Doc Comment", "/// インターフェース"),
+            ("Misplaced Doc Comment", "/// 文"),
+            ("Misplaced Doc Comment", "/** 式の中 */"),
+        ]
+    );
+}
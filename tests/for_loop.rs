@@ -0,0 +1,80 @@
+mod common;
+
+use common::{analyze_one, diagnostics, graph, program, run};
+
+#[test]
+fn body_is_unrolled_per_iteration() {
+    let mc = analyze_one(&program(
+        "for i in 0..2 { a[i]: float { name = \"A\" + str(i) } }",
+        "y: float[2]",
+        "for i in 0..2 {\n            let v = inputs.a[i] * 2\n            outputs.y[i] = v + i\n        }",
+    ));
+    let labels = mc.nodes.iter().map(|n| n.label_owned()).collect::<Vec<_>>();
+    assert_eq!(labels, ["A0", "A1", "y[0]", "y[1]"]);
+    // 回ごとに let のスコープが分かれ、i は定数になる
+    assert_eq!(
+        graph(&mc),
+        [
+            "c0 Constant Number <- ()",
+            "c1 Multiply <- (n0, c0.0)",
+            "c2 Constant Number <- ()",
+            "c3 Add <- (c1.0, c2.0)",
+            "c4 Constant Number <- ()",
+            "c5 Multiply <- (n1, c4.0)",
+            "c6 Constant Number <- ()",
+            "c7 Add <- (c5.0, c6.0)",
+        ]
+    );
+    assert_eq!(
+        run(&mc, &[("A0", 1.0), ("A1", 5.0)]),
+        [("y[0]".into(), 2.0), ("y[1]".into(), 11.0)]
+    );
+}
+
+#[test]
+fn errors_name_the_iteration() {
+    assert_eq!(
+        diagnostics(&program(
+            "a: float[3]",
+            "y: float[4]",
+            "for i in 0..4 {\n            outputs.y[i] = inputs.a[i]\n        }",
+        )),
+        [(
+            "Out of Bounds",
+            "i",
+            vec!["In the iteration where `i` is 3".to_owned()]
+        )]
+    );
+}
+
+#[test]
+fn range_must_be_integers() {
+    assert_eq!(
+        diagnostics(&program(
+            "a: float",
+            "y: float",
+            "for i in 0..1.5 {\n            outputs.y = inputs.a\n        }",
+        )),
+        [("Incompatible Types", "1.5", vec![])]
+    );
+}
+
+#[test]
+fn elements_must_be_declared_without_gaps() {
+    assert_eq!(
+        diagnostics(&program(
+            "a: float",
+            "for i in 0..3 { y[i + 1]: float }",
+            "outputs.y[0] = inputs.a",
+        )),
+        [("Missing Element", "y[i + 1]: float", vec![])]
+    );
+    assert_eq!(
+        diagnostics(&program(
+            "a: float",
+            "y: float[2]\n            y[1]: float",
+            "outputs.y[0] = inputs.a",
+        )),
+        [("Element Already Declared", "y[1]: float", vec![])]
+    );
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{analyze_one, diagnostics, graph, logic, run};
+use sw_miconlang::{analyze, parse};
+
+// エラーの名前と補足
+fn helps(source: &str) -> Vec<(&'static str, Option<String>)> {
+    let tree = parse(source, "test.mcl").unwrap().value;
+    analyze(&tree, "test.mcl")
+        .expect_err("source should not analyze")
+        .into_iter()
+        .map(|d| (d.error_type.name(), d.error_type.help()))
+        .collect()
+}
+
+// 関数の定義をファイルの先頭に置く
+fn with_functions(functions: &str, body: &str) -> String {
+    format!("{}\n{}", functions, logic(body))
+}
+
+#[test]
+fn calls_are_inlined_with_props_as_constants() {
+    let mc = analyze_one(&with_functions(
+        "fn scale{k}(v) = v * k",
+        "outputs.x = scale{3}(inputs.a) + scale{k = 0.5}(inputs.b)",
+    ));
+    // 呼び出しごとに本体が展開される
+    assert_eq!(
+        graph(&mc),
+        [
+            "c0 Constant Number <- ()",
+            "c1 Multiply <- (n0, c0.0)",
+            "c2 Constant Number <- ()",
+            "c3 Multiply <- (n1, c2.0)",
+            "c4 Add <- (c1.0, c3.0)",
+        ]
+    );
+    assert_eq!(run(&mc, &[("a", 2.0), ("b", 5.0)]), [("x".into(), 8.5)]);
+}
+
+#[test]
+fn if_becomes_a_switchbox() {
+    let mc = analyze_one(&with_functions(
+        "fn pick(c, a, b) = if c > 0 { a } else { b }",
+        "outputs.x = pick(inputs.a - inputs.b, inputs.a, inputs.b)",
+    ));
+    assert_eq!(
+        graph(&mc),
+        [
+            "c0 Subtract <- (n0, n1)",
+            "c1 Constant Number <- ()",
+            "c2 Greater Than <- (c0.0, c1.0)",
+            "c3 Numerical Switchbox <- (n0, n1, c2.0)",
+        ]
+    );
+    assert_eq!(run(&mc, &[("a", 2.0), ("b", 5.0)]), [("x".into(), 5.0)]);
+    assert_eq!(run(&mc, &[("a", 7.0), ("b", 5.0)]), [("x".into(), 7.0)]);
+}
+
+#[test]
+fn body_cannot_see_the_caller_scope() {
+    assert_eq!(
+        diagnostics(&with_functions(
+            "fn f(v) = v + y",
+            "let y = inputs.b\n        outputs.x = f(inputs.a)",
+        )),
+        [("Unknown Name", "y", vec![])]
+    );
+}
+
+#[test]
+fn recursion_is_reported() {
+    assert_eq!(
+        diagnostics(&with_functions(
+            "fn f(v) = g(v) + 1\nfn g(v) = f(v) * 2",
+            "outputs.x = f(inputs.a)",
+        )),
+        [("Recursive Function", "f(v)", vec![])]
+    );
+}
+
+#[test]
+fn functions_cannot_be_redefined() {
+    assert_eq!(
+        diagnostics(&with_functions(
+            "fn h(v) = v\nfn h(v) = v * 2",
+            "outputs.x = h(inputs.a)",
+        )),
+        [("Function Already Defined", "fn h(v) = v * 2", vec![])]
+    );
+}
+
+#[test]
+fn unknown_function_lists_the_callable_ones() {
+    let source = with_functions(
+        "fn scale{k}(v) = v * k\nfn half(v) = v / 2",
+        "outputs.x = sqrt(inputs.a)",
+    );
+    assert_eq!(
+        diagnostics(&source),
+        [("Unknown Function", "sqrt(inputs.a)", vec![])]
+    );
+    assert_eq!(
+        helps(&source),
+        [(
+            "Unknown Function",
+            Some("Available functions are `clamp`, `abs`, `pid`, `half`, `scale`".to_owned())
+        )]
+    );
+}
+
+#[test]
+fn duplicate_parameters_show_the_signature() {
+    let source = with_functions("fn f{k}(v, k) = v * k", "outputs.x = f{2}(inputs.a, 1)");
+    assert_eq!(diagnostics(&source), [("Duplicate Parameter", "k", vec![])]);
+    assert_eq!(
+        helps(&source),
+        [(
+            "Duplicate Parameter",
+            Some("Expected `f{k}(v, k)`".to_owned())
+        )]
+    );
+
+    let source = with_functions(
+        "fn f(v, w) = v * w",
+        "outputs.x = f(inputs.a, v = inputs.b)",
+    );
+    // v を 2 回渡したので w は足りない
+    let expected = Some("Expected `f(v, w)`".to_owned());
+    assert_eq!(
+        helps(&source),
+        [
+            ("Duplicate Parameter", expected.clone()),
+            ("Missing Parameter", expected)
+        ]
+    );
+}
+
+#[test]
+fn bodies_cannot_use_the_interface() {
+    assert_eq!(
+        diagnostics(&with_functions(
+            "fn f(v) = {\n    outputs.x = v * 3;\n    v\n}",
+            "outputs.x = f(inputs.a)",
+        )),
+        [("Interface in Function", "outputs.x = v * 3", vec![])]
+    );
+    assert_eq!(
+        diagnostics(&with_functions(
+            "fn g(v) = v + inputs.b",
+            "outputs.x = g(inputs.a)",
+        )),
+        [("Interface in Function", "inputs.b", vec![])]
+    );
+
+    // Lua のバックエンドでも同じ
+    let source = with_functions(
+        "fn f(v) = {\n    outputs.x = inputs.b;\n    v\n}",
+        "outputs.x = f(inputs.a)",
+    )
+    .replace(
+        "microcontroller M {",
+        "microcontroller M {\n    backend = \"lua\"",
+    );
+    assert_eq!(
+        diagnostics(&source),
+        [("Interface in Function", "outputs.x = inputs.b", vec![])]
+    );
+}
@@ -0,0 +1,41 @@
+use sw_miconlang::{Diagnostic, WithWarnings, compile_str, microcontroller::OptLevel};
+
+use std::collections::BTreeMap;
+
+type Compiled = Result<WithWarnings<BTreeMap<String, String>>, Vec<Diagnostic>>;
+
+// 長い式の構文解析はデバッグビルドだとテストスレッドのスタックに収まらない
+fn compile(body: String) -> Compiled {
+    let source = format!(
+        "microcontroller M {{\n    interface {{\n        inputs {{ a: float }}\n        outputs {{ y: float }}\n    }}\n\n    logic {{\n        outputs.y = {}\n    }}\n}}\n",
+        body
+    );
+    std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(move || compile_str(&source, "layout.mcl", OptLevel::None))
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[test]
+fn long_chain_is_folded_into_the_canvas() {
+    let chain = (0..70).fold("inputs.a".to_owned(), |e, _| format!("abs({})", e));
+    let compiled = compile(chain).expect("source should compile");
+    assert!(compiled.warnings.is_empty(), "{:?}", compiled.warnings);
+    assert!(compiled.value.contains_key("M"));
+}
+
+#[test]
+fn layout_outside_the_canvas_is_a_warning() {
+    // 同じ層に並ぶ 150 個の絶対値は折り返しても縦に収まらない
+    let compiled = compile(vec!["abs(inputs.a)"; 150].join(" + ")).expect("source should compile");
+    assert!(compiled.value.contains_key("M"));
+    assert!(!compiled.warnings.is_empty());
+    assert!(
+        compiled
+            .warnings
+            .iter()
+            .all(|d| d.error_type.name() == "Out of Canvas")
+    );
+}
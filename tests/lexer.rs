@@ -0,0 +1,54 @@
+use sw_miconlang::{
+    CompileErrorType, lex,
+    lexical::{LexError, Token, tokenize},
+};
+
+fn lex_errors(code: &str) -> Vec<(LexError, &str)> {
+    tokenize(code)
+        .expect_err("code should not tokenize")
+        .into_iter()
+        .map(|(err, span)| (err, &code[span]))
+        .collect()
+}
+
+#[test]
+fn overflowing_integer() {
+    let errors = lex_errors("let x = 99999999999999999999");
+    assert_eq!(errors, [(LexError::NumberOverflow, "99999999999999999999")]);
+
+    // i64 に収まれば整数のまま
+    let tokens = tokenize("9223372036854775807").unwrap();
+    assert!(matches!(tokens[..], [(Token::Int(i64::MAX), _)]));
+}
+
+#[test]
+fn unterminated_strings_point_at_their_first_line() {
+    let errors = lex_errors("let s = \"abc\nlet t = 1\n");
+    assert_eq!(errors, [(LexError::UnterminatedString, "\"abc")]);
+
+    // r#" は "# で閉じる
+    let errors = lex_errors("let s = r#\"abc\" def\nlet t = 1\n");
+    assert_eq!(errors, [(LexError::UnterminatedString, "r#\"abc\" def")]);
+
+    let tokens = tokenize("r#\"say \"hi\"\"#").unwrap();
+    assert!(matches!(&tokens[..], [(Token::String(s), _)] if s == "say \"hi\""));
+}
+
+#[test]
+fn columns_count_characters() {
+    let code = "// 速度の計算\nlet 速度 = 1 $";
+    let diagnostics = lex(code, "unicode.mcl").expect_err("code should not tokenize");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(matches!(
+        diagnostics[0].error_type,
+        CompileErrorType::InvalidToken {
+            error: LexError::StrayCharacter
+        }
+    ));
+
+    let mut report = Vec::new();
+    diagnostics[0].write(code, &mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    // バイト位置なら 16 列目になる
+    assert!(report.contains("unicode.mcl:2:12"), "{}", report);
+}
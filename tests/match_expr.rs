@@ -0,0 +1,115 @@
+mod common;
+
+use common::{analyze_one, diagnostics, graph, logic, run};
+
+#[test]
+fn constant_arms_become_one_function() {
+    let mc = analyze_one(&logic(
+        "outputs.x = match inputs.a { 2 => 20, 1 => 10, 3 => 0, _ => -1 }",
+    ));
+    // 値が 0 の分岐は項を作らない
+    assert_eq!(
+        graph(&mc),
+        [concat!(
+            "c0 f(x, y, z, w, a, b, c, d) ",
+            "10.0*(1-sgn(abs(x-1.0)))+20.0*(1-sgn(abs(x-2.0)))",
+            "+(-1.0)*sgn(abs(x-1.0))*sgn(abs(x-2.0))*sgn(abs(x-3.0))",
+            " <- (n0, -, -, -, -, -, -, -)"
+        )]
+    );
+    for (a, x) in [
+        (1.0, 10.0),
+        (2.0, 20.0),
+        (3.0, 0.0),
+        (4.0, -1.0),
+        (1.5, -1.0),
+    ] {
+        assert_eq!(run(&mc, &[("a", a)]), [("x".into(), x)]);
+    }
+}
+
+#[test]
+fn other_arms_become_a_switchbox_tree() {
+    let mc = analyze_one(&logic(
+        "outputs.x = match inputs.a { 1 => inputs.b, 2 => inputs.b * 2, 3 => inputs.b * 3, _ => 0 }",
+    ));
+    // a < 1.5 で 1 の分岐、残りは a < 2.5 で分け、葉は Equal で既定値と選ぶ
+    assert_eq!(
+        graph(&mc),
+        [
+            "c0 Constant Number <- ()",
+            "c1 Multiply <- (n1, c0.0)",
+            "c2 Constant Number <- ()",
+            "c3 Multiply <- (n1, c2.0)",
+            "c4 Constant Number <- ()",
+            "c5 Constant Number <- ()",
+            "c6 Less Than <- (n0, c5.0)",
+            "c7 Constant Number <- ()",
+            "c8 Equal <- (n0, c7.0)",
+            "c9 Numerical Switchbox <- (n1, c4.0, c8.0)",
+            "c10 Constant Number <- ()",
+            "c11 Less Than <- (n0, c10.0)",
+            "c12 Constant Number <- ()",
+            "c13 Equal <- (n0, c12.0)",
+            "c14 Numerical Switchbox <- (c1.0, c4.0, c13.0)",
+            "c15 Constant Number <- ()",
+            "c16 Equal <- (n0, c15.0)",
+            "c17 Numerical Switchbox <- (c3.0, c4.0, c16.0)",
+            "c18 Numerical Switchbox <- (c14.0, c17.0, c11.0)",
+            "c19 Numerical Switchbox <- (c9.0, c18.0, c6.0)",
+        ]
+    );
+    for (a, x) in [(1.0, 5.0), (2.0, 10.0), (3.0, 15.0), (0.0, 0.0), (2.5, 0.0)] {
+        assert_eq!(run(&mc, &[("a", a), ("b", 5.0)]), [("x".into(), x)]);
+    }
+}
+
+#[test]
+fn unreachable_arms_are_warned() {
+    assert_eq!(
+        diagnostics(&logic(
+            "outputs.x = match inputs.a { 1 => 1, 1.0 => 2, _ => 0, 2 => 3 }"
+        )),
+        [
+            ("Unreachable Arm", "1.0", vec![]),
+            ("Unreachable Arm", "2", vec![]),
+        ]
+    );
+}
+
+#[test]
+fn match_without_wildcard_is_an_error() {
+    let source = logic("outputs.x = match inputs.a { 1 => 1, 2 => 2 }");
+    assert_eq!(
+        diagnostics(&source),
+        [(
+            "Non-Exhaustive Match",
+            "match inputs.a { 1 => 1, 2 => 2 }",
+            vec![]
+        )]
+    );
+}
+
+#[test]
+fn negative_and_constant_patterns() {
+    let mc = analyze_one(&format!(
+        "const LOW = -2\n{}",
+        logic("outputs.x = match inputs.a { -1 => 10, - 0.5 => 5, LOW => 20, _ => 0 }")
+    ));
+    for (a, x) in [(-1.0, 10.0), (-0.5, 5.0), (-2.0, 20.0), (1.0, 0.0)] {
+        assert_eq!(run(&mc, &[("a", a)]), [("x".into(), x)]);
+    }
+}
+
+#[test]
+fn other_names_are_not_patterns() {
+    assert_eq!(
+        diagnostics(&logic(
+            "let y = inputs.b\n        outputs.x = match inputs.a { y => 1, Mode.A => 2, _ => 0 }"
+        )),
+        [
+            ("Unsupported Pattern", "y", vec![]),
+            ("Unsupported Pattern", "Mode.A", vec![]),
+        ]
+    );
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{analyze_one, diagnostics, graph, logic, run};
+
+#[test]
+fn modulo_binds_like_multiplication() {
+    let mc = analyze_one(&logic("outputs.x = inputs.a % inputs.b * 2"));
+    assert_eq!(
+        graph(&mc),
+        [
+            "c0 Modulo (fmod) <- (n0, n1)",
+            "c1 Constant Number <- ()",
+            "c2 Multiply <- (c0.0, c1.0)",
+        ]
+    );
+    assert_eq!(run(&mc, &[("a", 7.0), ("b", 3.0)]), [("x".into(), 2.0)]);
+}
+
+#[test]
+fn power_is_right_associative_and_binds_tighter_than_negation() {
+    let mc = analyze_one(&logic("outputs.x = -inputs.a ** 2"));
+    assert_eq!(
+        graph(&mc),
+        ["c0 f(x) pow(x, 2) <- (n0)", "c1 f(x) -x <- (c0.0)"]
+    );
+    assert_eq!(run(&mc, &[("a", 3.0)]), [("x".into(), -9.0)]);
+
+    let mc = analyze_one(&logic("outputs.x = inputs.a ** inputs.b ** 2"));
+    assert_eq!(
+        graph(&mc),
+        [
+            "c0 f(x) pow(x, 2) <- (n1)",
+            "c1 f(x, y, z) pow(x, y) <- (n0, c0.0, -)",
+        ]
+    );
+    assert_eq!(run(&mc, &[("a", 2.0), ("b", 3.0)]), [("x".into(), 512.0)]);
+}
+
+#[test]
+fn modulo_by_zero() {
+    assert_eq!(
+        diagnostics(&logic("outputs.x = inputs.a % 0")),
+        [("Modulo by Zero", "0", vec![])]
+    );
+    // 定数式では整数の剰余を計算する
+    assert_eq!(
+        diagnostics(&format!("const N = 7 % 0\n{}", logic("outputs.x = N"))),
+        [("Modulo by Zero", "0", vec![])]
+    );
+    let mc = analyze_one(&format!("const N = -7 % 3\n{}", logic("outputs.x = N")));
+    assert_eq!(run(&mc, &[]), [("x".into(), -1.0)]);
+}
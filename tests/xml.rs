@@ -0,0 +1,140 @@
+use sw_miconlang::{
+    CompileErrorType, compile_str, microcontroller::OptLevel, xml_schema::Microprocessor,
+};
+
+use std::fs;
+
+const SOURCE: &str = r#"
+microcontroller Script {
+    backend = "lua"
+
+    interface {
+        inputs { a: float }
+        outputs { b: float }
+    }
+
+    logic {
+        let doubled = inputs.a * 2
+        outputs.b = doubled + 1
+    }
+}
+"#;
+
+fn compile() -> String {
+    compile_str(SOURCE, "xml.mcl", OptLevel::None)
+        .expect("source should compile")
+        .value
+        .remove("Script")
+        .unwrap()
+}
+
+fn scripts(mp: &Microprocessor) -> Vec<&String> {
+    mp.group
+        .components
+        .c
+        .iter()
+        .filter_map(|c| c.object.attrs.as_ref()?.get("@script"))
+        .collect()
+}
+
+#[test]
+fn script_keeps_line_breaks() {
+    let xml = compile();
+    // 改行は XML 宣言の後ろだけで、スクリプトの改行やタブは文字参照になる
+    assert_eq!(xml.lines().count(), 2);
+    assert!(!xml.contains('\t'));
+    assert!(xml.contains("&#10;"));
+    assert!(xml.contains("&#9;"));
+
+    let mp: Microprocessor = quick_xml::de::from_str(&xml).unwrap();
+    let scripts = scripts(&mp);
+    assert_eq!(scripts.len(), 1);
+    let script = scripts[0];
+    assert!(script.starts_with("function onTick()\n"));
+    assert!(script.ends_with("end\n"));
+    assert!(script.lines().count() > 2);
+    assert!(script.lines().any(|line| line.starts_with('\t')));
+}
+
+// lua(file = ...) で読み込むスクリプト
+const LUA_FILE_SOURCE: &str = r#"
+microcontroller Inline {
+    interface {
+        inputs { a: float }
+        outputs { b: float }
+    }
+
+    logic {
+        outputs.b = lua(file = "script.lua") {
+            inputs { x: float = inputs.a }
+            outputs { y: float }
+        }
+    }
+}
+"#;
+
+fn compile_with_script(name: &str, script: &str) -> Result<String, Vec<CompileErrorType>> {
+    let dir = std::env::temp_dir().join(format!("miconlang-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("script.lua"), script).unwrap();
+    let filename = dir.join("inline.mcl");
+    let r = compile_str(LUA_FILE_SOURCE, &filename.to_string_lossy(), OptLevel::None);
+    fs::remove_dir_all(&dir).unwrap();
+    r.map(|mut xml| xml.value.remove("Inline").unwrap())
+        .map_err(|diagnostics| diagnostics.into_iter().map(|d| d.error_type).collect())
+}
+
+#[test]
+fn script_file_keeps_comments_on_their_lines() {
+    let script = "-- doubles the input\nfunction onTick()\n\t-- channel 1\n\toutput.setNumber(1, input.getNumber(1) * 2)\nend\n";
+    let xml = compile_with_script("lua-file", script).unwrap();
+    assert_eq!(xml.lines().count(), 2);
+
+    let mp: Microprocessor = quick_xml::de::from_str(&xml).unwrap();
+    assert_eq!(scripts(&mp), [script]);
+}
+
+#[test]
+fn script_length_counts_character_references() {
+    // 改行を文字参照にすると上限を超える
+    let line = "output.setNumber(1, input.getNumber(1))\n";
+    let script = line.repeat(4000 / line.len());
+    assert!(script.chars().count() < 4096);
+
+    let errors = compile_with_script("lua-length", &script).unwrap_err();
+    assert!(matches!(
+        errors[..],
+        [CompileErrorType::LuaScriptTooLong { limit: 4096, .. }]
+    ));
+}
+
+#[test]
+fn non_ascii_locals_are_renamed() {
+    let source = r#"
+microcontroller Unicode {
+    backend = "lua"
+
+    interface {
+        inputs { a: float }
+        outputs { b: float }
+    }
+
+    logic {
+        let 速度 = inputs.a * 2
+        let _t1 = 速度 + 1
+        outputs.b = 速度 * _t1
+    }
+}
+"#;
+    let xml = compile_str(source, "unicode.mcl", OptLevel::None)
+        .expect("source should compile")
+        .value
+        .remove("Unicode")
+        .unwrap();
+    let mp: Microprocessor = quick_xml::de::from_str(&xml).unwrap();
+    let script = scripts(&mp)[0];
+    assert!(script.is_ascii());
+    assert!(script.contains("local _t1 = input.getNumber(1) * 2\n"));
+    assert!(script.contains("local _t1_ = _t1 + 1\n"));
+    assert!(script.contains("output.setNumber(1, _t1 * _t1_)\n"));
+}